@@ -7,6 +7,7 @@ import pyarrow as pa
 import pyarrow.parquet as pq
 import datetime
 import psutil
+import re
 from workloads.tpch_gen import tpch_gen
 
 
@@ -108,6 +109,56 @@ def build_reader(cwd, reader):
             break
 
 
+CHECKSUM = re.compile(r"^checksum: ([0-9a-f]{16}) \((\d+) rows\)$", re.MULTILINE)
+
+
+def read_checksum(reader, workload) -> str | None:
+    """Runs `reader` on `workload` once with `--checksum`, untimed, and returns the digest
+    it prints, see readers/arrow-rs/src/checksum.rs."""
+    workspace_dir = os.path.dirname(os.path.realpath(__file__))
+    working_dir = os.path.join(workspace_dir, reader["working_dir"])
+
+    executable_path = os.path.join(working_dir, reader["bin"])
+    workload_path = os.path.join(workspace_dir, workload["path"])
+
+    try:
+        output = subprocess.run(
+            f"{executable_path} {workload_path} --checksum",
+            cwd=working_dir,
+            shell=True,
+            check=True,
+            capture_output=True,
+            text=True,
+        ).stdout
+    except subprocess.CalledProcessError as e:
+        print(f"Failed to read the checksum of {workload['path']} with {reader['name']}: {e}")
+        return None
+    m = CHECKSUM.search(output)
+    if not m:
+        print(f"No checksum printed by {reader['name']} for {workload['path']}")
+        return None
+    return m.group(1)
+
+
+def compare_checksums(results):
+    """Marks every result whose digest differs from the one most readers agree on for its
+    workload, and prints the mismatches."""
+    by_workload = {}
+    for rv in results:
+        if rv is not None and "checksum" in rv:
+            by_workload.setdefault(rv["workload"]["name"], []).append(rv)
+    for workload, rvs in by_workload.items():
+        digests = [rv["checksum"] for rv in rvs if rv["checksum"] is not None]
+        expected = max(set(digests), key=digests.count) if digests else None
+        for rv in rvs:
+            rv["checksum_matches"] = expected is not None and rv["checksum"] == expected
+            if not rv["checksum_matches"]:
+                print(
+                    f"Checksum mismatch on {workload}: {rv['reader']['name']} read "
+                    f"{rv['checksum']}, expected {expected}"
+                )
+
+
 def benchmark_one(repeat, reader, workload) -> dict | None:
     workspace_dir = os.path.dirname(os.path.realpath(__file__))
     working_dir = os.path.join(workspace_dir, reader["working_dir"])
@@ -137,6 +188,7 @@ def benchmark(global_config, readers, workloads):
     results = []
 
     repeat = global_config["repeat"]
+    checksum = global_config.get("checksum", False)
 
     for r in readers:
         working_dir = os.path.join(workspace_dir, r["working_dir"])
@@ -145,7 +197,11 @@ def benchmark(global_config, readers, workloads):
             if global_config["clear_page_cache"]:
                 clear_page_cache()
             rv = benchmark_one(repeat, r, f)
+            if rv is not None and checksum:
+                rv["checksum"] = read_checksum(r, f)
             results.append(rv)
+    if checksum:
+        compare_checksums(results)
     return results
 
 
@@ -1,7 +1,7 @@
 use std::{path::Path, time::Duration};
 
 use chrono::Local;
-use format_study::encode_parquet_meta;
+use format_study::{encode_parquet_meta, encode_parquet_meta_with_statistics};
 use parquet::{format::FileMetaData, thrift::TSerializable};
 use serde::Serialize;
 
@@ -19,6 +19,10 @@ struct Config {
     num_columns: usize,
     mimalloc: bool,
     simd: bool,
+    /// Decoded into [`format_study::view::FileMetaDataView`] instead of `FileMetaData`.
+    borrowed: bool,
+    /// Every column chunk has min/max statistics.
+    statistics: bool,
 }
 
 #[derive(Debug, Serialize)]
@@ -41,23 +45,54 @@ fn benchmark(column_size: Option<usize>) -> Vec<BenchmarkResult> {
     let mut results = vec![];
 
     for num_column in columns.iter() {
-        let c = Config {
-            num_columns: *num_column,
-            mimalloc: cfg!(feature = "mimalloc"),
-            simd: cfg!(feature = "simd"),
-        };
-        let result = benchmark_one(&c);
-        results.extend(result);
+        for statistics in [false, true] {
+            // The borrowed decoder is built on the SIMD protocol.
+            for borrowed in [false, true] {
+                if borrowed && !cfg!(feature = "simd") {
+                    continue;
+                }
+                let c = Config {
+                    num_columns: *num_column,
+                    mimalloc: cfg!(feature = "mimalloc"),
+                    simd: cfg!(feature = "simd"),
+                    borrowed,
+                    statistics,
+                };
+                let result = benchmark_one(&c);
+                results.extend(result);
+            }
+        }
     }
     results
 }
 
 fn benchmark_one(c: &Config) -> Vec<BenchmarkResult> {
     let mut results = vec![];
-    let (buf, metadata) = encode_parquet_meta(c.num_columns);
+    let (buf, metadata) = match c.statistics {
+        true => encode_parquet_meta_with_statistics(c.num_columns),
+        false => encode_parquet_meta(c.num_columns),
+    };
     let meta_size = buf.len();
 
     for _ in 0..REPEAT {
+        #[cfg(feature = "simd")]
+        if c.borrowed {
+            let start = std::time::Instant::now();
+            let mut input = format_study::TCompactSimdInputProtocol::new(&buf);
+            let view =
+                format_study::view::FileMetaDataView::read_from_in_protocol(&mut input).unwrap();
+            let elapse = start.elapsed();
+            assert_eq!(metadata, FileMetaData::from(view));
+            results.push(BenchmarkResult {
+                config: c.clone(),
+                measurements: Measurements {
+                    elapse,
+                    meta_data_size: meta_size,
+                },
+            });
+            continue;
+        }
+
         let start = std::time::Instant::now();
         #[cfg(feature = "simd")]
         let decoded_meta = {
@@ -19,6 +19,7 @@ def load_data(input: str):
             'elapse': elapsed_time,
             'meta_data_size': measurements['meta_data_size'],
             'mimalloc': config['mimalloc'],
+            'borrowed': config.get('borrowed', False),
         })
 
     df = pd.DataFrame(records)
@@ -31,7 +32,7 @@ def plot(input: str, output: str):
     df = load_data(input)
     fig, (ax1, ax2, ax3) = plt.subplots(1, 3, figsize=(12, 4))
 
-    sns.barplot(data=df, x='num_columns', y='elapse', ax=ax1, color='tab:blue')
+    sns.barplot(data=df, x='num_columns', y='elapse', hue='borrowed', ax=ax1, color='tab:blue')
     ax1.set_xlabel('Number of columns')
     ax1.set_ylabel('Elapsed time (nanoseconds)', color='tab:blue')
     ax1.tick_params(axis='y', labelcolor='tab:blue')
@@ -45,7 +46,7 @@ def plot(input: str, output: str):
 
     ax3.set_xlabel('Number of columns')
     ax3.set_ylabel('Time per column (nanoseconds)', color='tab:orange')
-    sns.barplot(data=df, x='num_columns', y='time_per_column', ax=ax3, color='tab:orange')
+    sns.barplot(data=df, x='num_columns', y='time_per_column', hue='borrowed', ax=ax3, color='tab:orange')
     ax3.tick_params(axis='y', labelcolor='tab:orange')
 
     fig.tight_layout()
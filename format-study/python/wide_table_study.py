@@ -41,7 +41,7 @@ def benchmark(parquet_dir, output_dir):
         for stats in stats_options:
             input_file = f"{parquet_dir}/{column}col_10b_{stats}.parquet"
             command = [
-                "cargo", "run", "--bin", "wide_table_bench", "--release", "--features", "mimalloc", "--",
+                "cargo", "run", "--bin", "wide_table_bench", "--release", "--features", "mimalloc,simd", "--",
                 "--input", input_file, "--output-dir", output_dir,
             ]
             print(f"Running command: {' '.join(command)}")
@@ -59,6 +59,8 @@ def load_data(input_dir):
                 records.extend(json.load(json_f)[1:])
     df = pd.DataFrame(records)
     df['time_per_column'] = df['metadata_end_to_end_load_time_nanos'] / df['column_cnt']
+    if 'lazy_index_time_nanos' in df:
+        df['lazy_decode_time_nanos'] = df['lazy_index_time_nanos'] + df['lazy_projection_time_nanos']
     df['stats'] = df.apply(lambda x: stats_type_from_file_name(x['file_name']), axis=1)
     return df
 
@@ -123,6 +125,23 @@ def plot_bar(df, ax1, ax2):
     ax2.set_title('Metadata size')
 
 
+def plot_lazy(df, ax):
+    df = df.melt(id_vars=['column_cnt', 'stats'],
+                 value_vars=['simd_decode_time_nanos', 'lazy_decode_time_nanos'],
+                 var_name='decoder', value_name='time')
+    df['decoder'] = df['decoder'].map({'simd_decode_time_nanos': 'full',
+                                       'lazy_decode_time_nanos': 'lazy, projected'})
+    sns.lineplot(data=df, x='column_cnt', y='time', hue='stats', style='decoder',
+                 ax=ax, hue_order=hue_order, markers=True, dashes=True)
+    ax.legend(frameon=False)
+    ax.set_xlabel('Number of columns')
+    ax.set_ylabel('Thrift decode time (ms)')
+    ax.set_xscale('log')
+    ax.set_yscale('log')
+    ax.set_title('Full vs lazy, projected SIMD decode')
+    ax.yaxis.set_major_formatter(plt.FuncFormatter(lambda x, _: '{:.0f}'.format(x/1e6)))
+
+
 def plot_all(input_dir, output_dir):
     df = load_data(input_dir) 
     fig, (ax2, ax1) = plt.subplots(2, 2, figsize=(10, 8))
@@ -141,6 +160,12 @@ def plot_all(input_dir, output_dir):
     plot_bar(df, ax1, ax2)
     fig.savefig(os.path.join(output_dir, "metadata_decode_bar.png"), dpi=300)
 
+    # Only results of a `simd` build have the lazy decoder's times.
+    if df.get('lazy_index_time_nanos', pd.Series(dtype=float)).notna().any():
+        fig, ax = plt.subplots(figsize=(5, 4))
+        plot_lazy(df, ax)
+        fig.savefig(os.path.join(output_dir, "metadata_lazy_decode.png"), dpi=300)
+
 if __name__ == "__main__":
     parser = argparse.ArgumentParser(description="Generate and benchmark Parquet files")
     parser.add_argument("action", choices=["generate", "benchmark", "plot", "plot_trend"], help="Action to perform")
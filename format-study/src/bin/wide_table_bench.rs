@@ -34,6 +34,14 @@ struct Measurements {
     row_group_cnt: usize,
     row_cnt: usize,
     file_name: String,
+    /// With the `simd` feature, the time to decode the whole footer with
+    /// `TCompactSimdInputProtocol`, the baseline for the lazy decoder: the same bytes,
+    /// already in memory, and no conversion to `ParquetMetaData`.
+    simd_decode_time_nanos: Option<usize>,
+    /// With the `simd` feature, the time to index the footer with `LazyFileMetaData`, and
+    /// to decode the first `--projection` columns of every row group from the index.
+    lazy_index_time_nanos: Option<usize>,
+    lazy_projection_time_nanos: Option<usize>,
 }
 
 fn get_metadata_len<R: ChunkReader>(reader: &R) -> usize {
@@ -58,6 +66,54 @@ fn get_column_row_count(meta: &ArrowReaderMetadata) -> (usize, usize, usize) {
     (row_group_cnt, row_cnt as usize, column_cnt)
 }
 
+/// Times of the whole SIMD decode, the lazy index and the lazy projection, with the
+/// footer read into memory beforehand.
+#[cfg(feature = "simd")]
+fn benchmark_lazy(
+    path: impl AsRef<Path>,
+    projection: usize,
+) -> (Option<usize>, Option<usize>, Option<usize>) {
+    use parquet::{file::reader::Length, thrift::TSerializable};
+
+    let reader = std::fs::File::open(path).unwrap();
+    let metadata_len = get_metadata_len(&reader);
+    let footer = reader
+        .get_bytes(
+            reader.len() - FOOTER_SIZE as u64 - metadata_len as u64,
+            metadata_len,
+        )
+        .unwrap();
+
+    let now = std::time::Instant::now();
+    let mut protocol = format_study::TCompactSimdInputProtocol::new(&footer);
+    parquet::format::FileMetaData::read_from_in_protocol(&mut protocol).unwrap();
+    let decode_time = now.elapsed();
+
+    let now = std::time::Instant::now();
+    let lazy = format_study::lazy::LazyFileMetaData::try_new(&footer).unwrap();
+    let index_time = now.elapsed();
+
+    let now = std::time::Instant::now();
+    let columns: Vec<usize> = (0..projection.min(lazy.num_columns())).collect();
+    let row_groups: Vec<usize> = (0..lazy.num_row_groups()).collect();
+    lazy.project(&row_groups, &columns).unwrap();
+    let projection_time = now.elapsed();
+
+    (
+        Some(decode_time.as_nanos() as usize),
+        Some(index_time.as_nanos() as usize),
+        Some(projection_time.as_nanos() as usize),
+    )
+}
+
+#[cfg(not(feature = "simd"))]
+fn benchmark_lazy(
+    _path: impl AsRef<Path>,
+    _projection: usize,
+) -> (Option<usize>, Option<usize>, Option<usize>) {
+    (None, None, None)
+}
+
 fn benchmark_one(path: impl AsRef<Path>) -> Measurements {
     let mut now = std::time::Instant::now();
     let file = std::fs::File::open(&path).unwrap();
@@ -88,6 +144,9 @@ fn benchmark_one(path: impl AsRef<Path>) -> Measurements {
         row_group_cnt,
         row_cnt,
         file_name: path.as_ref().file_name().unwrap().to_str().unwrap().into(),
+        simd_decode_time_nanos: None,
+        lazy_index_time_nanos: None,
+        lazy_projection_time_nanos: None,
     }
 }
 
@@ -95,11 +154,12 @@ fn benchmark(
     file: impl AsRef<Path>,
     repeat: usize,
     flamegraph: bool,
+    projection: usize,
 ) -> (Vec<Measurements>, Option<pprof::Report>) {
     let mut measurements = vec![];
     let mut report: Option<pprof::Report> = None;
     for i in 0..repeat {
-        if flamegraph && i == (repeat - 1) {
+        let m = if flamegraph && i == (repeat - 1) {
             let guard = pprof::ProfilerGuardBuilder::default()
                 .frequency(999)
                 .blocklist(&["libc", "libgcc", "pthread", "vdso"])
@@ -107,10 +167,19 @@ fn benchmark(
                 .unwrap();
             let rt = benchmark_one(&file);
             report = Some(guard.report().build().unwrap());
-            measurements.push(rt)
+            rt
         } else {
-            measurements.push(benchmark_one(&file));
-        }
+            benchmark_one(&file)
+        };
+
+        // A step of its own, outside the profiled region.
+        let (simd_decode, lazy_index, lazy_projection) = benchmark_lazy(&file, projection);
+        measurements.push(Measurements {
+            simd_decode_time_nanos: simd_decode,
+            lazy_index_time_nanos: lazy_index,
+            lazy_projection_time_nanos: lazy_projection,
+            ..m
+        });
     }
 
     (measurements, report)
@@ -143,6 +212,10 @@ struct Args {
 
     #[arg(long)]
     flamegraph: bool,
+
+    /// Number of columns, the first ones, the lazy decoder decodes (`simd` feature only)
+    #[arg(long, default_value_t = 10)]
+    projection: usize,
 }
 
 fn main() {
@@ -151,7 +224,7 @@ fn main() {
         println!("Running with debug assertions, are you building with --release?");
     }
     let args = Args::parse();
-    let (results, report) = benchmark(&args.input, args.repeat, args.flamegraph);
+    let (results, report) = benchmark(&args.input, args.repeat, args.flamegraph, args.projection);
 
     let out_file = save_to_json(args.output_dir, &results);
     if let Some(report) = report {
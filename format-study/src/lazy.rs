@@ -0,0 +1,216 @@
+//! `FileMetaData` decoded only for the row groups and column chunks asked for.
+//!
+//! [`LazyFileMetaData::try_new`] walks the footer once, skipping the schema, but for
+//! counting its leaves, and every column chunk without decoding them and recording where
+//! each starts. Everything else, the top level fields and the rest of each row group, is
+//! small and decoded on the way.
+//! A column chunk is then decoded from its offset as if it started the buffer: the field
+//! ids of a compact struct are deltas from zero at its start, so nothing before it is
+//! needed.
+
+use parquet::format::{
+    ColumnChunk, ColumnOrder, EncryptionAlgorithm, KeyValue, RowGroup, SchemaElement, SortingColumn,
+};
+use parquet::thrift::TSerializable;
+use thrift::protocol::{verify_required_field_exists, TInputProtocol, TType};
+
+use crate::simd_thrift::invalid_data;
+use crate::view::{read_list, read_struct};
+use crate::TCompactSimdInputProtocol;
+
+/// The footer of a parquet file, indexed for decoding a projection of it.
+pub struct LazyFileMetaData<'a> {
+    buf: &'a [u8],
+    pub version: i32,
+    pub num_rows: i64,
+    pub key_value_metadata: Option<Vec<KeyValue>>,
+    pub created_by: Option<String>,
+    pub column_orders: Option<Vec<ColumnOrder>>,
+    pub encryption_algorithm: Option<EncryptionAlgorithm>,
+    pub footer_signing_key_metadata: Option<Vec<u8>>,
+    /// Offset of the schema list.
+    schema: usize,
+    num_columns: usize,
+    row_groups: Vec<LazyRowGroup>,
+}
+
+/// A row group without its column chunks, and where each of them starts.
+struct LazyRowGroup {
+    row_group: RowGroup,
+    columns: Vec<usize>,
+}
+
+impl<'a> LazyFileMetaData<'a> {
+    /// Indexes the encoded `FileMetaData` in `buf`.
+    pub fn try_new(buf: &'a [u8]) -> thrift::Result<Self> {
+        let i = &mut TCompactSimdInputProtocol::new(buf);
+        let mut f_1 = None;
+        let mut f_2 = None;
+        let mut f_3 = None;
+        let mut f_4 = None;
+        let mut f_5 = None;
+        let mut f_6 = None;
+        let mut f_7 = None;
+        let mut f_8 = None;
+        let mut f_9 = None;
+        read_struct(i, |i, id, field_type| {
+            match id {
+                1 => f_1 = Some(i.read_i32()?),
+                2 => f_2 = Some((position(buf, i), count_leaves(i)?)),
+                3 => f_3 = Some(i.read_i64()?),
+                4 => f_4 = Some(read_list(i, |i| LazyRowGroup::index(buf, i))?),
+                5 => f_5 = Some(read_list(i, |i| KeyValue::read_from_in_protocol(i))?),
+                6 => f_6 = Some(i.read_string()?),
+                7 => f_7 = Some(read_list(i, |i| ColumnOrder::read_from_in_protocol(i))?),
+                8 => f_8 = Some(EncryptionAlgorithm::read_from_in_protocol(i)?),
+                9 => f_9 = Some(i.read_bytes()?),
+                _ => i.skip(field_type)?,
+            }
+            Ok(())
+        })?;
+        verify_required_field_exists("FileMetaData.version", &f_1)?;
+        verify_required_field_exists("FileMetaData.schema", &f_2)?;
+        verify_required_field_exists("FileMetaData.num_rows", &f_3)?;
+        verify_required_field_exists("FileMetaData.row_groups", &f_4)?;
+        let (schema, num_columns) = f_2.unwrap();
+        Ok(Self {
+            buf,
+            version: f_1.unwrap(),
+            num_rows: f_3.unwrap(),
+            key_value_metadata: f_5,
+            created_by: f_6,
+            column_orders: f_7,
+            encryption_algorithm: f_8,
+            footer_signing_key_metadata: f_9,
+            schema,
+            num_columns,
+            row_groups: f_4.unwrap(),
+        })
+    }
+
+    /// Decodes the whole schema.
+    pub fn schema(&self) -> thrift::Result<Vec<SchemaElement>> {
+        read_list(
+            &mut TCompactSimdInputProtocol::new(&self.buf[self.schema..]),
+            |i| SchemaElement::read_from_in_protocol(i),
+        )
+    }
+
+    pub fn num_row_groups(&self) -> usize {
+        self.row_groups.len()
+    }
+
+    /// The number of leaf columns in the schema, which every row group has a column chunk
+    /// for.
+    pub fn num_columns(&self) -> usize {
+        self.num_columns
+    }
+
+    /// Decodes row group `i` with only the column chunks of `columns`, in that order.
+    pub fn row_group(&self, i: usize, columns: &[usize]) -> thrift::Result<RowGroup> {
+        let lazy = self.row_groups.get(i).ok_or_else(|| {
+            invalid_data(format!(
+                "file has {} row groups, cannot read row group {}",
+                self.row_groups.len(),
+                i
+            ))
+        })?;
+        let columns = columns
+            .iter()
+            .map(|&c| {
+                let offset = *lazy.columns.get(c).ok_or_else(|| {
+                    invalid_data(format!(
+                        "row group {} has {} columns, cannot read column {}",
+                        i,
+                        lazy.columns.len(),
+                        c
+                    ))
+                })?;
+                ColumnChunk::read_from_in_protocol(&mut TCompactSimdInputProtocol::new(
+                    &self.buf[offset..],
+                ))
+            })
+            .collect::<thrift::Result<_>>()?;
+        Ok(RowGroup {
+            columns,
+            ..lazy.row_group.clone()
+        })
+    }
+
+    /// Decodes `row_groups` with only the column chunks of `columns`.
+    pub fn project(
+        &self,
+        row_groups: &[usize],
+        columns: &[usize],
+    ) -> thrift::Result<Vec<RowGroup>> {
+        row_groups
+            .iter()
+            .map(|&i| self.row_group(i, columns))
+            .collect()
+    }
+}
+
+impl LazyRowGroup {
+    fn index(buf: &[u8], i: &mut TCompactSimdInputProtocol) -> thrift::Result<Self> {
+        let mut f_1 = None;
+        let mut f_2 = None;
+        let mut f_3 = None;
+        let mut f_4 = None;
+        let mut f_5 = None;
+        let mut f_6 = None;
+        let mut f_7 = None;
+        read_struct(i, |i, id, field_type| {
+            match id {
+                1 => {
+                    f_1 = Some(read_list(i, |i| {
+                        let offset = position(buf, i);
+                        i.skip(TType::Struct)?;
+                        Ok(offset)
+                    })?)
+                }
+                2 => f_2 = Some(i.read_i64()?),
+                3 => f_3 = Some(i.read_i64()?),
+                4 => f_4 = Some(read_list(i, |i| SortingColumn::read_from_in_protocol(i))?),
+                5 => f_5 = Some(i.read_i64()?),
+                6 => f_6 = Some(i.read_i64()?),
+                7 => f_7 = Some(i.read_i16()?),
+                _ => i.skip(field_type)?,
+            }
+            Ok(())
+        })?;
+        verify_required_field_exists("RowGroup.columns", &f_1)?;
+        verify_required_field_exists("RowGroup.total_byte_size", &f_2)?;
+        verify_required_field_exists("RowGroup.num_rows", &f_3)?;
+        Ok(Self {
+            row_group: RowGroup {
+                columns: vec![],
+                total_byte_size: f_2.unwrap(),
+                num_rows: f_3.unwrap(),
+                sorting_columns: f_4,
+                file_offset: f_5,
+                total_compressed_size: f_6,
+                ordinal: f_7,
+            },
+            columns: f_1.unwrap(),
+        })
+    }
+}
+
+/// Offset of the next byte `i` reads from `buf`.
+fn position(buf: &[u8], i: &TCompactSimdInputProtocol) -> usize {
+    buf.len() - i.as_slice().len()
+}
+
+/// Counts the leaves of the schema list, the elements with a physical `type_`, skipping
+/// everything else.
+fn count_leaves(i: &mut TCompactSimdInputProtocol) -> thrift::Result<usize> {
+    let is_leaf = read_list(i, |i| {
+        let mut has_type = false;
+        read_struct(i, |i, id, field_type| {
+            has_type |= id == 1;
+            i.skip(field_type)
+        })?;
+        Ok(has_type)
+    })?;
+    Ok(is_leaf.into_iter().filter(|&leaf| leaf).count())
+}
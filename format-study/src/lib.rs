@@ -2,7 +2,7 @@ use arrow::datatypes::{DataType, Field, Fields, Schema};
 use arrow::ipc::writer::{IpcDataGenerator, IpcWriteOptions};
 use parquet::format::{
     ColumnChunk, ColumnMetaData, CompressionCodec, Encoding, FieldRepetitionType, FileMetaData,
-    RowGroup, SchemaElement, Type,
+    RowGroup, SchemaElement, Statistics, Type,
 };
 use parquet::thrift::TSerializable;
 use rand::rngs::StdRng;
@@ -15,9 +15,24 @@ mod simd_thrift;
 #[cfg(feature = "simd")]
 pub use simd_thrift::TCompactSimdInputProtocol;
 
+#[cfg(feature = "simd")]
+pub mod lazy;
+
+#[cfg(feature = "simd")]
+pub mod view;
+
 const NUM_ROW_GROUPS: usize = 10;
 
 pub fn encode_parquet_meta(num_columns: usize) -> (Vec<u8>, FileMetaData) {
+    encode_meta(num_columns, false)
+}
+
+/// [`encode_parquet_meta`] with min/max [`Statistics`] in every column chunk.
+pub fn encode_parquet_meta_with_statistics(num_columns: usize) -> (Vec<u8>, FileMetaData) {
+    encode_meta(num_columns, true)
+}
+
+fn encode_meta(num_columns: usize, statistics: bool) -> (Vec<u8>, FileMetaData) {
     let mut rng = StdRng::seed_from_u64(42);
     let mut schema = Vec::with_capacity(num_columns + 1);
 
@@ -67,7 +82,16 @@ pub fn encode_parquet_meta(num_columns: usize) -> (Vec<u8>, FileMetaData) {
                         data_page_offset: rng.gen(),
                         index_page_offset: Some(rng.gen()),
                         dictionary_page_offset: Some(rng.gen()),
-                        statistics: None,
+                        statistics: statistics.then(|| Statistics {
+                            max: None,
+                            min: None,
+                            null_count: Some(0),
+                            distinct_count: None,
+                            max_value: Some(rng.gen::<f32>().to_le_bytes().to_vec()),
+                            min_value: Some(rng.gen::<f32>().to_le_bytes().to_vec()),
+                            is_max_value_exact: Some(true),
+                            is_min_value_exact: Some(true),
+                        }),
                         encoding_stats: None,
                         bloom_filter_offset: None,
                         bloom_filter_length: None,
@@ -1,12 +1,25 @@
 use thrift::protocol::{
     TFieldIdentifier, TInputProtocol, TListIdentifier, TMapIdentifier, TMessageIdentifier,
-    TSetIdentifier, TStructIdentifier, TType,
+    TMessageType, TSetIdentifier, TStructIdentifier, TType,
 };
 use varint_simd::SignedVarIntTarget;
 
+const COMPACT_PROTOCOL_ID: u8 = 0x82;
+const COMPACT_VERSION: u8 = 0x01;
+const COMPACT_VERSION_MASK: u8 = 0x1F;
+
 /// A more performant implementation of [`TCompactInputProtocol`] that reads a slice
 ///
+/// Every read is bounds checked: truncated input fails with
+/// [`TransportErrorKind::EndOfFile`] and malformed input with
+/// [`ProtocolErrorKind::InvalidData`], so it can be pointed at untrusted footers.
+///
 /// [`TCompactInputProtocol`]: thrift::protocol::TCompactInputProtocol
+/// [`TransportErrorKind::EndOfFile`]: thrift::TransportErrorKind::EndOfFile
+/// [`ProtocolErrorKind::InvalidData`]: thrift::ProtocolErrorKind::InvalidData
+///
+/// The whole compact protocol is supported, messages, sets and maps included. Skipping a
+/// field reads nothing into memory, strings are skipped as the binary they may be.
 pub struct TCompactSimdInputProtocol<'a> {
     buf: &'a [u8],
     // Identifier of the last field deserialized for a struct.
@@ -33,15 +46,48 @@ impl<'a> TCompactSimdInputProtocol<'a> {
         self.buf
     }
 
+    /// Reads a binary as a slice of the input, without copying it.
+    pub fn read_borrowed_bytes(&mut self) -> thrift::Result<&'a [u8]> {
+        let len = self.read_vlq()? as usize;
+        if len > self.buf.len() {
+            return Err(eof_error());
+        }
+        let (ret, rest) = self.buf.split_at(len);
+        self.buf = rest;
+        Ok(ret)
+    }
+
+    /// Reads a string as a slice of the input, without copying it.
+    pub fn read_borrowed_str(&mut self) -> thrift::Result<&'a str> {
+        let bytes = self.read_borrowed_bytes()?;
+        std::str::from_utf8(bytes).map_err(|e| invalid_data(e.to_string()))
+    }
+
     fn read_vlq(&mut self) -> thrift::Result<u64> {
-        let (val, shift) = varint_simd::decode(&self.buf).unwrap();
-        self.buf = &self.buf[shift..];
-        Ok(val)
+        self.read_varint(varint_simd::decode::<u64>)
     }
 
     fn read_zig_zag<T: SignedVarIntTarget>(&mut self) -> thrift::Result<T> {
-        let (val, shift): (T, usize) = varint_simd::decode_zigzag(&self.buf).unwrap();
-        self.buf = &self.buf[shift..];
+        self.read_varint(varint_simd::decode_zigzag::<T>)
+    }
+
+    /// Runs a SIMD varint `decode`, which loads 16 bytes at a time. With fewer left, they
+    /// are copied into a zero padded block first; a varint cut short by the end of the
+    /// buffer is then terminated by the padding, and its length runs past the buffer.
+    #[inline]
+    fn read_varint<T, E>(
+        &mut self,
+        decode: impl Fn(&[u8]) -> Result<(T, usize), E>,
+    ) -> thrift::Result<T> {
+        let decoded = if self.buf.len() >= 16 {
+            decode(self.buf)
+        } else {
+            let mut block = [0u8; 16];
+            block[..self.buf.len()].copy_from_slice(self.buf);
+            decode(&block)
+        };
+        let (val, shift) = decoded.map_err(|_| invalid_data("varint overflows its type"))?;
+        self.buf = self.buf.get(shift..).ok_or_else(eof_error)?;
         Ok(val)
     }
 
@@ -54,20 +100,58 @@ impl<'a> TCompactSimdInputProtocol<'a> {
             // high bits set high if count and type encoded separately
             possible_element_count as i32
         } else {
-            self.read_vlq()? as _
+            i32::try_from(self.read_vlq()?).map_err(|_| invalid_data("list size overflows i32"))?
         };
+        // Every element takes at least one byte, so a larger count cannot be satisfied, and
+        // would otherwise be trusted for the capacity of the decoded Vec.
+        if element_count as usize > self.buf.len() {
+            return Err(eof_error());
+        }
 
         Ok((element_type, element_count))
     }
+
+    fn skip_bytes(&mut self, len: usize) -> thrift::Result<()> {
+        self.buf = self.buf.get(len..).ok_or_else(eof_error)?;
+        Ok(())
+    }
 }
 
 impl<'a> TInputProtocol for TCompactSimdInputProtocol<'a> {
     fn read_message_begin(&mut self) -> thrift::Result<TMessageIdentifier> {
-        unimplemented!()
+        let compact_id = self.read_byte()?;
+        if compact_id != COMPACT_PROTOCOL_ID {
+            return Err(bad_version(format!(
+                "invalid compact protocol header {:?}",
+                compact_id
+            )));
+        }
+
+        let type_and_byte = self.read_byte()?;
+        let received_version = type_and_byte & COMPACT_VERSION_MASK;
+        if received_version != COMPACT_VERSION {
+            return Err(bad_version(format!(
+                "cannot process compact protocol version {:?}",
+                received_version
+            )));
+        }
+
+        let message_type = TMessageType::try_from(type_and_byte >> 5)?;
+        // the writer sends the signed sequence number as a u32, without zigzag encoding
+        let sequence_number = self.read_varint(varint_simd::decode::<u32>)? as i32;
+        let service_call_name = self.read_string()?;
+
+        self.last_read_field_id = 0;
+
+        Ok(TMessageIdentifier::new(
+            service_call_name,
+            message_type,
+            sequence_number,
+        ))
     }
 
     fn read_message_end(&mut self) -> thrift::Result<()> {
-        unimplemented!()
+        Ok(())
     }
 
     fn read_struct_begin(&mut self) -> thrift::Result<Option<TStructIdentifier>> {
@@ -80,7 +164,7 @@ impl<'a> TInputProtocol for TCompactSimdInputProtocol<'a> {
         self.last_read_field_id = self
             .read_field_id_stack
             .pop()
-            .expect("should have previous field ids");
+            .ok_or_else(|| invalid_data("struct end without a struct begin"))?;
         Ok(())
     }
 
@@ -112,7 +196,10 @@ impl<'a> TInputProtocol for TCompactSimdInputProtocol<'a> {
             ),
             _ => {
                 if field_delta != 0 {
-                    self.last_read_field_id += field_delta as i16;
+                    self.last_read_field_id = self
+                        .last_read_field_id
+                        .checked_add(field_delta as i16)
+                        .ok_or_else(|| invalid_data("field id overflows i16"))?;
                 } else {
                     self.last_read_field_id = self.read_i16()?;
                 };
@@ -138,20 +225,14 @@ impl<'a> TInputProtocol for TCompactSimdInputProtocol<'a> {
                 match b {
                     0x01 => Ok(true),
                     0x02 => Ok(false),
-                    unkn => Err(thrift::Error::Protocol(thrift::ProtocolError {
-                        kind: thrift::ProtocolErrorKind::InvalidData,
-                        message: format!("cannot convert {} into bool", unkn),
-                    })),
+                    unkn => Err(invalid_data(format!("cannot convert {} into bool", unkn))),
                 }
             }
         }
     }
 
     fn read_bytes(&mut self) -> thrift::Result<Vec<u8>> {
-        let len = self.read_vlq()? as usize;
-        let ret = self.buf.get(..len).ok_or_else(eof_error)?.to_vec();
-        self.buf = &self.buf[len..];
-        Ok(ret)
+        self.read_borrowed_bytes().map(<[u8]>::to_vec)
     }
 
     fn read_i8(&mut self) -> thrift::Result<i8> {
@@ -171,9 +252,9 @@ impl<'a> TInputProtocol for TCompactSimdInputProtocol<'a> {
     }
 
     fn read_double(&mut self) -> thrift::Result<f64> {
-        let slice = (self.buf[..8]).try_into().unwrap();
-        self.buf = &self.buf[8..];
-        Ok(f64::from_le_bytes(slice))
+        let (bytes, rest) = self.buf.split_first_chunk::<8>().ok_or_else(eof_error)?;
+        self.buf = rest;
+        Ok(f64::from_le_bytes(*bytes))
     }
 
     fn read_string(&mut self) -> thrift::Result<String> {
@@ -191,25 +272,93 @@ impl<'a> TInputProtocol for TCompactSimdInputProtocol<'a> {
     }
 
     fn read_set_begin(&mut self) -> thrift::Result<TSetIdentifier> {
-        unimplemented!()
+        let (element_type, element_count) = self.read_list_set_begin()?;
+        Ok(TSetIdentifier::new(element_type, element_count))
     }
 
     fn read_set_end(&mut self) -> thrift::Result<()> {
-        unimplemented!()
+        Ok(())
     }
 
     fn read_map_begin(&mut self) -> thrift::Result<TMapIdentifier> {
-        unimplemented!()
+        let element_count = i32::try_from(self.read_varint(varint_simd::decode::<u32>)?)
+            .map_err(|_| invalid_data("map size overflows i32"))?;
+        if element_count == 0 {
+            return Ok(TMapIdentifier::new(None, None, 0));
+        }
+        let type_header = self.read_byte()?;
+        let key_type = collection_u8_to_type((type_header & 0xF0) >> 4)?;
+        let val_type = collection_u8_to_type(type_header & 0x0F)?;
+        // Every key and value takes at least one byte, as with lists.
+        if element_count as usize > self.buf.len() / 2 {
+            return Err(eof_error());
+        }
+        Ok(TMapIdentifier::new(key_type, val_type, element_count))
     }
 
     fn read_map_end(&mut self) -> thrift::Result<()> {
         Ok(())
     }
 
+    fn skip_till_depth(&mut self, field_type: TType, depth: i8) -> thrift::Result<()> {
+        if depth == 0 {
+            return Err(thrift::Error::Protocol(thrift::ProtocolError {
+                kind: thrift::ProtocolErrorKind::DepthLimit,
+                message: format!("cannot parse past {:?}", field_type),
+            }));
+        }
+
+        match field_type {
+            TType::Bool => self.read_bool().map(|_| ()),
+            TType::I08 => self.read_byte().map(|_| ()),
+            TType::I16 => self.read_i16().map(|_| ()),
+            TType::I32 => self.read_i32().map(|_| ()),
+            TType::I64 => self.read_i64().map(|_| ()),
+            TType::Double => self.skip_bytes(8),
+            TType::String => {
+                let len = self.read_vlq()? as usize;
+                self.skip_bytes(len)
+            }
+            TType::Struct => {
+                self.read_struct_begin()?;
+                loop {
+                    let field_ident = self.read_field_begin()?;
+                    if field_ident.field_type == TType::Stop {
+                        break;
+                    }
+                    self.skip_till_depth(field_ident.field_type, depth - 1)?;
+                }
+                self.read_struct_end()
+            }
+            TType::List | TType::Set => {
+                let (element_type, element_count) = self.read_list_set_begin()?;
+                for _ in 0..element_count {
+                    self.skip_till_depth(element_type, depth - 1)?;
+                }
+                Ok(())
+            }
+            TType::Map => {
+                let map_ident = self.read_map_begin()?;
+                if let (Some(key_type), Some(val_type)) = (map_ident.key_type, map_ident.value_type)
+                {
+                    for _ in 0..map_ident.size {
+                        self.skip_till_depth(key_type, depth - 1)?;
+                        self.skip_till_depth(val_type, depth - 1)?;
+                    }
+                }
+                Ok(())
+            }
+            u => Err(thrift::Error::Protocol(thrift::ProtocolError {
+                kind: thrift::ProtocolErrorKind::Unknown,
+                message: format!("cannot skip field type {:?}", &u),
+            })),
+        }
+    }
+
     #[inline]
     fn read_byte(&mut self) -> thrift::Result<u8> {
-        let ret = *self.buf.first().ok_or_else(eof_error)?;
-        self.buf = &self.buf[1..];
+        let (&ret, rest) = self.buf.split_first().ok_or_else(eof_error)?;
+        self.buf = rest;
         Ok(ret)
     }
 }
@@ -234,13 +383,24 @@ fn u8_to_type(b: u8) -> thrift::Result<TType> {
         0x0A => Ok(TType::Set),
         0x0B => Ok(TType::Map),
         0x0C => Ok(TType::Struct),
-        unkn => Err(thrift::Error::Protocol(thrift::ProtocolError {
-            kind: thrift::ProtocolErrorKind::InvalidData,
-            message: format!("cannot convert {} into TType", unkn),
-        })),
+        unkn => Err(invalid_data(format!("cannot convert {} into TType", unkn))),
     }
 }
 
+pub(crate) fn invalid_data(message: impl Into<String>) -> thrift::Error {
+    thrift::Error::Protocol(thrift::ProtocolError {
+        kind: thrift::ProtocolErrorKind::InvalidData,
+        message: message.into(),
+    })
+}
+
+fn bad_version(message: String) -> thrift::Error {
+    thrift::Error::Protocol(thrift::ProtocolError {
+        kind: thrift::ProtocolErrorKind::BadVersion,
+        message,
+    })
+}
+
 fn eof_error() -> thrift::Error {
     thrift::Error::Transport(thrift::TransportError {
         kind: thrift::TransportErrorKind::EndOfFile,
@@ -0,0 +1,534 @@
+//! `FileMetaData` decoded without copying its strings and binaries.
+//!
+//! Every column name, path and statistics value in the owned [`FileMetaData`] is its own
+//! allocation. The views here borrow them from the footer buffer instead, leaving only
+//! the `Vec`s of nested structs and lists to allocate. Structs that rarely appear in a
+//! footer, or hold nothing to borrow, are decoded into their owned `parquet::format` type.
+
+use parquet::format::{
+    ColumnChunk, ColumnCryptoMetaData, ColumnMetaData, ColumnOrder, CompressionCodec,
+    ConvertedType, Encoding, EncryptionAlgorithm, FieldRepetitionType, FileMetaData, KeyValue,
+    LogicalType, PageEncodingStats, RowGroup, SchemaElement, SortingColumn, Statistics, Type,
+};
+use parquet::thrift::TSerializable;
+use thrift::protocol::{verify_required_field_exists, TInputProtocol, TType};
+
+use crate::TCompactSimdInputProtocol;
+
+/// Reads a list with `read` for each element.
+pub(crate) fn read_list<'a, T>(
+    i: &mut TCompactSimdInputProtocol<'a>,
+    mut read: impl FnMut(&mut TCompactSimdInputProtocol<'a>) -> thrift::Result<T>,
+) -> thrift::Result<Vec<T>> {
+    let list_ident = i.read_list_begin()?;
+    let mut values = Vec::with_capacity(list_ident.size as usize);
+    for _ in 0..list_ident.size {
+        values.push(read(i)?);
+    }
+    i.read_list_end()?;
+    Ok(values)
+}
+
+/// Reads the fields of a struct, calling `read` with the id and type of each.
+pub(crate) fn read_struct<'a>(
+    i: &mut TCompactSimdInputProtocol<'a>,
+    mut read: impl FnMut(&mut TCompactSimdInputProtocol<'a>, i16, TType) -> thrift::Result<()>,
+) -> thrift::Result<()> {
+    i.read_struct_begin()?;
+    loop {
+        let field_ident = i.read_field_begin()?;
+        if field_ident.field_type == TType::Stop {
+            break;
+        }
+        // The compact protocol always sets the id.
+        let id = field_ident.id.unwrap_or_default();
+        read(i, id, field_ident.field_type)?;
+        i.read_field_end()?;
+    }
+    i.read_struct_end()
+}
+
+/// [`FileMetaData`] borrowing from the footer it was decoded from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileMetaDataView<'a> {
+    pub version: i32,
+    pub schema: Vec<SchemaElementView<'a>>,
+    pub num_rows: i64,
+    pub row_groups: Vec<RowGroupView<'a>>,
+    pub key_value_metadata: Option<Vec<KeyValueView<'a>>>,
+    pub created_by: Option<&'a str>,
+    pub column_orders: Option<Vec<ColumnOrder>>,
+    pub encryption_algorithm: Option<EncryptionAlgorithm>,
+    pub footer_signing_key_metadata: Option<&'a [u8]>,
+}
+
+impl<'a> FileMetaDataView<'a> {
+    pub fn read_from_in_protocol(i: &mut TCompactSimdInputProtocol<'a>) -> thrift::Result<Self> {
+        let mut f_1 = None;
+        let mut f_2 = None;
+        let mut f_3 = None;
+        let mut f_4 = None;
+        let mut f_5 = None;
+        let mut f_6 = None;
+        let mut f_7 = None;
+        let mut f_8 = None;
+        let mut f_9 = None;
+        read_struct(i, |i, id, field_type| {
+            match id {
+                1 => f_1 = Some(i.read_i32()?),
+                2 => f_2 = Some(read_list(i, SchemaElementView::read_from_in_protocol)?),
+                3 => f_3 = Some(i.read_i64()?),
+                4 => f_4 = Some(read_list(i, RowGroupView::read_from_in_protocol)?),
+                5 => f_5 = Some(read_list(i, KeyValueView::read_from_in_protocol)?),
+                6 => f_6 = Some(i.read_borrowed_str()?),
+                7 => f_7 = Some(read_list(i, |i| ColumnOrder::read_from_in_protocol(i))?),
+                8 => f_8 = Some(EncryptionAlgorithm::read_from_in_protocol(i)?),
+                9 => f_9 = Some(i.read_borrowed_bytes()?),
+                _ => i.skip(field_type)?,
+            }
+            Ok(())
+        })?;
+        verify_required_field_exists("FileMetaData.version", &f_1)?;
+        verify_required_field_exists("FileMetaData.schema", &f_2)?;
+        verify_required_field_exists("FileMetaData.num_rows", &f_3)?;
+        verify_required_field_exists("FileMetaData.row_groups", &f_4)?;
+        Ok(Self {
+            version: f_1.unwrap(),
+            schema: f_2.unwrap(),
+            num_rows: f_3.unwrap(),
+            row_groups: f_4.unwrap(),
+            key_value_metadata: f_5,
+            created_by: f_6,
+            column_orders: f_7,
+            encryption_algorithm: f_8,
+            footer_signing_key_metadata: f_9,
+        })
+    }
+}
+
+impl From<FileMetaDataView<'_>> for FileMetaData {
+    fn from(view: FileMetaDataView<'_>) -> Self {
+        FileMetaData {
+            version: view.version,
+            schema: view.schema.into_iter().map(Into::into).collect(),
+            num_rows: view.num_rows,
+            row_groups: view.row_groups.into_iter().map(Into::into).collect(),
+            key_value_metadata: view
+                .key_value_metadata
+                .map(|kvs| kvs.into_iter().map(Into::into).collect()),
+            created_by: view.created_by.map(Into::into),
+            column_orders: view.column_orders,
+            encryption_algorithm: view.encryption_algorithm,
+            footer_signing_key_metadata: view.footer_signing_key_metadata.map(Into::into),
+        }
+    }
+}
+
+/// [`SchemaElement`] borrowing its name.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SchemaElementView<'a> {
+    pub type_: Option<Type>,
+    pub type_length: Option<i32>,
+    pub repetition_type: Option<FieldRepetitionType>,
+    pub name: &'a str,
+    pub num_children: Option<i32>,
+    pub converted_type: Option<ConvertedType>,
+    pub scale: Option<i32>,
+    pub precision: Option<i32>,
+    pub field_id: Option<i32>,
+    pub logical_type: Option<LogicalType>,
+}
+
+impl<'a> SchemaElementView<'a> {
+    pub fn read_from_in_protocol(i: &mut TCompactSimdInputProtocol<'a>) -> thrift::Result<Self> {
+        let mut f_1 = None;
+        let mut f_2 = None;
+        let mut f_3 = None;
+        let mut f_4 = None;
+        let mut f_5 = None;
+        let mut f_6 = None;
+        let mut f_7 = None;
+        let mut f_8 = None;
+        let mut f_9 = None;
+        let mut f_10 = None;
+        read_struct(i, |i, id, field_type| {
+            match id {
+                1 => f_1 = Some(Type::read_from_in_protocol(i)?),
+                2 => f_2 = Some(i.read_i32()?),
+                3 => f_3 = Some(FieldRepetitionType::read_from_in_protocol(i)?),
+                4 => f_4 = Some(i.read_borrowed_str()?),
+                5 => f_5 = Some(i.read_i32()?),
+                6 => f_6 = Some(ConvertedType::read_from_in_protocol(i)?),
+                7 => f_7 = Some(i.read_i32()?),
+                8 => f_8 = Some(i.read_i32()?),
+                9 => f_9 = Some(i.read_i32()?),
+                10 => f_10 = Some(LogicalType::read_from_in_protocol(i)?),
+                _ => i.skip(field_type)?,
+            }
+            Ok(())
+        })?;
+        verify_required_field_exists("SchemaElement.name", &f_4)?;
+        Ok(Self {
+            type_: f_1,
+            type_length: f_2,
+            repetition_type: f_3,
+            name: f_4.unwrap(),
+            num_children: f_5,
+            converted_type: f_6,
+            scale: f_7,
+            precision: f_8,
+            field_id: f_9,
+            logical_type: f_10,
+        })
+    }
+}
+
+impl From<SchemaElementView<'_>> for SchemaElement {
+    fn from(view: SchemaElementView<'_>) -> Self {
+        SchemaElement {
+            type_: view.type_,
+            type_length: view.type_length,
+            repetition_type: view.repetition_type,
+            name: view.name.into(),
+            num_children: view.num_children,
+            converted_type: view.converted_type,
+            scale: view.scale,
+            precision: view.precision,
+            field_id: view.field_id,
+            logical_type: view.logical_type,
+        }
+    }
+}
+
+/// [`RowGroup`] borrowing from its column chunks.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RowGroupView<'a> {
+    pub columns: Vec<ColumnChunkView<'a>>,
+    pub total_byte_size: i64,
+    pub num_rows: i64,
+    pub sorting_columns: Option<Vec<SortingColumn>>,
+    pub file_offset: Option<i64>,
+    pub total_compressed_size: Option<i64>,
+    pub ordinal: Option<i16>,
+}
+
+impl<'a> RowGroupView<'a> {
+    pub fn read_from_in_protocol(i: &mut TCompactSimdInputProtocol<'a>) -> thrift::Result<Self> {
+        let mut f_1 = None;
+        let mut f_2 = None;
+        let mut f_3 = None;
+        let mut f_4 = None;
+        let mut f_5 = None;
+        let mut f_6 = None;
+        let mut f_7 = None;
+        read_struct(i, |i, id, field_type| {
+            match id {
+                1 => f_1 = Some(read_list(i, ColumnChunkView::read_from_in_protocol)?),
+                2 => f_2 = Some(i.read_i64()?),
+                3 => f_3 = Some(i.read_i64()?),
+                4 => f_4 = Some(read_list(i, |i| SortingColumn::read_from_in_protocol(i))?),
+                5 => f_5 = Some(i.read_i64()?),
+                6 => f_6 = Some(i.read_i64()?),
+                7 => f_7 = Some(i.read_i16()?),
+                _ => i.skip(field_type)?,
+            }
+            Ok(())
+        })?;
+        verify_required_field_exists("RowGroup.columns", &f_1)?;
+        verify_required_field_exists("RowGroup.total_byte_size", &f_2)?;
+        verify_required_field_exists("RowGroup.num_rows", &f_3)?;
+        Ok(Self {
+            columns: f_1.unwrap(),
+            total_byte_size: f_2.unwrap(),
+            num_rows: f_3.unwrap(),
+            sorting_columns: f_4,
+            file_offset: f_5,
+            total_compressed_size: f_6,
+            ordinal: f_7,
+        })
+    }
+}
+
+impl From<RowGroupView<'_>> for RowGroup {
+    fn from(view: RowGroupView<'_>) -> Self {
+        RowGroup {
+            columns: view.columns.into_iter().map(Into::into).collect(),
+            total_byte_size: view.total_byte_size,
+            num_rows: view.num_rows,
+            sorting_columns: view.sorting_columns,
+            file_offset: view.file_offset,
+            total_compressed_size: view.total_compressed_size,
+            ordinal: view.ordinal,
+        }
+    }
+}
+
+/// [`ColumnChunk`] borrowing its file path and metadata.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnChunkView<'a> {
+    pub file_path: Option<&'a str>,
+    pub file_offset: i64,
+    pub meta_data: Option<ColumnMetaDataView<'a>>,
+    pub offset_index_offset: Option<i64>,
+    pub offset_index_length: Option<i32>,
+    pub column_index_offset: Option<i64>,
+    pub column_index_length: Option<i32>,
+    pub crypto_metadata: Option<ColumnCryptoMetaData>,
+    pub encrypted_column_metadata: Option<&'a [u8]>,
+}
+
+impl<'a> ColumnChunkView<'a> {
+    pub fn read_from_in_protocol(i: &mut TCompactSimdInputProtocol<'a>) -> thrift::Result<Self> {
+        let mut f_1 = None;
+        let mut f_2 = None;
+        let mut f_3 = None;
+        let mut f_4 = None;
+        let mut f_5 = None;
+        let mut f_6 = None;
+        let mut f_7 = None;
+        let mut f_8 = None;
+        let mut f_9 = None;
+        read_struct(i, |i, id, field_type| {
+            match id {
+                1 => f_1 = Some(i.read_borrowed_str()?),
+                2 => f_2 = Some(i.read_i64()?),
+                3 => f_3 = Some(ColumnMetaDataView::read_from_in_protocol(i)?),
+                4 => f_4 = Some(i.read_i64()?),
+                5 => f_5 = Some(i.read_i32()?),
+                6 => f_6 = Some(i.read_i64()?),
+                7 => f_7 = Some(i.read_i32()?),
+                8 => f_8 = Some(ColumnCryptoMetaData::read_from_in_protocol(i)?),
+                9 => f_9 = Some(i.read_borrowed_bytes()?),
+                _ => i.skip(field_type)?,
+            }
+            Ok(())
+        })?;
+        verify_required_field_exists("ColumnChunk.file_offset", &f_2)?;
+        Ok(Self {
+            file_path: f_1,
+            file_offset: f_2.unwrap(),
+            meta_data: f_3,
+            offset_index_offset: f_4,
+            offset_index_length: f_5,
+            column_index_offset: f_6,
+            column_index_length: f_7,
+            crypto_metadata: f_8,
+            encrypted_column_metadata: f_9,
+        })
+    }
+}
+
+impl From<ColumnChunkView<'_>> for ColumnChunk {
+    fn from(view: ColumnChunkView<'_>) -> Self {
+        ColumnChunk {
+            file_path: view.file_path.map(Into::into),
+            file_offset: view.file_offset,
+            meta_data: view.meta_data.map(Into::into),
+            offset_index_offset: view.offset_index_offset,
+            offset_index_length: view.offset_index_length,
+            column_index_offset: view.column_index_offset,
+            column_index_length: view.column_index_length,
+            crypto_metadata: view.crypto_metadata,
+            encrypted_column_metadata: view.encrypted_column_metadata.map(Into::into),
+        }
+    }
+}
+
+/// [`ColumnMetaData`] borrowing its path and statistics.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnMetaDataView<'a> {
+    pub type_: Type,
+    pub encodings: Vec<Encoding>,
+    pub path_in_schema: Vec<&'a str>,
+    pub codec: CompressionCodec,
+    pub num_values: i64,
+    pub total_uncompressed_size: i64,
+    pub total_compressed_size: i64,
+    pub key_value_metadata: Option<Vec<KeyValueView<'a>>>,
+    pub data_page_offset: i64,
+    pub index_page_offset: Option<i64>,
+    pub dictionary_page_offset: Option<i64>,
+    pub statistics: Option<StatisticsView<'a>>,
+    pub encoding_stats: Option<Vec<PageEncodingStats>>,
+    pub bloom_filter_offset: Option<i64>,
+    pub bloom_filter_length: Option<i32>,
+}
+
+impl<'a> ColumnMetaDataView<'a> {
+    pub fn read_from_in_protocol(i: &mut TCompactSimdInputProtocol<'a>) -> thrift::Result<Self> {
+        let mut f_1 = None;
+        let mut f_2 = None;
+        let mut f_3 = None;
+        let mut f_4 = None;
+        let mut f_5 = None;
+        let mut f_6 = None;
+        let mut f_7 = None;
+        let mut f_8 = None;
+        let mut f_9 = None;
+        let mut f_10 = None;
+        let mut f_11 = None;
+        let mut f_12 = None;
+        let mut f_13 = None;
+        let mut f_14 = None;
+        let mut f_15 = None;
+        read_struct(i, |i, id, field_type| {
+            match id {
+                1 => f_1 = Some(Type::read_from_in_protocol(i)?),
+                2 => f_2 = Some(read_list(i, |i| Encoding::read_from_in_protocol(i))?),
+                3 => f_3 = Some(read_list(i, |i| i.read_borrowed_str())?),
+                4 => f_4 = Some(CompressionCodec::read_from_in_protocol(i)?),
+                5 => f_5 = Some(i.read_i64()?),
+                6 => f_6 = Some(i.read_i64()?),
+                7 => f_7 = Some(i.read_i64()?),
+                8 => f_8 = Some(read_list(i, KeyValueView::read_from_in_protocol)?),
+                9 => f_9 = Some(i.read_i64()?),
+                10 => f_10 = Some(i.read_i64()?),
+                11 => f_11 = Some(i.read_i64()?),
+                12 => f_12 = Some(StatisticsView::read_from_in_protocol(i)?),
+                13 => {
+                    f_13 = Some(read_list(i, |i| {
+                        PageEncodingStats::read_from_in_protocol(i)
+                    })?)
+                }
+                14 => f_14 = Some(i.read_i64()?),
+                15 => f_15 = Some(i.read_i32()?),
+                _ => i.skip(field_type)?,
+            }
+            Ok(())
+        })?;
+        verify_required_field_exists("ColumnMetaData.type_", &f_1)?;
+        verify_required_field_exists("ColumnMetaData.encodings", &f_2)?;
+        verify_required_field_exists("ColumnMetaData.path_in_schema", &f_3)?;
+        verify_required_field_exists("ColumnMetaData.codec", &f_4)?;
+        verify_required_field_exists("ColumnMetaData.num_values", &f_5)?;
+        verify_required_field_exists("ColumnMetaData.total_uncompressed_size", &f_6)?;
+        verify_required_field_exists("ColumnMetaData.total_compressed_size", &f_7)?;
+        verify_required_field_exists("ColumnMetaData.data_page_offset", &f_9)?;
+        Ok(Self {
+            type_: f_1.unwrap(),
+            encodings: f_2.unwrap(),
+            path_in_schema: f_3.unwrap(),
+            codec: f_4.unwrap(),
+            num_values: f_5.unwrap(),
+            total_uncompressed_size: f_6.unwrap(),
+            total_compressed_size: f_7.unwrap(),
+            key_value_metadata: f_8,
+            data_page_offset: f_9.unwrap(),
+            index_page_offset: f_10,
+            dictionary_page_offset: f_11,
+            statistics: f_12,
+            encoding_stats: f_13,
+            bloom_filter_offset: f_14,
+            bloom_filter_length: f_15,
+        })
+    }
+}
+
+impl From<ColumnMetaDataView<'_>> for ColumnMetaData {
+    fn from(view: ColumnMetaDataView<'_>) -> Self {
+        ColumnMetaData {
+            type_: view.type_,
+            encodings: view.encodings,
+            path_in_schema: view.path_in_schema.into_iter().map(Into::into).collect(),
+            codec: view.codec,
+            num_values: view.num_values,
+            total_uncompressed_size: view.total_uncompressed_size,
+            total_compressed_size: view.total_compressed_size,
+            key_value_metadata: view
+                .key_value_metadata
+                .map(|kvs| kvs.into_iter().map(Into::into).collect()),
+            data_page_offset: view.data_page_offset,
+            index_page_offset: view.index_page_offset,
+            dictionary_page_offset: view.dictionary_page_offset,
+            statistics: view.statistics.map(Into::into),
+            encoding_stats: view.encoding_stats,
+            bloom_filter_offset: view.bloom_filter_offset,
+            bloom_filter_length: view.bloom_filter_length,
+        }
+    }
+}
+
+/// [`Statistics`] borrowing its min and max values.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StatisticsView<'a> {
+    pub max: Option<&'a [u8]>,
+    pub min: Option<&'a [u8]>,
+    pub null_count: Option<i64>,
+    pub distinct_count: Option<i64>,
+    pub max_value: Option<&'a [u8]>,
+    pub min_value: Option<&'a [u8]>,
+    pub is_max_value_exact: Option<bool>,
+    pub is_min_value_exact: Option<bool>,
+}
+
+impl<'a> StatisticsView<'a> {
+    pub fn read_from_in_protocol(i: &mut TCompactSimdInputProtocol<'a>) -> thrift::Result<Self> {
+        let mut view = Self::default();
+        read_struct(i, |i, id, field_type| {
+            match id {
+                1 => view.max = Some(i.read_borrowed_bytes()?),
+                2 => view.min = Some(i.read_borrowed_bytes()?),
+                3 => view.null_count = Some(i.read_i64()?),
+                4 => view.distinct_count = Some(i.read_i64()?),
+                5 => view.max_value = Some(i.read_borrowed_bytes()?),
+                6 => view.min_value = Some(i.read_borrowed_bytes()?),
+                7 => view.is_max_value_exact = Some(i.read_bool()?),
+                8 => view.is_min_value_exact = Some(i.read_bool()?),
+                _ => i.skip(field_type)?,
+            }
+            Ok(())
+        })?;
+        Ok(view)
+    }
+}
+
+impl From<StatisticsView<'_>> for Statistics {
+    fn from(view: StatisticsView<'_>) -> Self {
+        Statistics {
+            max: view.max.map(Into::into),
+            min: view.min.map(Into::into),
+            null_count: view.null_count,
+            distinct_count: view.distinct_count,
+            max_value: view.max_value.map(Into::into),
+            min_value: view.min_value.map(Into::into),
+            is_max_value_exact: view.is_max_value_exact,
+            is_min_value_exact: view.is_min_value_exact,
+        }
+    }
+}
+
+/// [`KeyValue`] borrowing its key and value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyValueView<'a> {
+    pub key: &'a str,
+    pub value: Option<&'a str>,
+}
+
+impl<'a> KeyValueView<'a> {
+    pub fn read_from_in_protocol(i: &mut TCompactSimdInputProtocol<'a>) -> thrift::Result<Self> {
+        let mut f_1 = None;
+        let mut f_2 = None;
+        read_struct(i, |i, id, field_type| {
+            match id {
+                1 => f_1 = Some(i.read_borrowed_str()?),
+                2 => f_2 = Some(i.read_borrowed_str()?),
+                _ => i.skip(field_type)?,
+            }
+            Ok(())
+        })?;
+        verify_required_field_exists("KeyValue.key", &f_1)?;
+        Ok(Self {
+            key: f_1.unwrap(),
+            value: f_2,
+        })
+    }
+}
+
+impl From<KeyValueView<'_>> for KeyValue {
+    fn from(view: KeyValueView<'_>) -> Self {
+        KeyValue {
+            key: view.key.into(),
+            value: view.value.map(Into::into),
+        }
+    }
+}
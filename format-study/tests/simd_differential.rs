@@ -0,0 +1,1160 @@
+//! Differential tests of [`TCompactSimdInputProtocol`] against parquet's
+//! `TCompactSliceInputProtocol`.
+//!
+//! Both decode generated `FileMetaData` encodings, mutations of them and arbitrary bytes,
+//! and must either decode the same metadata from the same bytes or fail with the same
+//! class of error. Where parquet's protocol has no answer to compare against, the SIMD
+//! protocol only has to fail, and the `rejects_*` tests pin down how. The borrowed
+//! `FileMetaDataView` must agree with them on generated and truncated encodings, and
+//! `LazyFileMetaData` must decode any projection of generated encodings as the full
+//! decode does. The rest of the compact protocol, messages, sets, maps and skipping, is
+//! checked on arbitrary Thrift values against thrift's own `TCompactInputProtocol`, and
+//! skipping against reading on mutations of them. Run a longer session with
+//! `PROPTEST_CASES=100000 cargo test --release --features simd --test simd_differential`.
+
+use std::ops::Range;
+
+use format_study::lazy::LazyFileMetaData;
+use format_study::view::FileMetaDataView;
+use format_study::TCompactSimdInputProtocol;
+use parquet::format::{
+    ColumnChunk, ColumnMetaData, ColumnOrder, CompressionCodec, ConvertedType, Encoding,
+    FieldRepetitionType, FileMetaData, IntType, KeyValue, LogicalType, PageEncodingStats, PageType,
+    RowGroup, SchemaElement, SortingColumn, Statistics, Type,
+};
+use parquet::thrift::{TCompactSliceInputProtocol, TSerializable};
+use proptest::option;
+use proptest::prelude::*;
+use proptest::sample::Index;
+use thrift::protocol::{
+    TCompactInputProtocol, TCompactOutputProtocol, TFieldIdentifier, TInputProtocol,
+    TListIdentifier, TMapIdentifier, TMessageIdentifier, TMessageType, TOutputProtocol,
+    TSetIdentifier, TStructIdentifier, TType,
+};
+use thrift::{ApplicationErrorKind, ProtocolErrorKind, TransportErrorKind};
+
+/// What an error is compared by, messages are free to differ.
+#[derive(Debug, PartialEq)]
+enum ErrorClass {
+    Transport(TransportErrorKind),
+    Protocol(ProtocolErrorKind),
+    Application(ApplicationErrorKind),
+    User,
+}
+
+fn class(e: &thrift::Error) -> ErrorClass {
+    match e {
+        thrift::Error::Transport(e) => ErrorClass::Transport(e.kind),
+        thrift::Error::Protocol(e) => ErrorClass::Protocol(e.kind),
+        thrift::Error::Application(e) => ErrorClass::Application(e.kind),
+        thrift::Error::User(_) => ErrorClass::User,
+    }
+}
+
+/// Decodes `buf` with both protocols and checks they agree, returning the metadata.
+fn differential(buf: &[u8]) -> Result<Option<FileMetaData>, TestCaseError> {
+    let mut simd = TCompactSimdInputProtocol::new(buf);
+    let mut reference = Reference::new(buf);
+    let simd_result = FileMetaData::read_from_in_protocol(&mut simd);
+    let reference_result = FileMetaData::read_from_in_protocol(&mut reference);
+    match (simd_result, reference_result) {
+        (Ok(simd_meta), Ok(reference_meta)) => {
+            prop_assert_eq!(&simd_meta, &reference_meta);
+            prop_assert_eq!(simd.as_slice().len(), reference.inner.as_slice().len());
+            Ok(Some(simd_meta))
+        }
+        (simd_result, Err(reference_err)) if is_unchecked(&reference_err) => {
+            prop_assert!(
+                simd_result.is_err(),
+                "simd accepted what the reference leaves unchecked: {}",
+                reference_err
+            );
+            Ok(None)
+        }
+        (Err(simd_err), Err(reference_err)) => {
+            prop_assert_eq!(
+                class(&simd_err),
+                class(&reference_err),
+                "simd: {}, reference: {}",
+                simd_err,
+                reference_err
+            );
+            Ok(None)
+        }
+        (simd_result, reference_result) => Err(TestCaseError::fail(format!(
+            "simd: {:?}, reference: {:?}",
+            simd_result.map(|_| ()),
+            reference_result.map(|_| ())
+        ))),
+    }
+}
+
+/// Decodes `buf` into a [`FileMetaDataView`], which must agree with the owned decode. The
+/// view reads every field `parquet::format` does, so this holds for any input.
+fn view_differential(buf: &[u8]) -> Result<(), TestCaseError> {
+    let mut owned = TCompactSimdInputProtocol::new(buf);
+    let mut view = TCompactSimdInputProtocol::new(buf);
+    let owned_result = FileMetaData::read_from_in_protocol(&mut owned);
+    let view_result = FileMetaDataView::read_from_in_protocol(&mut view).map(FileMetaData::from);
+    match (owned_result, view_result) {
+        (Ok(owned_meta), Ok(view_meta)) => {
+            prop_assert_eq!(owned_meta, view_meta);
+            prop_assert_eq!(owned.as_slice().len(), view.as_slice().len());
+        }
+        (Err(owned_err), Err(view_err)) => prop_assert_eq!(
+            class(&owned_err),
+            class(&view_err),
+            "owned: {}, view: {}",
+            owned_err,
+            view_err
+        ),
+        (owned_result, view_result) => prop_assert!(
+            false,
+            "owned: {:?}, view: {:?}",
+            owned_result.map(|_| ()),
+            view_result.map(|_| ())
+        ),
+    }
+    Ok(())
+}
+
+/// `TCompactSliceInputProtocol`, stopping with an [`Unchecked`] error where it has no
+/// answer to compare against: on the input it would panic on, truncate to the width of
+/// a type, or allocate for without bound. All of that is invalid, so the SIMD protocol
+/// has to fail on it too. Sets and maps, which it does not support, are read through
+/// thrift's `TCompactInputProtocol`, and strings are skipped as binary, which share their
+/// wire type.
+struct Reference<'a> {
+    inner: TCompactSliceInputProtocol<'a>,
+    // Field ids are tracked alongside the inner protocol, which panics on a delta
+    // overflowing i16.
+    last_read_field_id: i16,
+    read_field_id_stack: Vec<i16>,
+}
+
+impl<'a> Reference<'a> {
+    fn new(buf: &'a [u8]) -> Self {
+        Self {
+            inner: TCompactSliceInputProtocol::new(buf),
+            last_read_field_id: 0,
+            read_field_id_stack: vec![],
+        }
+    }
+
+    /// Checks the varint at `offset` fits in `bits`, leaving truncation to the inner
+    /// protocol.
+    fn check_varint(&self, offset: usize, bits: u32) -> thrift::Result<()> {
+        let buf = self.inner.as_slice().get(offset..).unwrap_or_default();
+        let max_bytes = bits.div_ceil(7) as usize;
+        for (i, &byte) in buf.iter().take(max_bytes).enumerate() {
+            if i == max_bytes - 1 && u64::from(byte) >> (bits - 7 * i as u32) != 0 {
+                return Err(unchecked("varint wider than its type"));
+            }
+            if byte & 0x80 == 0 {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl TInputProtocol for Reference<'_> {
+    fn read_message_begin(&mut self) -> thrift::Result<TMessageIdentifier> {
+        Err(not_implemented())
+    }
+
+    fn read_message_end(&mut self) -> thrift::Result<()> {
+        Err(not_implemented())
+    }
+
+    fn read_struct_begin(&mut self) -> thrift::Result<Option<TStructIdentifier>> {
+        self.read_field_id_stack.push(self.last_read_field_id);
+        self.last_read_field_id = 0;
+        self.inner.read_struct_begin()
+    }
+
+    fn read_struct_end(&mut self) -> thrift::Result<()> {
+        self.last_read_field_id = self.read_field_id_stack.pop().unwrap();
+        self.inner.read_struct_end()
+    }
+
+    fn read_field_begin(&mut self) -> thrift::Result<TFieldIdentifier> {
+        if let Some(&header) = self.inner.as_slice().first() {
+            let field_delta = (header >> 4) as i16;
+            match header & 0x0F {
+                0 => {}
+                _ if field_delta != 0 => {
+                    if self.last_read_field_id.checked_add(field_delta).is_none() {
+                        return Err(unchecked("field id overflows i16"));
+                    }
+                }
+                _ => self.check_varint(1, 16)?,
+            }
+        }
+        let field = self.inner.read_field_begin()?;
+        if let Some(id) = field.id {
+            self.last_read_field_id = id;
+        }
+        Ok(field)
+    }
+
+    fn read_field_end(&mut self) -> thrift::Result<()> {
+        self.inner.read_field_end()
+    }
+
+    fn read_bool(&mut self) -> thrift::Result<bool> {
+        self.inner.read_bool()
+    }
+
+    fn read_bytes(&mut self) -> thrift::Result<Vec<u8>> {
+        self.check_varint(0, 64)?;
+        self.inner.read_bytes()
+    }
+
+    fn read_i8(&mut self) -> thrift::Result<i8> {
+        self.inner.read_i8()
+    }
+
+    fn read_i16(&mut self) -> thrift::Result<i16> {
+        self.check_varint(0, 16)?;
+        self.inner.read_i16()
+    }
+
+    fn read_i32(&mut self) -> thrift::Result<i32> {
+        self.check_varint(0, 32)?;
+        self.inner.read_i32()
+    }
+
+    fn read_i64(&mut self) -> thrift::Result<i64> {
+        self.check_varint(0, 64)?;
+        self.inner.read_i64()
+    }
+
+    fn read_double(&mut self) -> thrift::Result<f64> {
+        if self.inner.as_slice().len() < 8 {
+            return Err(unchecked("double past the end of the input"));
+        }
+        self.inner.read_double()
+    }
+
+    fn read_string(&mut self) -> thrift::Result<String> {
+        self.check_varint(0, 64)?;
+        self.inner.read_string()
+    }
+
+    fn read_list_begin(&mut self) -> thrift::Result<TListIdentifier> {
+        if self.inner.as_slice().first().is_some_and(|h| h >> 4 == 15) {
+            self.check_varint(1, 32)?;
+        }
+        let list = self.inner.read_list_begin()?;
+        // Every element takes at least a byte, so a larger list fails on whichever check
+        // comes first, and the inner protocol would allocate it up front.
+        if list.size < 0 || list.size as usize > self.inner.as_slice().len() {
+            return Err(unchecked("list size past the end of the input"));
+        }
+        Ok(list)
+    }
+
+    fn read_list_end(&mut self) -> thrift::Result<()> {
+        self.inner.read_list_end()
+    }
+
+    fn read_set_begin(&mut self) -> thrift::Result<TSetIdentifier> {
+        let list = self.read_list_begin()?;
+        Ok(TSetIdentifier::new(list.element_type, list.size))
+    }
+
+    fn read_set_end(&mut self) -> thrift::Result<()> {
+        Ok(())
+    }
+
+    fn read_map_begin(&mut self) -> thrift::Result<TMapIdentifier> {
+        self.check_varint(0, 32)?;
+        let mut rest = self.inner.as_slice();
+        let map = TCompactInputProtocol::new(&mut rest).read_map_begin()?;
+        // As for lists, every entry takes at least two bytes.
+        if map.size < 0 || map.size as usize > rest.len() / 2 {
+            return Err(unchecked("map size past the end of the input"));
+        }
+        for _ in rest.len()..self.inner.as_slice().len() {
+            self.inner.read_byte()?;
+        }
+        Ok(map)
+    }
+
+    fn read_map_end(&mut self) -> thrift::Result<()> {
+        self.inner.read_map_end()
+    }
+
+    fn read_byte(&mut self) -> thrift::Result<u8> {
+        self.inner.read_byte()
+    }
+
+    fn skip_till_depth(&mut self, field_type: TType, depth: i8) -> thrift::Result<()> {
+        if depth == 0 {
+            return Err(thrift::Error::Protocol(thrift::ProtocolError {
+                kind: ProtocolErrorKind::DepthLimit,
+                message: format!("cannot parse past {:?}", field_type),
+            }));
+        }
+        match field_type {
+            TType::String => self.read_bytes().map(|_| ()),
+            TType::Struct => {
+                self.read_struct_begin()?;
+                loop {
+                    let field = self.read_field_begin()?;
+                    if field.field_type == TType::Stop {
+                        break;
+                    }
+                    self.skip_till_depth(field.field_type, depth - 1)?;
+                }
+                self.read_struct_end()
+            }
+            TType::List => {
+                let list = self.read_list_begin()?;
+                for _ in 0..list.size {
+                    self.skip_till_depth(list.element_type, depth - 1)?;
+                }
+                Ok(())
+            }
+            TType::Set => {
+                let set = self.read_set_begin()?;
+                for _ in 0..set.size {
+                    self.skip_till_depth(set.element_type, depth - 1)?;
+                }
+                Ok(())
+            }
+            TType::Map => {
+                let map = self.read_map_begin()?;
+                if let (Some(key_type), Some(value_type)) = (map.key_type, map.value_type) {
+                    for _ in 0..map.size {
+                        self.skip_till_depth(key_type, depth - 1)?;
+                        self.skip_till_depth(value_type, depth - 1)?;
+                    }
+                }
+                Ok(())
+            }
+            TType::Bool => self.read_bool().map(|_| ()),
+            TType::I08 => self.read_i8().map(|_| ()),
+            TType::I16 => self.read_i16().map(|_| ()),
+            TType::I32 => self.read_i32().map(|_| ()),
+            TType::I64 => self.read_i64().map(|_| ()),
+            TType::Double => self.read_double().map(|_| ()),
+            u => Err(thrift::Error::Protocol(thrift::ProtocolError {
+                kind: ProtocolErrorKind::Unknown,
+                message: format!("cannot skip field type {:?}", u),
+            })),
+        }
+    }
+}
+
+fn not_implemented() -> thrift::Error {
+    thrift::Error::Protocol(thrift::ProtocolError {
+        kind: ProtocolErrorKind::NotImplemented,
+        message: "not supported by the reference".into(),
+    })
+}
+
+/// Input [`Reference`] has no answer for.
+#[derive(Debug)]
+struct Unchecked(&'static str);
+
+impl std::fmt::Display for Unchecked {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "unchecked: {}", self.0)
+    }
+}
+
+impl std::error::Error for Unchecked {}
+
+fn unchecked(reason: &'static str) -> thrift::Error {
+    thrift::Error::User(Box::new(Unchecked(reason)))
+}
+
+fn is_unchecked(e: &thrift::Error) -> bool {
+    matches!(e, thrift::Error::User(e) if e.is::<Unchecked>())
+}
+
+/// A `FileMetaData` of `num_columns` columns and `num_row_groups` row groups, with every
+/// optional field set or not, long lists, negative numbers, booleans and multi-byte
+/// strings, so that every read path is exercised.
+fn file_metadata(
+    num_columns: Range<usize>,
+    num_row_groups: Range<usize>,
+) -> impl Strategy<Value = FileMetaData> {
+    (num_columns, num_row_groups).prop_flat_map(|(num_columns, num_row_groups)| {
+        let column_orders = vec![ColumnOrder::TYPEORDER(Default::default()); num_columns];
+        (
+            schema(num_columns),
+            prop::collection::vec(row_group(num_columns), num_row_groups),
+            any::<i32>(),
+            any::<i64>(),
+            option::of(key_values()),
+            option::of(string()),
+            option::of(Just(column_orders)),
+            option::of(bytes()),
+        )
+            .prop_map(
+                |(
+                    schema,
+                    mut row_groups,
+                    version,
+                    num_rows,
+                    key_value_metadata,
+                    created_by,
+                    column_orders,
+                    footer_signing_key_metadata,
+                )| {
+                    for (i, row_group) in row_groups.iter_mut().enumerate() {
+                        row_group.ordinal = Some(i as _);
+                    }
+                    FileMetaData {
+                        schema,
+                        row_groups,
+                        version,
+                        num_rows,
+                        key_value_metadata,
+                        created_by,
+                        column_orders,
+                        encryption_algorithm: None,
+                        footer_signing_key_metadata,
+                    }
+                },
+            )
+    })
+}
+
+fn string() -> impl Strategy<Value = String> {
+    let char = prop_oneof![
+        3 => prop::char::range('a', 'z'),
+        1 => prop::char::range('\u{80}', '\u{10FFFF}'),
+    ];
+    prop::collection::vec(char, 0..40).prop_map(String::from_iter)
+}
+
+fn bytes() -> impl Strategy<Value = Vec<u8>> {
+    prop::collection::vec(any::<u8>(), 0..40)
+}
+
+fn key_values() -> impl Strategy<Value = Vec<KeyValue>> {
+    let key_value = (string(), option::of(string())).prop_map(|(k, v)| KeyValue::new(k, v));
+    prop::collection::vec(key_value, 0..20)
+}
+
+fn logical_type() -> impl Strategy<Value = LogicalType> {
+    prop_oneof![
+        Just(LogicalType::STRING(Default::default())),
+        (prop::sample::select(vec![8, 16, 32, 64]), any::<bool>()).prop_map(
+            |(bit_width, is_signed)| LogicalType::INTEGER(IntType {
+                bit_width,
+                is_signed,
+            })
+        ),
+        Just(LogicalType::UNKNOWN(Default::default())),
+    ]
+}
+
+fn schema(num_columns: usize) -> impl Strategy<Value = Vec<SchemaElement>> {
+    let leaf = (
+        prop::sample::select(vec![Type::INT32, Type::INT64, Type::BYTE_ARRAY]),
+        option::of(any::<i32>()),
+        option::of(prop::sample::select(vec![
+            FieldRepetitionType::REQUIRED,
+            FieldRepetitionType::OPTIONAL,
+        ])),
+        string(),
+        option::of(Just(ConvertedType::UTF8)),
+        option::of(any::<i32>()),
+        option::of(any::<i32>()),
+        option::of(any::<i32>()),
+        option::of(logical_type()),
+    )
+        .prop_map(
+            |(
+                type_,
+                type_length,
+                repetition_type,
+                name,
+                converted_type,
+                scale,
+                precision,
+                field_id,
+                logical_type,
+            )| SchemaElement {
+                type_: Some(type_),
+                type_length,
+                repetition_type,
+                name,
+                num_children: None,
+                converted_type,
+                scale,
+                precision,
+                field_id,
+                logical_type,
+            },
+        );
+    (string(), prop::collection::vec(leaf, num_columns)).prop_map(move |(name, leaves)| {
+        let root = SchemaElement {
+            type_: None,
+            type_length: None,
+            repetition_type: None,
+            name,
+            num_children: Some(num_columns as _),
+            converted_type: None,
+            scale: None,
+            precision: None,
+            field_id: None,
+            logical_type: None,
+        };
+        std::iter::once(root).chain(leaves).collect()
+    })
+}
+
+fn statistics() -> impl Strategy<Value = Statistics> {
+    (
+        option::of(bytes()),
+        option::of(bytes()),
+        option::of(bytes()),
+        option::of(bytes()),
+        option::of(any::<i64>()),
+        option::of(any::<i64>()),
+        option::of(any::<bool>()),
+        option::of(any::<bool>()),
+    )
+        .prop_map(
+            |(
+                max,
+                min,
+                max_value,
+                min_value,
+                null_count,
+                distinct_count,
+                is_max_value_exact,
+                is_min_value_exact,
+            )| Statistics {
+                max,
+                min,
+                max_value,
+                min_value,
+                null_count,
+                distinct_count,
+                is_max_value_exact,
+                is_min_value_exact,
+            },
+        )
+}
+
+fn column_metadata() -> impl Strategy<Value = ColumnMetaData> {
+    let encoding = prop::sample::select(vec![Encoding::PLAIN, Encoding::RLE_DICTIONARY]);
+    let encoding_stats = any::<i32>()
+        .prop_map(|count| PageEncodingStats::new(PageType::DATA_PAGE, Encoding::PLAIN, count));
+    (
+        (
+            prop::collection::vec(encoding, 0..20),
+            prop::collection::vec(string(), 0..20),
+            any::<i64>(),
+            any::<i64>(),
+            any::<i64>(),
+            option::of(key_values()),
+            any::<i64>(),
+        ),
+        (
+            option::of(any::<i64>()),
+            option::of(any::<i64>()),
+            option::of(statistics()),
+            option::of(prop::collection::vec(encoding_stats, 0..4)),
+            option::of(any::<i64>()),
+            option::of(any::<i32>()),
+        ),
+    )
+        .prop_map(
+            |(
+                (
+                    encodings,
+                    path_in_schema,
+                    num_values,
+                    total_uncompressed_size,
+                    total_compressed_size,
+                    key_value_metadata,
+                    data_page_offset,
+                ),
+                (
+                    index_page_offset,
+                    dictionary_page_offset,
+                    statistics,
+                    encoding_stats,
+                    bloom_filter_offset,
+                    bloom_filter_length,
+                ),
+            )| ColumnMetaData {
+                type_: Type::BYTE_ARRAY,
+                encodings,
+                path_in_schema,
+                codec: CompressionCodec::SNAPPY,
+                num_values,
+                total_uncompressed_size,
+                total_compressed_size,
+                key_value_metadata,
+                data_page_offset,
+                index_page_offset,
+                dictionary_page_offset,
+                statistics,
+                encoding_stats,
+                bloom_filter_offset,
+                bloom_filter_length,
+            },
+        )
+}
+
+fn column_chunk() -> impl Strategy<Value = ColumnChunk> {
+    (
+        option::of(string()),
+        any::<i64>(),
+        option::of(column_metadata()),
+        option::of(any::<i32>()),
+        option::of(any::<i64>()),
+        option::of(any::<i32>()),
+        option::of(any::<i64>()),
+        option::of(bytes()),
+    )
+        .prop_map(
+            |(
+                file_path,
+                file_offset,
+                meta_data,
+                offset_index_length,
+                offset_index_offset,
+                column_index_length,
+                column_index_offset,
+                encrypted_column_metadata,
+            )| ColumnChunk {
+                file_path,
+                file_offset,
+                meta_data,
+                offset_index_length,
+                offset_index_offset,
+                column_index_length,
+                column_index_offset,
+                crypto_metadata: None,
+                encrypted_column_metadata,
+            },
+        )
+}
+
+/// A row group of `num_columns` column chunks, its ordinal left to [`file_metadata`].
+fn row_group(num_columns: usize) -> impl Strategy<Value = RowGroup> {
+    let sorting_column = (any::<i32>(), any::<bool>(), any::<bool>()).prop_map(
+        |(column, descending, nulls_first)| SortingColumn::new(column, descending, nulls_first),
+    );
+    (
+        prop::collection::vec(column_chunk(), num_columns),
+        any::<i64>(),
+        any::<i64>(),
+        option::of(prop::collection::vec(sorting_column, 0..4)),
+        option::of(any::<i64>()),
+        option::of(any::<i64>()),
+    )
+        .prop_map(
+            |(
+                columns,
+                total_byte_size,
+                num_rows,
+                sorting_columns,
+                file_offset,
+                total_compressed_size,
+            )| RowGroup {
+                columns,
+                total_byte_size,
+                num_rows,
+                sorting_columns,
+                file_offset,
+                total_compressed_size,
+                ordinal: None,
+            },
+        )
+}
+
+fn encode(meta: &FileMetaData) -> Vec<u8> {
+    let mut buf = vec![];
+    let mut out = TCompactOutputProtocol::new(&mut buf);
+    meta.write_to_out_protocol(&mut out).unwrap();
+    buf
+}
+
+#[derive(Debug, Clone)]
+enum Mutation {
+    Overwrite(Index, u8),
+    Insert(Index, u8),
+    Remove(Index),
+    Truncate(Index),
+}
+
+impl Mutation {
+    fn apply(&self, buf: &mut Vec<u8>) {
+        if buf.is_empty() {
+            return;
+        }
+        match self {
+            Mutation::Overwrite(i, byte) => {
+                let i = i.index(buf.len());
+                buf[i] = *byte;
+            }
+            Mutation::Insert(i, byte) => buf.insert(i.index(buf.len() + 1), *byte),
+            Mutation::Remove(i) => {
+                buf.remove(i.index(buf.len()));
+            }
+            Mutation::Truncate(i) => buf.truncate(i.index(buf.len())),
+        }
+    }
+}
+
+fn mutation() -> impl Strategy<Value = Mutation> {
+    prop_oneof![
+        4 => (any::<Index>(), any::<u8>()).prop_map(|(i, b)| Mutation::Overwrite(i, b)),
+        1 => (any::<Index>(), any::<u8>()).prop_map(|(i, b)| Mutation::Insert(i, b)),
+        1 => any::<Index>().prop_map(Mutation::Remove),
+        1 => any::<Index>().prop_map(Mutation::Truncate),
+    ]
+}
+
+/// A Thrift value, strings as the bytes they are on the wire and doubles as their bits.
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Bool(bool),
+    I8(i8),
+    I16(i16),
+    I32(i32),
+    I64(i64),
+    Double(u64),
+    String(Vec<u8>),
+    Struct(Vec<(i16, Value)>),
+    List(TType, Vec<Value>),
+    Set(TType, Vec<Value>),
+    Map(TType, TType, Vec<(Value, Value)>),
+}
+
+impl Value {
+    fn ttype(&self) -> TType {
+        match self {
+            Value::Bool(_) => TType::Bool,
+            Value::I8(_) => TType::I08,
+            Value::I16(_) => TType::I16,
+            Value::I32(_) => TType::I32,
+            Value::I64(_) => TType::I64,
+            Value::Double(_) => TType::Double,
+            Value::String(_) => TType::String,
+            Value::Struct(_) => TType::Struct,
+            Value::List(..) => TType::List,
+            Value::Set(..) => TType::Set,
+            Value::Map(..) => TType::Map,
+        }
+    }
+
+    fn write(&self, o: &mut impl TOutputProtocol) -> thrift::Result<()> {
+        match self {
+            Value::Bool(v) => o.write_bool(*v),
+            Value::I8(v) => o.write_i8(*v),
+            Value::I16(v) => o.write_i16(*v),
+            Value::I32(v) => o.write_i32(*v),
+            Value::I64(v) => o.write_i64(*v),
+            Value::Double(v) => o.write_double(f64::from_bits(*v)),
+            Value::String(v) => o.write_bytes(v),
+            Value::Struct(fields) => {
+                o.write_struct_begin(&TStructIdentifier::new("s"))?;
+                for (id, value) in fields {
+                    o.write_field_begin(&TFieldIdentifier::new("f", value.ttype(), *id))?;
+                    value.write(o)?;
+                    o.write_field_end()?;
+                }
+                o.write_field_stop()?;
+                o.write_struct_end()
+            }
+            Value::List(element_type, values) => {
+                o.write_list_begin(&TListIdentifier::new(*element_type, values.len() as i32))?;
+                values.iter().try_for_each(|v| v.write(o))?;
+                o.write_list_end()
+            }
+            Value::Set(element_type, values) => {
+                o.write_set_begin(&TSetIdentifier::new(*element_type, values.len() as i32))?;
+                values.iter().try_for_each(|v| v.write(o))?;
+                o.write_set_end()
+            }
+            Value::Map(key_type, value_type, entries) => {
+                let map = TMapIdentifier::new(*key_type, *value_type, entries.len() as i32);
+                o.write_map_begin(&map)?;
+                for (k, v) in entries {
+                    k.write(o)?;
+                    v.write(o)?;
+                }
+                o.write_map_end()
+            }
+        }
+    }
+
+    /// Reads a value of `ttype`, without trusting any size for an allocation, nesting at
+    /// most `depth` levels deep as `skip_till_depth` does.
+    fn read(i: &mut impl TInputProtocol, ttype: TType, depth: i8) -> thrift::Result<Value> {
+        if depth == 0 {
+            return Err(thrift::Error::Protocol(thrift::ProtocolError {
+                kind: ProtocolErrorKind::DepthLimit,
+                message: format!("cannot parse past {:?}", ttype),
+            }));
+        }
+        Ok(match ttype {
+            TType::Bool => Value::Bool(i.read_bool()?),
+            TType::I08 => Value::I8(i.read_i8()?),
+            TType::I16 => Value::I16(i.read_i16()?),
+            TType::I32 => Value::I32(i.read_i32()?),
+            TType::I64 => Value::I64(i.read_i64()?),
+            TType::Double => Value::Double(i.read_double()?.to_bits()),
+            TType::String => Value::String(i.read_bytes()?),
+            TType::Struct => {
+                i.read_struct_begin()?;
+                let mut fields = vec![];
+                loop {
+                    let field = i.read_field_begin()?;
+                    if field.field_type == TType::Stop {
+                        break;
+                    }
+                    fields.push((
+                        field.id.unwrap(),
+                        Value::read(i, field.field_type, depth - 1)?,
+                    ));
+                    i.read_field_end()?;
+                }
+                i.read_struct_end()?;
+                Value::Struct(fields)
+            }
+            TType::List => {
+                let list = i.read_list_begin()?;
+                let values = (0..list.size)
+                    .map(|_| Value::read(i, list.element_type, depth - 1))
+                    .collect::<thrift::Result<_>>()?;
+                i.read_list_end()?;
+                Value::List(list.element_type, values)
+            }
+            TType::Set => {
+                let set = i.read_set_begin()?;
+                let values = (0..set.size)
+                    .map(|_| Value::read(i, set.element_type, depth - 1))
+                    .collect::<thrift::Result<_>>()?;
+                i.read_set_end()?;
+                Value::Set(set.element_type, values)
+            }
+            TType::Map => {
+                let map = i.read_map_begin()?;
+                let (key_type, value_type) = match (map.key_type, map.value_type) {
+                    (Some(k), Some(v)) => (k, v),
+                    _ => (TType::I32, TType::I32),
+                };
+                let entries = (0..map.size)
+                    .map(|_| {
+                        Ok((
+                            Value::read(i, key_type, depth - 1)?,
+                            Value::read(i, value_type, depth - 1)?,
+                        ))
+                    })
+                    .collect::<thrift::Result<_>>()?;
+                i.read_map_end()?;
+                Value::Map(key_type, value_type, entries)
+            }
+            u => {
+                return Err(thrift::Error::Protocol(thrift::ProtocolError {
+                    kind: ProtocolErrorKind::Unknown,
+                    message: format!("cannot read field type {:?}", u),
+                }))
+            }
+        })
+    }
+}
+
+/// Values of a single Thrift type, as container elements must be, with `I32` for empty
+/// containers.
+fn same_type(values: Vec<Value>) -> (TType, Vec<Value>) {
+    let ttype = values.first().map_or(TType::I32, Value::ttype);
+    (
+        ttype,
+        values.into_iter().filter(|v| v.ttype() == ttype).collect(),
+    )
+}
+
+fn value() -> impl Strategy<Value = Value> {
+    let leaf = prop_oneof![
+        any::<bool>().prop_map(Value::Bool),
+        any::<i8>().prop_map(Value::I8),
+        any::<i16>().prop_map(Value::I16),
+        any::<i32>().prop_map(Value::I32),
+        any::<i64>().prop_map(Value::I64),
+        any::<u64>().prop_map(Value::Double),
+        prop::collection::vec(any::<u8>(), 0..40).prop_map(Value::String),
+    ];
+    leaf.prop_recursive(4, 64, 20, |inner| {
+        prop_oneof![
+            prop::collection::vec((any::<i16>(), inner.clone()), 0..8).prop_map(Value::Struct),
+            prop::collection::vec(inner.clone(), 0..20).prop_map(|values| {
+                let (ttype, values) = same_type(values);
+                Value::List(ttype, values)
+            }),
+            prop::collection::vec(inner.clone(), 0..20).prop_map(|values| {
+                let (ttype, values) = same_type(values);
+                Value::Set(ttype, values)
+            }),
+            prop::collection::vec((inner.clone(), inner), 0..8).prop_map(|entries| {
+                let (keys, values): (Vec<_>, Vec<_>) = entries.into_iter().unzip();
+                let (key_type, keys) = same_type(keys);
+                let (value_type, values) = same_type(values);
+                let entries: Vec<_> = keys.into_iter().zip(values).collect();
+                match entries.is_empty() {
+                    true => Value::Map(TType::I32, TType::I32, entries),
+                    false => Value::Map(key_type, value_type, entries),
+                }
+            }),
+        ]
+    })
+}
+
+fn message_type() -> impl Strategy<Value = TMessageType> {
+    prop_oneof![
+        Just(TMessageType::Call),
+        Just(TMessageType::Reply),
+        Just(TMessageType::Exception),
+        Just(TMessageType::OneWay),
+    ]
+}
+
+/// A message whose body is a struct of `fields`.
+fn encode_message(message: &TMessageIdentifier, fields: &[(i16, Value)]) -> Vec<u8> {
+    let mut buf = vec![];
+    let mut out = TCompactOutputProtocol::new(&mut buf);
+    out.write_message_begin(message).unwrap();
+    Value::Struct(fields.to_vec()).write(&mut out).unwrap();
+    out.write_message_end().unwrap();
+    buf
+}
+
+fn read_message(i: &mut impl TInputProtocol) -> thrift::Result<(TMessageIdentifier, Value)> {
+    let message = i.read_message_begin()?;
+    let body = Value::read(i, TType::Struct, 64)?;
+    i.read_message_end()?;
+    Ok((message, body))
+}
+
+proptest! {
+    #[test]
+    fn generated_metadata(meta in file_metadata(0..40, 0..4)) {
+        let buf = encode(&meta);
+        let decoded = differential(&buf)?;
+        prop_assert_eq!(decoded, Some(meta));
+        view_differential(&buf)?;
+    }
+
+    #[test]
+    fn mutated_metadata(
+        meta in file_metadata(0..20, 0..3),
+        mutations in prop::collection::vec(mutation(), 1..8),
+    ) {
+        let mut buf = encode(&meta);
+        for m in &mutations {
+            m.apply(&mut buf);
+        }
+        differential(&buf)?;
+        view_differential(&buf)?;
+    }
+
+    #[test]
+    fn truncated_metadata(meta in file_metadata(0..20, 2..3), len in any::<Index>()) {
+        let buf = encode(&meta);
+        differential(&buf[..len.index(buf.len())])?;
+        view_differential(&buf[..len.index(buf.len())])?;
+    }
+
+    #[test]
+    fn arbitrary_bytes(buf in prop::collection::vec(any::<u8>(), 0..512)) {
+        differential(&buf)?;
+    }
+
+    #[test]
+    fn lazy_projection(
+        meta in file_metadata(1..40, 0..4),
+        columns in prop::collection::vec(any::<Index>(), 0..8),
+    ) {
+        let num_columns = meta.schema.len() - 1;
+        let buf = encode(&meta);
+        let lazy = LazyFileMetaData::try_new(&buf)?;
+        prop_assert_eq!(lazy.version, meta.version);
+        prop_assert_eq!(lazy.num_rows, meta.num_rows);
+        prop_assert_eq!(&lazy.key_value_metadata, &meta.key_value_metadata);
+        prop_assert_eq!(&lazy.created_by, &meta.created_by);
+        prop_assert_eq!(&lazy.column_orders, &meta.column_orders);
+        prop_assert_eq!(&lazy.footer_signing_key_metadata, &meta.footer_signing_key_metadata);
+        prop_assert_eq!(lazy.schema()?, meta.schema.clone());
+        prop_assert_eq!(lazy.num_columns(), num_columns);
+
+        let columns: Vec<usize> = columns.iter().map(|c| c.index(num_columns)).collect();
+        let row_groups: Vec<usize> = (0..lazy.num_row_groups()).rev().collect();
+        let expected: Vec<RowGroup> = row_groups
+            .iter()
+            .map(|&i| RowGroup {
+                columns: columns.iter().map(|&c| meta.row_groups[i].columns[c].clone()).collect(),
+                ..meta.row_groups[i].clone()
+            })
+            .collect();
+        prop_assert_eq!(lazy.project(&row_groups, &columns)?, expected);
+        prop_assert!(lazy.row_group(lazy.num_row_groups(), &columns).is_err());
+    }
+
+    #[test]
+    fn lazy_truncated_metadata(meta in file_metadata(0..20, 2..3), len in any::<Index>()) {
+        let buf = encode(&meta);
+        prop_assert!(LazyFileMetaData::try_new(&buf[..len.index(buf.len())]).is_err());
+    }
+
+    /// A mutated field type is followed by the index, which skips by type, but not by the
+    /// full decode, which reads by id, so they may disagree and only panics are checked for.
+    #[test]
+    fn lazy_mutated_metadata(
+        meta in file_metadata(0..20, 0..3),
+        mutations in prop::collection::vec(mutation(), 1..8),
+    ) {
+        let mut buf = encode(&meta);
+        for m in &mutations {
+            m.apply(&mut buf);
+        }
+        if let Ok(lazy) = LazyFileMetaData::try_new(&buf) {
+            let _ = lazy.schema();
+            let columns: Vec<usize> = (0..lazy.num_columns() + 1).collect();
+            for i in 0..lazy.num_row_groups() {
+                let _ = lazy.row_group(i, &columns);
+            }
+        }
+    }
+
+    #[test]
+    fn messages_round_trip(
+        name in "\\PC{0,20}",
+        message_type in message_type(),
+        sequence_number in any::<i32>(),
+        fields in prop::collection::vec((any::<i16>(), value()), 0..8),
+    ) {
+        let message = TMessageIdentifier::new(name, message_type, sequence_number);
+        let buf = encode_message(&message, &fields);
+
+        let mut simd = TCompactSimdInputProtocol::new(&buf);
+        let decoded = read_message(&mut simd);
+        let reference = read_message(&mut TCompactInputProtocol::new(buf.as_slice()));
+        prop_assert_eq!(decoded.as_ref().ok(), reference.as_ref().ok());
+        prop_assert_eq!(decoded.ok(), Some((message, Value::Struct(fields))));
+        prop_assert!(simd.as_slice().is_empty());
+    }
+
+    #[test]
+    fn skip_consumes_the_value(value in value(), trailer in any::<u8>()) {
+        let mut buf = vec![];
+        value.write(&mut TCompactOutputProtocol::new(&mut buf)).unwrap();
+        buf.push(trailer);
+
+        let mut simd = TCompactSimdInputProtocol::new(&buf);
+        simd.skip(value.ttype()).unwrap();
+        prop_assert_eq!(simd.as_slice(), &[trailer][..]);
+    }
+
+    #[test]
+    fn mutated_values_skip_as_they_read(
+        message_type in message_type(),
+        fields in prop::collection::vec((any::<i16>(), value()), 0..8),
+        mutations in prop::collection::vec(mutation(), 1..8),
+    ) {
+        let message = TMessageIdentifier::new("m", message_type, 0);
+        let mut buf = encode_message(&message, &fields);
+        for m in &mutations {
+            m.apply(&mut buf);
+        }
+
+        let mut read = TCompactSimdInputProtocol::new(&buf);
+        let read_result = read_message(&mut read).map(|_| ());
+        let mut skipped = TCompactSimdInputProtocol::new(&buf);
+        let skip_result = skipped
+            .read_message_begin()
+            .and_then(|_| skipped.skip(TType::Struct));
+        match (read_result, skip_result) {
+            (Ok(()), Ok(())) => prop_assert_eq!(read.as_slice().len(), skipped.as_slice().len()),
+            (Err(read_err), Err(skip_err)) => prop_assert_eq!(
+                class(&read_err),
+                class(&skip_err),
+                "read: {}, skip: {}",
+                read_err,
+                skip_err
+            ),
+            (read_result, skip_result) => prop_assert!(
+                false,
+                "read: {:?}, skip: {:?}",
+                read_result,
+                skip_result
+            ),
+        }
+    }
+}
+
+// How the SIMD protocol rejects the input `Reference` leaves unchecked.
+
+const EOF: ErrorClass = ErrorClass::Transport(TransportErrorKind::EndOfFile);
+const INVALID_DATA: ErrorClass = ErrorClass::Protocol(ProtocolErrorKind::InvalidData);
+
+#[test]
+fn rejects_list_sizes_past_the_input() {
+    // Three i32s, with a single byte left.
+    let buf = [0x35, 0x00];
+    let e = TCompactSimdInputProtocol::new(&buf)
+        .read_list_begin()
+        .unwrap_err();
+    assert_eq!(class(&e), EOF);
+}
+
+#[test]
+fn rejects_list_sizes_over_i32() {
+    // 2^31 i32s, the size following the header.
+    let buf = [0xF5, 0x80, 0x80, 0x80, 0x80, 0x08];
+    let e = TCompactSimdInputProtocol::new(&buf)
+        .read_list_begin()
+        .unwrap_err();
+    assert_eq!(class(&e), INVALID_DATA);
+}
+
+#[test]
+fn rejects_map_sizes_past_the_input() {
+    // One i32 to i32 entry, with a single byte left.
+    let buf = [0x01, 0x55, 0x00];
+    let e = TCompactSimdInputProtocol::new(&buf)
+        .read_map_begin()
+        .unwrap_err();
+    assert_eq!(class(&e), EOF);
+}
+
+#[test]
+fn rejects_field_ids_over_i16() {
+    // Field 32767, its id written out, then a field one after it.
+    let buf = [0x05, 0xFE, 0xFF, 0x03, 0x00, 0x15, 0x00];
+    let mut i = TCompactSimdInputProtocol::new(&buf);
+    i.read_struct_begin().unwrap();
+    assert_eq!(i.read_field_begin().unwrap().id, Some(i16::MAX));
+    i.read_i32().unwrap();
+    i.read_field_end().unwrap();
+    let e = i.read_field_begin().unwrap_err();
+    assert_eq!(class(&e), INVALID_DATA);
+}
+
+#[test]
+fn rejects_varints_wider_than_their_type() {
+    let e = TCompactSimdInputProtocol::new(&[0xFF, 0xFF, 0x07])
+        .read_i16()
+        .unwrap_err();
+    assert_eq!(class(&e), INVALID_DATA);
+    let e = TCompactSimdInputProtocol::new(&[0xFF, 0xFF, 0xFF, 0xFF, 0x1F])
+        .read_i32()
+        .unwrap_err();
+    assert_eq!(class(&e), INVALID_DATA);
+    let e = TCompactSimdInputProtocol::new(&[0xFF; 11])
+        .read_i64()
+        .unwrap_err();
+    assert_eq!(class(&e), INVALID_DATA);
+}
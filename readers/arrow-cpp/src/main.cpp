@@ -3,12 +3,160 @@
 #include <arrow/api.h>
 #include <parquet/arrow/reader.h>
 #include <parquet/exception.h>
+#include <arrow/type_traits.h>
+#include <arrow/util/config.h>
+#include <algorithm>
+#include <cstdint>
 #include <filesystem>
+#include <iomanip>
 #include <iostream>
+#include <string_view>
 
 namespace fs = std::filesystem;
 
-arrow::Status read_parquet(std::string input_path, bool print)
+// The content digest of --checksum, encoded and hashed the same way as the arrow-rs
+// reader's, see readers/arrow-rs/src/checksum.rs, so that the harness can compare the
+// digests the readers print.
+namespace checksum
+{
+	constexpr uint64_t FNV_OFFSET = 0xcbf29ce484222325ULL;
+	constexpr uint64_t FNV_PRIME = 0x100000001b3ULL;
+
+	uint64_t fnv1a(const std::string &bytes)
+	{
+		uint64_t hash = FNV_OFFSET;
+		for (unsigned char b : bytes)
+		{
+			hash = (hash ^ b) * FNV_PRIME;
+		}
+		return hash;
+	}
+
+	void put_u64(std::string &out, uint64_t value)
+	{
+		for (int i = 0; i < 8; i++)
+		{
+			out.push_back(static_cast<char>((value >> (8 * i)) & 0xff));
+		}
+	}
+
+	arrow::Status encode(const arrow::Array &array, int64_t row, std::string &out);
+
+	arrow::Status encode_list(const arrow::Array &values, std::string &out)
+	{
+		put_u64(out, values.length());
+		for (int64_t i = 0; i < values.length(); i++)
+		{
+			ARROW_RETURN_NOT_OK(encode(values, i, out));
+		}
+		return arrow::Status::OK();
+	}
+
+	// Appends the encoding of value `row` of `array`: a null is 0x00, any other value 0x01
+	// then its bytes. Dictionaries are encoded as their values, views as strings and binaries.
+	arrow::Status encode(const arrow::Array &array, int64_t row, std::string &out)
+	{
+		if (array.type_id() == arrow::Type::DICTIONARY)
+		{
+			const auto &dictionary = static_cast<const arrow::DictionaryArray &>(array);
+			if (dictionary.IsNull(row))
+			{
+				out.push_back(0);
+				return arrow::Status::OK();
+			}
+			return encode(*dictionary.dictionary(), dictionary.GetValueIndex(row), out);
+		}
+		if (array.type_id() == arrow::Type::NA || array.IsNull(row))
+		{
+			out.push_back(0);
+			return arrow::Status::OK();
+		}
+		out.push_back(1);
+		auto bytes = [&out](std::string_view value)
+		{
+			put_u64(out, value.size());
+			out.append(value);
+		};
+		switch (array.type_id())
+		{
+		case arrow::Type::BOOL:
+			out.push_back(static_cast<const arrow::BooleanArray &>(array).Value(row) ? 1 : 0);
+			break;
+		case arrow::Type::STRING:
+		case arrow::Type::BINARY:
+			bytes(static_cast<const arrow::BinaryArray &>(array).GetView(row));
+			break;
+		case arrow::Type::LARGE_STRING:
+		case arrow::Type::LARGE_BINARY:
+			bytes(static_cast<const arrow::LargeBinaryArray &>(array).GetView(row));
+			break;
+#if ARROW_VERSION_MAJOR >= 15
+		case arrow::Type::STRING_VIEW:
+		case arrow::Type::BINARY_VIEW:
+			bytes(static_cast<const arrow::BinaryViewArray &>(array).GetView(row));
+			break;
+#endif
+		case arrow::Type::STRUCT:
+		{
+			const auto &array_struct = static_cast<const arrow::StructArray &>(array);
+			for (int i = 0; i < array_struct.num_fields(); i++)
+			{
+				ARROW_RETURN_NOT_OK(encode(*array_struct.field(i), row, out));
+			}
+			break;
+		}
+		// A map is a list of its entries, each a struct of the key and the value.
+		case arrow::Type::LIST:
+		case arrow::Type::MAP:
+			return encode_list(*static_cast<const arrow::ListArray &>(array).value_slice(row), out);
+		case arrow::Type::LARGE_LIST:
+			return encode_list(*static_cast<const arrow::LargeListArray &>(array).value_slice(row), out);
+		case arrow::Type::FIXED_SIZE_LIST:
+			return encode_list(*static_cast<const arrow::FixedSizeListArray &>(array).value_slice(row), out);
+		default:
+			if (!arrow::is_fixed_width(array.type_id()))
+			{
+				return arrow::Status::NotImplemented("checksum of ", array.type()->ToString());
+			}
+			// Integers, floats, dates, times, timestamps, decimals, ...: their little endian bytes.
+			int width = static_cast<const arrow::FixedWidthType &>(*array.type()).bit_width() / 8;
+			const uint8_t *values = array.data()->buffers[1]->data();
+			out.append(reinterpret_cast<const char *>(values + (array.offset() + row) * width), width);
+		}
+		return arrow::Status::OK();
+	}
+
+	// Prints the digest of every column, the wrapping sum of the hashes of its values, and
+	// of the row count and every column digest.
+	arrow::Status report(const arrow::Table &table)
+	{
+		std::string digests;
+		put_u64(digests, table.num_rows());
+		std::string buf;
+		for (int i = 0; i < table.num_columns(); i++)
+		{
+			uint64_t digest = 0;
+			for (const auto &chunk : table.column(i)->chunks())
+			{
+				for (int64_t row = 0; row < chunk->length(); row++)
+				{
+					buf.clear();
+					ARROW_RETURN_NOT_OK(encode(*chunk, row, buf));
+					digest += fnv1a(buf);
+				}
+			}
+			const std::string &name = table.schema()->field(i)->name();
+			std::cout << "checksum " << name << ": " << std::hex << std::setw(16) << std::setfill('0') << digest << std::dec << std::endl;
+			digests.append(name);
+			put_u64(digests, digest);
+		}
+		std::cout << "checksum: " << std::hex << std::setw(16) << std::setfill('0') << fnv1a(digests) << std::dec
+				  << " (" << table.num_rows() << " rows)" << std::endl;
+		return arrow::Status::OK();
+	}
+}
+
+arrow::Status read_parquet(std::string input_path, bool print, bool print_checksum)
 {
 	arrow::MemoryPool *pool = arrow::default_memory_pool();
 	std::shared_ptr<arrow::io::RandomAccessFile> input;
@@ -26,6 +174,10 @@ arrow::Status read_parquet(std::string input_path, bool print)
 		std::cout << "Table: " << std::endl;
 		std::cout << table->ToString() << std::endl;
 	}
+	if (print_checksum)
+	{
+		ARROW_RETURN_NOT_OK(checksum::report(*table));
+	}
 	return arrow::Status::OK();
 }
 
@@ -33,7 +185,7 @@ int main(int argc, char **argv)
 {
 	if (argc < 2)
 	{
-		std::cerr << "Usage: " << argv[0] << " <input_parquet_file> [-p|--print]" << std::endl;
+		std::cerr << "Usage: " << argv[0] << " <input_parquet_file> [-p|--print] [--checksum]" << std::endl;
 		return -1;
 	}
 
@@ -47,8 +199,9 @@ int main(int argc, char **argv)
 	{
 		print = true;
 	}
+	bool print_checksum = std::find(args.begin(), args.end(), "--checksum") != args.end();
 
-	auto status = read_parquet(input_path, print);
+	auto status = read_parquet(input_path, print, print_checksum);
 	if (status != arrow::Status::OK())
 	{
 		std::cerr << "Error reading parquet file: " << status.ToString() << std::endl;
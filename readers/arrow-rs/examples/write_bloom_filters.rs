@@ -0,0 +1,59 @@
+//! Rewrites a parquet file with bloom filters, so that `--bloom-filter` has something to
+//! probe: none of the workload writers emit them.
+//!
+//! ```text
+//! cargo run --release --example write_bloom_filters -- <input> <output> [column,...]
+//! ```
+//!
+//! Filters are written for the given top-level columns, or for every column. The row
+//! group size and the compression of the input are kept.
+
+use std::error::Error;
+use std::fs::File;
+
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use parquet::arrow::ArrowWriter;
+use parquet::file::properties::WriterProperties;
+use parquet::schema::types::ColumnPath;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args: Vec<String> = std::env::args().collect();
+    let (input, output) = match args.as_slice() {
+        [_, input, output, ..] => (input, output),
+        _ => return Err("usage: write_bloom_filters <input> <output> [column,...]".into()),
+    };
+    let columns: Option<Vec<&str>> = args.get(3).map(|c| c.split(',').collect());
+
+    let builder = ParquetRecordBatchReaderBuilder::try_new(File::open(input)?)?;
+    let metadata = builder.metadata().clone();
+    let mut properties = WriterProperties::builder();
+    if let Some(rg) = metadata.row_groups().first() {
+        properties = properties.set_max_row_group_size(rg.num_rows().max(1) as usize);
+        if let Some(column) = rg.columns().first() {
+            properties = properties.set_compression(column.compression());
+        }
+    }
+    let descr = metadata.file_metadata().schema_descr();
+    let mut filtered = 0;
+    for leaf in descr.columns() {
+        let root = leaf.path().parts()[0].as_str();
+        if columns.as_ref().is_none_or(|c| c.contains(&root)) {
+            let path = ColumnPath::new(leaf.path().parts().to_vec());
+            properties = properties.set_column_bloom_filter_enabled(path, true);
+            filtered += 1;
+        }
+    }
+    if filtered == 0 {
+        let wanted = args.get(3).map_or("", |c| c.as_str());
+        return Err(format!("{} has none of the columns {:?}", input, wanted).into());
+    }
+
+    let schema = builder.schema().clone();
+    let mut writer = ArrowWriter::try_new(File::create(output)?, schema, Some(properties.build()))?;
+    for batch in builder.build()? {
+        writer.write(&batch?)?;
+    }
+    writer.close()?;
+    println!("{}: bloom filters on {} columns", output, filtered);
+    Ok(())
+}
@@ -0,0 +1,225 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::future::{ready, Future};
+use std::time::Instant;
+
+use arrow::array::RecordBatch;
+use futures::{StreamExt, TryStreamExt};
+use parquet::arrow::arrow_reader::ArrowReaderOptions;
+use parquet::arrow::async_reader::AsyncFileReader;
+use parquet::arrow::ParquetRecordBatchStreamBuilder;
+use parquet::file::metadata::ParquetMetaDataReader;
+
+use crate::error::{ReadError, Stage};
+use crate::metrics::{nanos, Metrics};
+use crate::prefetch::{IoPlan, Prefetch, PrefetchOptions, PrefetchReader};
+use crate::scan::{self, ScanOptions, ScanPlan};
+
+/// Reads through [`ParquetRecordBatchStreamBuilder`], with up to `concurrency` row groups
+/// in flight, each through its own reader from `open`.
+///
+/// The footer is read once and shared by every row group stream. Phase timings are
+/// added to `metrics`, so a caller may account for opening the store beforehand. A
+/// decode error ends its row group, see [`ScanOptions::keep_going`]. With `prefetch`, the
+/// column chunks are planned up front and read through [`PrefetchReader`]s. Batches are
+/// passed to `on_batch` as they are decoded, or in file order if [`ScanPlan::is_ordered`].
+pub async fn read<R, F, Fut>(
+    open: F,
+    options: ArrowReaderOptions,
+    scan: &ScanOptions,
+    concurrency: usize,
+    prefetch: Option<PrefetchOptions>,
+    metrics: &mut Metrics,
+    mut on_batch: impl FnMut(&ScanPlan, RecordBatch) -> Result<(), Box<dyn Error>>,
+) -> Result<ScanPlan, Box<dyn Error>>
+where
+    R: AsyncFileReader + Unpin + Send + 'static,
+    F: Fn() -> Fut,
+    Fut: Future<Output = parquet::errors::Result<R>> + Send + 'static,
+{
+    let now = Instant::now();
+    let mut input = open().await.map_err(ReadError::at(Stage::Open))?;
+    metrics.open_time_nanos += nanos(now.elapsed());
+
+    let now = Instant::now();
+    metrics.footers_read += 1;
+    let mut metadata = input
+        .get_metadata()
+        .await
+        .map_err(ReadError::at(Stage::Footer))?;
+    metrics.metadata_decode_time_nanos = nanos(now.elapsed());
+
+    if scan.needs_page_index() {
+        let now = Instant::now();
+        let mut reader = ParquetMetaDataReader::new_with_metadata(metadata.as_ref().clone())
+            .with_page_indexes(true);
+        reader
+            .load_page_index(&mut input)
+            .await
+            .map_err(ReadError::at(Stage::PageIndex))?;
+        metadata = reader
+            .finish()
+            .map_err(ReadError::at(Stage::PageIndex))?
+            .into();
+        metrics.page_index_load_time_nanos = nanos(now.elapsed());
+    }
+
+    let now = Instant::now();
+    let metadata =
+        scan::arrow_metadata(metadata, options, scan).map_err(ReadError::at(Stage::Schema))?;
+    metrics.schema_build_time_nanos = nanos(now.elapsed());
+
+    let mut plan = ScanPlan::try_new(&metadata, scan)?;
+
+    if scan.bloom_filter {
+        let now = Instant::now();
+        plan.probe_bloom_filters(input, &metadata)
+            .await
+            .map_err(ReadError::at(Stage::BloomFilter))?;
+        metrics.bloom_filter_time_nanos = nanos(now.elapsed());
+    }
+
+    let mut io_plan = None;
+    let mut driver = None;
+    let mut prefetched = Prefetch::default();
+    if let Some(options) = prefetch {
+        let schema = metadata.metadata().file_metadata().schema_descr();
+        let leaves = plan.decoded_leaves(schema);
+        let p = IoPlan::new(
+            metadata.metadata(),
+            &plan.row_groups,
+            &leaves,
+            options.coalesce,
+        );
+        prefetched = Prefetch::new(&p, &open);
+        if options.depth > 0 {
+            driver = Some(tokio::spawn(prefetched.clone().drive(options.depth)));
+        }
+        io_plan = Some(p);
+    }
+
+    let now = Instant::now();
+    let mut in_order = plan.is_ordered().then(InOrder::default);
+    let mut batches =
+        futures::stream::iter(plan.split_row_groups(&metadata).into_iter().enumerate())
+            .map(|(position, (row_group, selection))| {
+                let (plan, metadata, input) = (&plan, metadata.clone(), open());
+                let prefetched = prefetched.clone();
+                let error = move |e| ReadError::new(Stage::RowGroup(row_group), e);
+                async move {
+                    let stream = async {
+                        let input = PrefetchReader::new(input.await?, prefetched, row_group);
+                        let builder =
+                            ParquetRecordBatchStreamBuilder::new_with_metadata(input, metadata);
+                        plan.apply_to(builder, vec![row_group], selection)?.build()
+                    };
+                    let batches = match stream.await {
+                        Ok(stream) => stream.map_err(error).left_stream(),
+                        Err(e) => futures::stream::once(ready(Err(error(e)))).right_stream(),
+                    };
+                    // Every row group ends with `None`, for `InOrder` to move on to the next.
+                    batches
+                        .map(Some)
+                        .chain(futures::stream::once(ready(None)))
+                        .map(move |batch| (position, batch))
+                }
+            })
+            .buffered(concurrency)
+            .flatten_unordered(concurrency);
+
+    'read: while let Some((position, batch)) = batches.next().await {
+        let ready = match &mut in_order {
+            Some(in_order) => in_order.push(position, batch),
+            None => batch.into_iter().collect(),
+        };
+        for batch in ready {
+            match batch {
+                Ok(batch) => {
+                    metrics.record_batch(&batch);
+                    on_batch(&plan, batch)?;
+                    if plan.is_done() {
+                        break 'read;
+                    }
+                }
+                Err(e) if scan.keep_going => {
+                    eprintln!("error: {}", e);
+                    metrics.errors += 1;
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+    drop(batches);
+    metrics.decode_time_nanos = nanos(now.elapsed());
+
+    if let Some(driver) = driver {
+        driver.abort();
+    }
+    if let Some(io_plan) = io_plan {
+        io_plan.report();
+    }
+
+    Ok(plan)
+}
+
+/// Holds back the batches of a row group until every row group before it has ended, so
+/// that they are passed on in file order. At most `concurrency` row groups are decoded at
+/// once, so the row groups held back are bounded by that too.
+struct InOrder<T> {
+    next: usize,
+    /// Batches of the row groups at and after `next`, and whether they have ended.
+    pending: BTreeMap<usize, (Vec<T>, bool)>,
+}
+
+impl<T> Default for InOrder<T> {
+    fn default() -> Self {
+        Self {
+            next: 0,
+            pending: BTreeMap::new(),
+        }
+    }
+}
+
+impl<T> InOrder<T> {
+    /// Takes a batch of the row group at `position`, or its end if `None`, and returns the
+    /// batches that are now in order.
+    fn push(&mut self, position: usize, batch: Option<T>) -> Vec<T> {
+        let entry = self.pending.entry(position).or_default();
+        match batch {
+            Some(batch) => entry.0.push(batch),
+            None => entry.1 = true,
+        }
+        let mut ready = vec![];
+        while let Some(mut entry) = self.pending.first_entry() {
+            if *entry.key() != self.next {
+                break;
+            }
+            ready.append(&mut entry.get_mut().0);
+            if !entry.get().1 {
+                break;
+            }
+            entry.remove();
+            self.next += 1;
+        }
+        ready
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn in_order_holds_back_later_row_groups() {
+        let mut in_order = InOrder::default();
+        assert_eq!(in_order.push(1, Some("b1")), Vec::<&str>::new());
+        assert_eq!(in_order.push(0, Some("a1")), vec!["a1"]);
+        assert_eq!(in_order.push(2, Some("c1")), Vec::<&str>::new());
+        assert_eq!(in_order.push(1, None), Vec::<&str>::new());
+        assert_eq!(in_order.push(0, Some("a2")), vec!["a2"]);
+        assert_eq!(in_order.push(0, None), vec!["b1", "c1"]);
+        assert_eq!(in_order.push(2, Some("c2")), vec!["c2"]);
+        assert_eq!(in_order.push(2, None), Vec::<&str>::new());
+        assert!(in_order.pending.is_empty());
+    }
+}
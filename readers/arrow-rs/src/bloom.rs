@@ -0,0 +1,544 @@
+//! Row group pruning with the split block bloom filters of column chunks.
+//!
+//! Only `=` and `IN` terms are probed, a filter can say a value is definitely absent but
+//! never that it is present. Only row groups that survived statistics pruning are probed.
+//! The async readers read one column chunk filter at a time through
+//! [`ParquetRecordBatchStreamBuilder::get_row_group_column_bloom_filter`], so only the
+//! filters of predicate columns are read. The sync reader reads them through
+//! [`SerializedRowGroupReader`], which reads every filter of a row group at once.
+
+use std::collections::HashMap;
+use std::ops::Range;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use arrow::array::{AsArray, Datum};
+use arrow::compute::cast;
+use arrow::datatypes::{DataType, Float32Type, Float64Type, Int32Type, Int64Type, Schema};
+use bytes::Bytes;
+use futures::future::BoxFuture;
+use parquet::arrow::arrow_reader::ArrowReaderMetadata;
+use parquet::arrow::async_reader::AsyncFileReader;
+use parquet::arrow::ParquetRecordBatchStreamBuilder;
+use parquet::basic::Type as PhysicalType;
+use parquet::bloom_filter::Sbbf;
+use parquet::errors::Result;
+use parquet::file::metadata::ParquetMetaData;
+use parquet::file::properties::ReaderProperties;
+use parquet::file::reader::{ChunkReader, Length, RowGroupReader};
+use parquet::file::serialized_reader::SerializedRowGroupReader;
+
+use crate::predicate::{CmpOp, Literal, Predicate};
+use crate::projection::leaves_for_path;
+use crate::pruning::field_type;
+
+/// What probing the bloom filters cost and what it skipped.
+#[derive(Debug, Default)]
+pub struct BloomProbe {
+    /// Row groups that can still match, in order.
+    pub row_groups: Vec<usize>,
+    pub row_groups_skipped: usize,
+    /// Column chunk filters read, and the bytes and requests it took.
+    pub filters_read: usize,
+    pub filter_bytes: usize,
+    pub requests: usize,
+    /// Compressed bytes of the decoded column chunks in skipped row groups.
+    pub bytes_saved: usize,
+}
+
+/// Probes the filters of `row_groups` for the `=` and `IN` terms of `predicate`. `leaves`
+/// are the columns the scan decodes, for counting the bytes saved.
+pub async fn probe<T: AsyncFileReader + Send + 'static>(
+    input: T,
+    metadata: &ArrowReaderMetadata,
+    predicate: &Predicate,
+    row_groups: &[usize],
+    leaves: &[usize],
+) -> Result<BloomProbe> {
+    let columns = probe_columns(predicate, metadata.metadata(), metadata.schema())?;
+    let counters = Arc::new(Counters::default());
+    let input = CountingInput {
+        inner: input,
+        counters: counters.clone(),
+    };
+    let mut builder = ParquetRecordBatchStreamBuilder::new_with_metadata(input, metadata.clone());
+
+    let mut probe = BloomProbe::default();
+    for &rg in row_groups {
+        // The filter of every probed column in this row group, `None` if it has none.
+        let mut filters = HashMap::new();
+        for &(leaf, _) in columns.values() {
+            let filter = builder.get_row_group_column_bloom_filter(rg, leaf).await?;
+            probe.filters_read += filter.is_some() as usize;
+            filters.insert(leaf, filter);
+        }
+        let filter = |leaf: usize| filters[&leaf].as_ref();
+        let matches = row_group_may_match(predicate, &columns, &filter);
+        probe.record(metadata.metadata(), rg, leaves, matches);
+    }
+
+    probe.filter_bytes = counters.bytes.load(Ordering::Relaxed);
+    probe.requests = counters.requests.load(Ordering::Relaxed);
+    Ok(probe)
+}
+
+/// Like [`probe`], reading the filters of the sync reader from its [`ChunkReader`].
+pub fn probe_sync<R: ChunkReader + 'static>(
+    input: R,
+    metadata: &ArrowReaderMetadata,
+    predicate: &Predicate,
+    row_groups: &[usize],
+    leaves: &[usize],
+) -> Result<BloomProbe> {
+    let parquet_metadata = metadata.metadata();
+    let columns = probe_columns(predicate, parquet_metadata, metadata.schema())?;
+    if columns.is_empty() {
+        // Nothing to probe, and the row group reader would read every filter regardless.
+        return Ok(BloomProbe {
+            row_groups: row_groups.to_vec(),
+            ..Default::default()
+        });
+    }
+    let counters = Arc::new(Counters::default());
+    let input = Arc::new(CountingInput {
+        inner: input,
+        counters: counters.clone(),
+    });
+    let properties = Arc::new(
+        ReaderProperties::builder()
+            .set_read_bloom_filter(true)
+            .build(),
+    );
+
+    let mut probe = BloomProbe::default();
+    for &rg in row_groups {
+        let reader = SerializedRowGroupReader::new(
+            input.clone(),
+            parquet_metadata.row_group(rg),
+            None,
+            properties.clone(),
+        )?;
+        probe.filters_read += (0..reader.num_columns())
+            .filter(|&i| reader.get_column_bloom_filter(i).is_some())
+            .count();
+        let filter = |leaf: usize| reader.get_column_bloom_filter(leaf);
+        let matches = row_group_may_match(predicate, &columns, &filter);
+        probe.record(parquet_metadata, rg, leaves, matches);
+    }
+
+    probe.filter_bytes = counters.bytes.load(Ordering::Relaxed);
+    probe.requests = counters.requests.load(Ordering::Relaxed);
+    Ok(probe)
+}
+
+impl BloomProbe {
+    /// Keeps `row_group` if it `matches`, or counts what skipping it saves.
+    fn record(
+        &mut self,
+        metadata: &ParquetMetaData,
+        row_group: usize,
+        leaves: &[usize],
+        matches: bool,
+    ) {
+        if matches {
+            self.row_groups.push(row_group);
+        } else {
+            self.row_groups_skipped += 1;
+            let rg = metadata.row_group(row_group);
+            self.bytes_saved += leaves
+                .iter()
+                .map(|&leaf| rg.column(leaf).compressed_size() as usize)
+                .sum::<usize>();
+        }
+    }
+}
+
+/// Whether a row group whose filters `filter` returns by leaf may match `predicate`.
+/// Columns that are not probed, or have no filter, always may.
+fn row_group_may_match<'a>(
+    predicate: &Predicate,
+    columns: &HashMap<&str, (usize, DataType)>,
+    filter: &dyn Fn(usize) -> Option<&'a Sbbf>,
+) -> bool {
+    let check = |column: &str, literal: &Literal| {
+        let Some((leaf, data_type)) = columns.get(column) else {
+            return true;
+        };
+        match (filter(*leaf), PhysicalValue::new(literal, data_type)) {
+            (Some(filter), Some(value)) => value.check(filter),
+            _ => true,
+        }
+    };
+    may_contain(predicate, &check)
+}
+
+/// Columns of `=` and `IN` terms backed by a single leaf whose values can be hashed like
+/// the writer did, with the Arrow type literals are cast to.
+fn probe_columns<'a>(
+    predicate: &'a Predicate,
+    metadata: &ParquetMetaData,
+    schema: &Schema,
+) -> Result<HashMap<&'a str, (usize, DataType)>> {
+    fn collect<'a>(predicate: &'a Predicate, out: &mut Vec<&'a str>) {
+        match predicate {
+            Predicate::And(l, r) | Predicate::Or(l, r) => {
+                collect(l, out);
+                collect(r, out);
+            }
+            Predicate::Compare {
+                column,
+                op: CmpOp::Eq,
+                ..
+            }
+            | Predicate::In {
+                column,
+                negated: false,
+                ..
+            } => out.push(column),
+            _ => {}
+        }
+    }
+    let mut probed = vec![];
+    collect(predicate, &mut probed);
+
+    let descr = metadata.file_metadata().schema_descr();
+    let mut columns = HashMap::new();
+    for column in probed {
+        let leaves = leaves_for_path(descr, column)?;
+        if let ([leaf], Some(data_type)) = (leaves.as_slice(), field_type(schema, column)) {
+            // Legacy INT96 timestamps are hashed as 12 bytes, not as the i64 they decode to.
+            if descr.column(*leaf).physical_type() != PhysicalType::INT96 {
+                columns.insert(column, (*leaf, data_type));
+            }
+        }
+    }
+    Ok(columns)
+}
+
+/// Returns false only if the filters rule out every row.
+fn may_contain(predicate: &Predicate, check: &dyn Fn(&str, &Literal) -> bool) -> bool {
+    match predicate {
+        Predicate::And(l, r) => may_contain(l, check) && may_contain(r, check),
+        Predicate::Or(l, r) => may_contain(l, check) || may_contain(r, check),
+        Predicate::Compare {
+            column,
+            op: CmpOp::Eq,
+            value,
+        } => check(column, value),
+        Predicate::In {
+            column,
+            values,
+            negated: false,
+        } => values.iter().any(|value| check(column, value)),
+        _ => true,
+    }
+}
+
+/// A literal in the physical type the writer hashed.
+enum PhysicalValue {
+    Bool(bool),
+    Int32(i32),
+    Int64(i64),
+    Float(f32),
+    Double(f64),
+    Bytes(Vec<u8>),
+}
+
+impl PhysicalValue {
+    fn new(literal: &Literal, data_type: &DataType) -> Option<Self> {
+        let scalar = literal.to_scalar(data_type).ok()?;
+        let (array, _) = scalar.get();
+        let value = match data_type {
+            DataType::Boolean => Self::Bool(array.as_boolean().value(0)),
+            DataType::Int8 | DataType::Int16 | DataType::Int32 | DataType::Date32 => {
+                let array = cast(array, &DataType::Int32).ok()?;
+                Self::Int32(array.as_primitive::<Int32Type>().value(0))
+            }
+            DataType::Int64 | DataType::Timestamp(_, _) => {
+                let array = cast(array, &DataType::Int64).ok()?;
+                Self::Int64(array.as_primitive::<Int64Type>().value(0))
+            }
+            DataType::Float32 => Self::Float(array.as_primitive::<Float32Type>().value(0)),
+            DataType::Float64 => Self::Double(array.as_primitive::<Float64Type>().value(0)),
+            DataType::Utf8 => Self::Bytes(array.as_string::<i32>().value(0).into()),
+            DataType::LargeUtf8 => Self::Bytes(array.as_string::<i64>().value(0).into()),
+            DataType::Binary => Self::Bytes(array.as_binary::<i32>().value(0).into()),
+            DataType::LargeBinary => Self::Bytes(array.as_binary::<i64>().value(0).into()),
+            _ => return None,
+        };
+        Some(value)
+    }
+
+    fn check(&self, filter: &Sbbf) -> bool {
+        match self {
+            Self::Bool(v) => filter.check(v),
+            Self::Int32(v) => filter.check(v),
+            Self::Int64(v) => filter.check(v),
+            Self::Float(v) => filter.check(v),
+            Self::Double(v) => filter.check(v),
+            Self::Bytes(v) => filter.check(v),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    bytes: AtomicUsize,
+    requests: AtomicUsize,
+}
+
+/// Counts the bytes and requests of the filter reads, of an [`AsyncFileReader`] or of a
+/// [`ChunkReader`].
+struct CountingInput<T> {
+    inner: T,
+    counters: Arc<Counters>,
+}
+
+impl<T: AsyncFileReader> AsyncFileReader for CountingInput<T> {
+    fn get_bytes(&mut self, range: Range<usize>) -> BoxFuture<'_, Result<Bytes>> {
+        self.counters
+            .bytes
+            .fetch_add(range.len(), Ordering::Relaxed);
+        self.counters.requests.fetch_add(1, Ordering::Relaxed);
+        self.inner.get_bytes(range)
+    }
+
+    fn get_metadata(&mut self) -> BoxFuture<'_, Result<Arc<ParquetMetaData>>> {
+        self.inner.get_metadata()
+    }
+}
+
+impl<R: ChunkReader> Length for CountingInput<R> {
+    fn len(&self) -> u64 {
+        self.inner.len()
+    }
+}
+
+impl<R: ChunkReader> ChunkReader for CountingInput<R> {
+    type T = R::T;
+
+    fn get_read(&self, start: u64) -> Result<Self::T> {
+        self.counters.requests.fetch_add(1, Ordering::Relaxed);
+        self.inner.get_read(start)
+    }
+
+    fn get_bytes(&self, start: u64, length: usize) -> Result<Bytes> {
+        self.counters.bytes.fetch_add(length, Ordering::Relaxed);
+        self.counters.requests.fetch_add(1, Ordering::Relaxed);
+        self.inner.get_bytes(start, length)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{
+        ArrayRef, BinaryArray, BooleanArray, Date32Array, Float32Array, Float64Array, Int16Array,
+        Int32Array, Int64Array, Int8Array, LargeStringArray, RecordBatch, StringArray,
+        TimestampMillisecondArray,
+    };
+    use arrow::datatypes::TimeUnit;
+    use futures::FutureExt;
+    use parquet::arrow::{parquet_to_arrow_schema, ArrowWriter};
+    use parquet::file::metadata::{FileMetaData, ParquetMetaDataReader};
+    use parquet::file::properties::WriterProperties;
+    use parquet::schema::parser::parse_message_type;
+    use parquet::schema::types::{ColumnPath, SchemaDescriptor};
+
+    /// Serves the async probe from memory.
+    struct BytesInput(Bytes);
+
+    impl AsyncFileReader for BytesInput {
+        fn get_bytes(&mut self, range: Range<usize>) -> BoxFuture<'_, Result<Bytes>> {
+            futures::future::ready(Ok(self.0.slice(range))).boxed()
+        }
+
+        fn get_metadata(&mut self) -> BoxFuture<'_, Result<Arc<ParquetMetaData>>> {
+            unreachable!("the probe is given the metadata")
+        }
+    }
+
+    /// Writes `values` as column `c`, two rows per row group, with a bloom filter on `c`
+    /// the way `write_bloom_filters` does, and returns the row groups the sync and the
+    /// async probe keep for `predicate`, and how many filters they read.
+    fn probed(values: ArrayRef, predicate: &str) -> (Vec<usize>, usize) {
+        let batch = RecordBatch::try_from_iter([("c", values)]).unwrap();
+        let properties = WriterProperties::builder()
+            .set_max_row_group_size(2)
+            .set_column_bloom_filter_enabled(ColumnPath::from("c"), true)
+            // Small filters, for the test to be quick.
+            .set_column_bloom_filter_ndv(ColumnPath::from("c"), 100)
+            .build();
+        let mut buf = vec![];
+        let mut writer = ArrowWriter::try_new(&mut buf, batch.schema(), Some(properties)).unwrap();
+        writer.write(&batch).unwrap();
+        writer.close().unwrap();
+        let file = Bytes::from(buf);
+        let metadata = ParquetMetaDataReader::new()
+            .parse_and_finish(&file)
+            .unwrap();
+        let metadata =
+            ArrowReaderMetadata::try_new(Arc::new(metadata), Default::default()).unwrap();
+
+        let predicate = Predicate::parse(predicate).unwrap();
+        let row_groups: Vec<usize> = (0..metadata.metadata().num_row_groups()).collect();
+        let sync = probe_sync(file.clone(), &metadata, &predicate, &row_groups, &[0]).unwrap();
+        let input = BytesInput(file);
+        let probe = probe(input, &metadata, &predicate, &row_groups, &[0]);
+        let probe = futures::executor::block_on(probe).unwrap();
+        assert_eq!(sync.row_groups, probe.row_groups);
+        assert_eq!(sync.filters_read, probe.filters_read);
+        assert_eq!(
+            probe.row_groups_skipped,
+            row_groups.len() - probe.row_groups.len()
+        );
+        (probe.row_groups, probe.filters_read)
+    }
+
+    #[test]
+    fn absent_values_prune_for_every_physical_type() {
+        let columns: Vec<(ArrayRef, [&str; 3])> = vec![
+            (
+                Arc::new(Int8Array::from(vec![1, 2, 3, 4])),
+                ["3", "5", "5, 1"],
+            ),
+            (
+                Arc::new(Int16Array::from(vec![1, 2, 3, 4])),
+                ["3", "5", "5, 1"],
+            ),
+            (
+                Arc::new(Int32Array::from(vec![1, 2, 3, 4])),
+                ["3", "5", "5, 1"],
+            ),
+            (
+                Arc::new(Date32Array::from(vec![1, 2, 3, 4])),
+                ["3", "5", "5, 1"],
+            ),
+            (
+                Arc::new(Int64Array::from(vec![1, 2, 3, 4])),
+                ["3", "5", "5, 1"],
+            ),
+            (
+                Arc::new(TimestampMillisecondArray::from(vec![1, 2, 3, 4])),
+                ["3", "5", "5, 1"],
+            ),
+            (
+                Arc::new(Float32Array::from(vec![1.5, 2.5, 3.5, 4.5])),
+                ["3.5", "5.5", "5.5, 1.5"],
+            ),
+            (
+                Arc::new(Float64Array::from(vec![1.5, 2.5, 3.5, 4.5])),
+                ["3.5", "5.5", "5.5, 1.5"],
+            ),
+            (
+                Arc::new(StringArray::from(vec!["a", "b", "c", "d"])),
+                ["'c'", "'e'", "'e', 'a'"],
+            ),
+            (
+                Arc::new(LargeStringArray::from(vec!["a", "b", "c", "d"])),
+                ["'c'", "'e'", "'e', 'a'"],
+            ),
+            (
+                Arc::new(BinaryArray::from(vec![&b"a"[..], b"b", b"c", b"d"])),
+                ["'c'", "'e'", "'e', 'a'"],
+            ),
+        ];
+        for (values, [second, absent, in_first]) in columns {
+            let data_type = values.data_type().clone();
+            let kept = probed(values.clone(), &format!("c = {}", second));
+            assert_eq!(kept, (vec![1], 2), "{}", data_type);
+            let kept = probed(values.clone(), &format!("c = {}", absent));
+            assert_eq!(kept, (vec![], 2), "{}", data_type);
+            let kept = probed(values, &format!("c IN ({})", in_first));
+            assert_eq!(kept, (vec![0], 2), "{}", data_type);
+        }
+
+        let values = Arc::new(BooleanArray::from(vec![true, true, false, false]));
+        assert_eq!(probed(values.clone(), "c = false"), (vec![1], 2));
+        assert_eq!(probed(values, "c = true"), (vec![0], 2));
+    }
+
+    #[test]
+    fn unprobed_terms_keep_row_groups() {
+        let values: ArrayRef = Arc::new(Int64Array::from(vec![1, 2, 3, 4]));
+        assert_eq!(probed(values.clone(), "c > 10"), (vec![0, 1], 0));
+        assert_eq!(probed(values.clone(), "c NOT IN (1, 2)"), (vec![0, 1], 0));
+        assert_eq!(probed(values.clone(), "c = 10 OR c < 0"), (vec![0, 1], 2));
+        assert_eq!(probed(values, "c = 10 AND c < 0"), (vec![], 2));
+    }
+
+    #[test]
+    fn may_contain_follows_the_predicate() {
+        // Only `a = 1` may be present.
+        let check = |column: &str, literal: &Literal| column == "a" && *literal == Literal::Int(1);
+        let may = |predicate: &str| may_contain(&Predicate::parse(predicate).unwrap(), &check);
+        assert!(may("a = 1"));
+        assert!(!may("a = 2"));
+        assert!(!may("b = 1"));
+        assert!(may("a = 2 OR a = 1"));
+        assert!(!may("a = 1 AND b = 1"));
+        assert!(may("a IN (2, 1)"));
+        assert!(!may("a IN (2, 3)"));
+        assert!(may("a NOT IN (2, 3)"));
+        assert!(may("a > 2"));
+        assert!(may("a = 2 OR b IS NULL"));
+    }
+
+    #[test]
+    fn literals_take_the_physical_type_of_the_column() {
+        let value =
+            |literal: Literal, data_type: DataType| PhysicalValue::new(&literal, &data_type);
+        assert!(matches!(
+            value(Literal::Int(3), DataType::Int8),
+            Some(PhysicalValue::Int32(3))
+        ));
+        assert!(matches!(
+            value(Literal::Int(3), DataType::Date32),
+            Some(PhysicalValue::Int32(3))
+        ));
+        assert!(matches!(
+            value(
+                Literal::Int(3),
+                DataType::Timestamp(TimeUnit::Microsecond, None)
+            ),
+            Some(PhysicalValue::Int64(3))
+        ));
+        assert!(matches!(
+            value(Literal::Float(1.5), DataType::Float32),
+            Some(PhysicalValue::Float(v)) if v == 1.5
+        ));
+        assert!(matches!(
+            value(Literal::Int(2), DataType::Float64),
+            Some(PhysicalValue::Double(v)) if v == 2.0
+        ));
+        assert!(matches!(
+            value(Literal::Str("x".into()), DataType::LargeBinary),
+            Some(PhysicalValue::Bytes(v)) if v == b"x"
+        ));
+        assert!(matches!(
+            value(Literal::Bool(true), DataType::Boolean),
+            Some(PhysicalValue::Bool(true))
+        ));
+        // Literals that do not fit the column, and types the filters are not probed for.
+        assert!(value(Literal::Int(300), DataType::Int8).is_none());
+        assert!(value(Literal::Float(1.5), DataType::Int32).is_none());
+        assert!(value(Literal::Int(1), DataType::Decimal128(10, 2)).is_none());
+    }
+
+    #[test]
+    fn int96_columns_are_not_probed() {
+        let message =
+            "message m { required int96 legacy; required int64 ts (TIMESTAMP(NANOS,false)); }";
+        let descr = Arc::new(SchemaDescriptor::new(Arc::new(
+            parse_message_type(message).unwrap(),
+        )));
+        let schema = parquet_to_arrow_schema(&descr, None).unwrap();
+        assert_eq!(schema.field(0).data_type(), schema.field(1).data_type());
+        let metadata =
+            ParquetMetaData::new(FileMetaData::new(1, 0, None, None, descr, None), vec![]);
+
+        let predicate = Predicate::parse("legacy = 1 AND ts = 1").unwrap();
+        let columns = probe_columns(&predicate, &metadata, &schema).unwrap();
+        assert_eq!(columns.keys().collect::<Vec<_>>(), vec![&"ts"]);
+    }
+}
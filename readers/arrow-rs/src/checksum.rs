@@ -0,0 +1,247 @@
+//! A content digest of everything a run returns, so that the ways this reader can read a
+//! file, sync or async, threaded, through a memory map, io_uring or an object store, with
+//! any `--strings` or `--batch-size`, can be checked against each other rather than only
+//! timed. The pyarrow and arrow-cpp readers print the same digest with `--checksum`,
+//! and `benchmark.py` compares the digests of every reader.
+//!
+//! Every value is encoded to a canonical byte string and hashed with 64-bit FNV-1a:
+//!
+//! - null: `0x00`
+//! - booleans: `0x01` then one byte, `0` or `1`
+//! - fixed width values (integers, floats, dates, timestamps, decimals, ...): `0x01` then
+//!   the little endian bytes of the value
+//! - strings and binary: `0x01`, the length as a little endian u64, then the bytes
+//! - structs: `0x01` then the encoding of each child in order
+//! - lists: `0x01`, the length as a little endian u64, then the encoding of each element
+//! - maps: as a list of their entries, each a struct of the key and the value
+//!
+//! Every value of a `Null` column is a null. Dictionaries are hashed as their values and
+//! Utf8View/BinaryView as Utf8/Binary, so the digest does not depend on the Arrow type a
+//! reader decodes into. A column's digest is the wrapping sum of the hashes of its values,
+//! which makes it independent of both batch boundaries and row order: `--async` and
+//! `--threads` return row groups in any order.
+//!
+//! Each column is summed on its own, so the digest does not tie values to their rows
+//! across columns: a reader that returned the right values of every column, but in a
+//! different row order for some of them, would go unnoticed.
+
+use std::sync::Arc;
+
+use arrow::array::{downcast_primitive_array, Array, ArrayRef, AsArray, RecordBatch, StructArray};
+use arrow::compute::cast;
+use arrow::datatypes::{DataType, Field, ToByteSlice};
+use arrow::error::ArrowError;
+
+const FNV_OFFSET: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(FNV_OFFSET, |hash, &b| {
+        (hash ^ b as u64).wrapping_mul(FNV_PRIME)
+    })
+}
+
+/// Per-column digests of every batch passed to [`Checksum::update`].
+#[derive(Debug, Default)]
+pub struct Checksum {
+    columns: Vec<(String, u64)>,
+    rows: usize,
+    buf: Vec<u8>,
+}
+
+impl Checksum {
+    pub fn update(&mut self, batch: &RecordBatch) -> Result<(), ArrowError> {
+        if self.columns.is_empty() {
+            self.columns = batch
+                .schema()
+                .fields()
+                .iter()
+                .map(|f| (f.name().clone(), 0))
+                .collect();
+        }
+        for (i, column) in batch.columns().iter().enumerate() {
+            let column = normalize(column)?;
+            let digest = &mut self.columns[i].1;
+            for row in 0..column.len() {
+                self.buf.clear();
+                encode(&column, row, &mut self.buf)?;
+                *digest = digest.wrapping_add(fnv1a(&self.buf));
+            }
+        }
+        self.rows += batch.num_rows();
+        Ok(())
+    }
+
+    /// The digest of the row count and every column digest, in schema order.
+    pub fn digest(&self) -> u64 {
+        let mut buf = (self.rows as u64).to_le_bytes().to_vec();
+        for (name, digest) in &self.columns {
+            buf.extend_from_slice(name.as_bytes());
+            buf.extend_from_slice(&digest.to_le_bytes());
+        }
+        fnv1a(&buf)
+    }
+
+    /// Prints the digest of every column and of the whole result.
+    pub fn report(&self) {
+        for (name, digest) in &self.columns {
+            println!("checksum {}: {:016x}", name, digest);
+        }
+        println!("checksum: {:016x} ({} rows)", self.digest(), self.rows);
+    }
+}
+
+/// Replaces dictionaries with their values, recursing into structs.
+fn normalize(array: &ArrayRef) -> Result<ArrayRef, ArrowError> {
+    match array.data_type() {
+        DataType::Dictionary(_, value) => normalize(&cast(array, value)?),
+        DataType::Struct(_) => {
+            let array = array.as_struct();
+            let columns = array
+                .columns()
+                .iter()
+                .map(normalize)
+                .collect::<Result<Vec<_>, _>>()?;
+            let fields = array
+                .fields()
+                .iter()
+                .zip(&columns)
+                .map(|(f, c)| Field::clone(f).with_data_type(c.data_type().clone()))
+                .collect();
+            Ok(Arc::new(StructArray::try_new(
+                fields,
+                columns,
+                array.nulls().cloned(),
+            )?))
+        }
+        _ => Ok(array.clone()),
+    }
+}
+
+fn encode(array: &dyn Array, row: usize, buf: &mut Vec<u8>) -> Result<(), ArrowError> {
+    // A `NullArray` has no validity buffer to say so.
+    if array.is_null(row) || array.data_type() == &DataType::Null {
+        buf.push(0);
+        return Ok(());
+    }
+    buf.push(1);
+    let bytes = |buf: &mut Vec<u8>, value: &[u8]| {
+        buf.extend_from_slice(&(value.len() as u64).to_le_bytes());
+        buf.extend_from_slice(value);
+    };
+    match array.data_type() {
+        DataType::Boolean => buf.push(array.as_boolean().value(row) as u8),
+        DataType::Utf8 => bytes(buf, array.as_string::<i32>().value(row).as_bytes()),
+        DataType::LargeUtf8 => bytes(buf, array.as_string::<i64>().value(row).as_bytes()),
+        DataType::Utf8View => bytes(buf, array.as_string_view().value(row).as_bytes()),
+        DataType::Binary => bytes(buf, array.as_binary::<i32>().value(row)),
+        DataType::LargeBinary => bytes(buf, array.as_binary::<i64>().value(row)),
+        DataType::BinaryView => bytes(buf, array.as_binary_view().value(row)),
+        DataType::FixedSizeBinary(_) => {
+            buf.extend_from_slice(array.as_fixed_size_binary().value(row))
+        }
+        DataType::Struct(_) => {
+            for column in array.as_struct().columns() {
+                encode(column.as_ref(), row, buf)?;
+            }
+        }
+        DataType::List(_) => encode_list(&array.as_list::<i32>().value(row), buf)?,
+        DataType::LargeList(_) => encode_list(&array.as_list::<i64>().value(row), buf)?,
+        DataType::FixedSizeList(_, _) => encode_list(&array.as_fixed_size_list().value(row), buf)?,
+        DataType::Map(_, _) => encode_list(&(Arc::new(array.as_map().value(row)) as _), buf)?,
+        _ => downcast_primitive_array!(
+            array => buf.extend_from_slice(array.value(row).to_byte_slice()),
+            data_type => {
+                return Err(ArrowError::NotYetImplemented(format!("checksum of {data_type}")))
+            }
+        ),
+    }
+    Ok(())
+}
+
+fn encode_list(values: &ArrayRef, buf: &mut Vec<u8>) -> Result<(), ArrowError> {
+    let values = normalize(values)?;
+    buf.extend_from_slice(&(values.len() as u64).to_le_bytes());
+    for row in 0..values.len() {
+        encode(&values, row, buf)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{
+        BooleanArray, Int32Array, Int64Builder, ListBuilder, MapBuilder, NullArray, StringArray,
+        StringBuilder, StringDictionaryBuilder,
+    };
+    use arrow::datatypes::{Int32Type, Schema};
+
+    fn digest(column: ArrayRef) -> u64 {
+        let schema = Schema::new(vec![Field::new("c", column.data_type().clone(), true)]);
+        let batch = RecordBatch::try_new(Arc::new(schema), vec![column]).unwrap();
+        let mut checksum = Checksum::default();
+        checksum.update(&batch).unwrap();
+        checksum.digest()
+    }
+
+    /// The digest the pyarrow and arrow-cpp readers are checked against as well.
+    #[test]
+    fn digest_of_a_known_batch() {
+        let mut list = ListBuilder::new(Int64Builder::new());
+        list.values().append_slice(&[1, 2]);
+        list.append(true);
+        list.append(true);
+        list.append(false);
+        let batch = RecordBatch::try_from_iter([
+            (
+                "i",
+                Arc::new(Int32Array::from(vec![Some(1), None, Some(3)])) as _,
+            ),
+            (
+                "s",
+                Arc::new(StringArray::from(vec![Some("a"), Some("bc"), None])) as _,
+            ),
+            (
+                "b",
+                Arc::new(BooleanArray::from(vec![Some(true), Some(false), None])) as _,
+            ),
+            ("l", Arc::new(list.finish()) as _),
+        ])
+        .unwrap();
+        let mut checksum = Checksum::default();
+        checksum.update(&batch).unwrap();
+        assert_eq!(checksum.digest(), 0xd1cd1661d912af68);
+    }
+
+    #[test]
+    fn null_column_hashes_as_nulls() {
+        let nulls = digest(Arc::new(NullArray::new(3)));
+        let strings = digest(Arc::new(StringArray::from(vec![None::<&str>; 3])));
+        assert_eq!(nulls, strings);
+    }
+
+    #[test]
+    fn map_values_are_normalized() {
+        let mut plain = MapBuilder::new(None, StringBuilder::new(), StringBuilder::new());
+        let mut dict = MapBuilder::new(
+            None,
+            StringDictionaryBuilder::<Int32Type>::new(),
+            StringDictionaryBuilder::<Int32Type>::new(),
+        );
+        for (k, v) in [("a", "x"), ("b", "y")] {
+            plain.keys().append_value(k);
+            plain.values().append_value(v);
+            dict.keys().append_value(k);
+            dict.values().append_value(v);
+        }
+        plain.append(true).unwrap();
+        dict.append(true).unwrap();
+        plain.append(false).unwrap();
+        dict.append(false).unwrap();
+        assert_eq!(
+            digest(Arc::new(plain.finish())),
+            digest(Arc::new(dict.finish()))
+        );
+    }
+}
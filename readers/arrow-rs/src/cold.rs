@@ -0,0 +1,30 @@
+//! Starting a run with the input out of the page cache, without root.
+
+use std::io;
+use std::path::Path;
+
+/// Drops the cached pages of `path` with `posix_fadvise(POSIX_FADV_DONTNEED)`.
+///
+/// Unlike writing to `/proc/sys/vm/drop_caches` this needs no privileges and leaves the
+/// rest of the page cache alone. Dirty pages, and pages mapped by another process, stay
+/// cached.
+#[cfg(target_os = "linux")]
+pub fn evict(path: &Path) -> io::Result<()> {
+    use std::os::fd::AsRawFd;
+
+    let file = std::fs::File::open(path)?;
+    // Safety: the descriptor is valid for the lifetime of `file`.
+    let ret = unsafe { libc::posix_fadvise(file.as_raw_fd(), 0, 0, libc::POSIX_FADV_DONTNEED) };
+    match ret {
+        0 => Ok(()),
+        errno => Err(io::Error::from_raw_os_error(errno)),
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn evict(_path: &Path) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "--cold is only supported on Linux",
+    ))
+}
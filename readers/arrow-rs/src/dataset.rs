@@ -0,0 +1,199 @@
+//! Expanding the input into the files of a dataset: a single file, a directory, or a glob.
+
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+/// The files of `input`, in path order.
+///
+/// A directory is walked recursively for `*.parquet` files, skipping entries whose name
+/// starts with `.` or `_`, such as `_SUCCESS` markers. A glob supports `*` and `?` within
+/// a path segment and `**` for any number of segments, e.g. `data/**/part-*.parquet`.
+pub fn files(input: &str) -> io::Result<Vec<PathBuf>> {
+    let path = Path::new(input);
+    if path.is_file() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+
+    let mut files = vec![];
+    if path.is_dir() {
+        walk(path, &mut files)?;
+        files.retain(|f| f.extension().is_some_and(|ext| ext == "parquet"));
+    } else if is_glob(input) {
+        let (base, pattern) = split_glob(path);
+        walk(&base, &mut files)?;
+        files.retain(|f| {
+            let relative = f.strip_prefix(&base).unwrap_or(f);
+            let segments: Vec<String> = relative
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy().into_owned())
+                .collect();
+            matches(&pattern, &segments)
+        });
+    } else {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("{} does not exist", input),
+        ));
+    }
+
+    if files.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("no parquet files in {}", input),
+        ));
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// The directory the files of `input` are found in, that their partition directories are
+/// relative to: `input` itself or the part of a glob before its first wildcard.
+pub fn root(input: &str) -> PathBuf {
+    let path = Path::new(input);
+    match path.is_dir() || !is_glob(input) {
+        true => path.to_path_buf(),
+        false => split_glob(path).0,
+    }
+}
+
+fn walk(dir: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        if name.to_string_lossy().starts_with(['.', '_']) {
+            continue;
+        }
+        let path = entry.path();
+        if path.is_dir() {
+            walk(&path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn is_glob(s: &str) -> bool {
+    s.contains(['*', '?'])
+}
+
+/// Splits a glob into the directory before its first wildcard and the remaining segments.
+fn split_glob(path: &Path) -> (PathBuf, Vec<String>) {
+    let mut base = PathBuf::new();
+    let mut pattern = vec![];
+    for component in path.components() {
+        let segment = component.as_os_str().to_string_lossy();
+        if pattern.is_empty() && !is_glob(&segment) {
+            base.push(component);
+        } else if component != Component::CurDir {
+            pattern.push(segment.into_owned());
+        }
+    }
+    if base.as_os_str().is_empty() {
+        base.push(".");
+    }
+    (base, pattern)
+}
+
+fn matches(pattern: &[String], path: &[String]) -> bool {
+    match (pattern.first(), path.first()) {
+        (Some(p), _) if p == "**" => {
+            matches(&pattern[1..], path) || (!path.is_empty() && matches(pattern, &path[1..]))
+        }
+        (Some(p), Some(segment)) => {
+            let p: Vec<char> = p.chars().collect();
+            let segment: Vec<char> = segment.chars().collect();
+            matches_segment(&p, &segment) && matches(&pattern[1..], &path[1..])
+        }
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+fn matches_segment(pattern: &[char], s: &[char]) -> bool {
+    match (pattern.first(), s.first()) {
+        (Some('*'), _) => {
+            matches_segment(&pattern[1..], s)
+                || (!s.is_empty() && matches_segment(pattern, &s[1..]))
+        }
+        (Some('?'), Some(_)) => matches_segment(&pattern[1..], &s[1..]),
+        (Some(p), Some(c)) => p == c && matches_segment(&pattern[1..], &s[1..]),
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn glob(pattern: &str, path: &str) -> bool {
+        let pattern: Vec<String> = pattern.split('/').map(Into::into).collect();
+        let path: Vec<String> = path.split('/').map(Into::into).collect();
+        matches(&pattern, &path)
+    }
+
+    #[test]
+    fn segment_wildcards() {
+        assert!(glob("part-*.parquet", "part-0.parquet"));
+        assert!(glob("part-*.parquet", "part-.parquet"));
+        assert!(!glob("part-*.parquet", "part-0.parquet.tmp"));
+        assert!(glob("part-?.parquet", "part-1.parquet"));
+        assert!(!glob("part-?.parquet", "part-10.parquet"));
+        assert!(glob("*", "a.parquet"));
+        assert!(!glob("*", "a/b.parquet"));
+        assert!(glob("*a*b*", "xxaxxbxx"));
+    }
+
+    #[test]
+    fn double_star_spans_segments() {
+        assert!(glob("**/*.parquet", "a.parquet"));
+        assert!(glob("**/*.parquet", "year=1/month=2/a.parquet"));
+        assert!(glob("year=*/**/a.parquet", "year=1/a.parquet"));
+        assert!(glob("year=*/**/a.parquet", "year=1/x/y/a.parquet"));
+        assert!(!glob("year=*/**/a.parquet", "a.parquet"));
+        assert!(!glob("**/a.parquet", "a.parquet/b"));
+    }
+
+    #[test]
+    fn split_at_first_wildcard() {
+        let (base, pattern) = split_glob(Path::new("data/year=*/part-?.parquet"));
+        assert_eq!(base, PathBuf::from("data"));
+        assert_eq!(pattern, vec!["year=*", "part-?.parquet"]);
+
+        let (base, pattern) = split_glob(Path::new("*.parquet"));
+        assert_eq!(base, PathBuf::from("."));
+        assert_eq!(pattern, vec!["*.parquet"]);
+        assert_eq!(root("data/**/a.parquet"), PathBuf::from("data"));
+    }
+
+    #[test]
+    fn walk_and_filter() {
+        let dir = std::env::temp_dir().join(format!("dataset-{}", std::process::id()));
+        for file in [
+            "y=1/a.parquet",
+            "y=1/_SUCCESS",
+            "y=2/b.parquet",
+            "y=2/b.csv",
+            ".hidden/c.parquet",
+            "_tmp/d.parquet",
+        ] {
+            let path = dir.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, b"").unwrap();
+        }
+        let dir_str = dir.to_str().unwrap();
+
+        let all = files(dir_str);
+        let glob = files(&format!("{}/*/a.*", dir_str));
+        let none = files(&format!("{}/*/none-*.parquet", dir_str));
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            all.unwrap(),
+            vec![dir.join("y=1/a.parquet"), dir.join("y=2/b.parquet")]
+        );
+        assert_eq!(glob.unwrap(), vec![dir.join("y=1/a.parquet")]);
+        assert!(none.is_err());
+    }
+}
@@ -0,0 +1,84 @@
+//! Errors that name the part of the read that failed.
+
+use std::error::Error;
+use std::fmt;
+
+type BoxError = Box<dyn Error + Send + Sync>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    Open,
+    /// The 8 byte footer, or with the async readers the footer and the metadata together.
+    Footer,
+    /// Decoding the thrift file metadata.
+    Metadata,
+    PageIndex,
+    /// Reading the bloom filters of the predicate columns.
+    BloomFilter,
+    /// Converting the parquet schema into an Arrow schema.
+    Schema,
+    RowGroup(usize),
+    /// Decoding the plan through one reader, for an error no row group reproduces on its
+    /// own, such as one of the predicate.
+    Decode,
+}
+
+impl fmt::Display for Stage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Stage::Open => write!(f, "open"),
+            Stage::Footer => write!(f, "footer"),
+            Stage::Metadata => write!(f, "metadata"),
+            Stage::PageIndex => write!(f, "page index"),
+            Stage::BloomFilter => write!(f, "bloom filter"),
+            Stage::Schema => write!(f, "schema"),
+            Stage::RowGroup(rg) => write!(f, "row group {}", rg),
+            Stage::Decode => write!(f, "decode"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ReadError {
+    pub stage: Stage,
+    /// The column that fails to decode on its own, if it could be narrowed down.
+    pub column: Option<String>,
+    /// File offset of the page that fails to decode, if it could be narrowed down.
+    pub page_offset: Option<i64>,
+    source: BoxError,
+}
+
+impl ReadError {
+    pub fn new(stage: Stage, source: impl Into<BoxError>) -> Self {
+        Self {
+            stage,
+            column: None,
+            page_offset: None,
+            source: source.into(),
+        }
+    }
+
+    /// Wraps an error of `stage`, for `map_err`.
+    pub fn at<E: Into<BoxError>>(stage: Stage) -> impl FnOnce(E) -> Self {
+        move |source| Self::new(stage, source)
+    }
+}
+
+impl fmt::Display for ReadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.stage)?;
+        if let Some(column) = &self.column {
+            write!(f, ", column {}", column)?;
+        }
+        if let Some(offset) = self.page_offset {
+            write!(f, ", page offset {}", offset)?;
+        }
+        write!(f, ": {}", self.source)
+    }
+}
+
+impl Error for ReadError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(self.source.as_ref())
+    }
+}
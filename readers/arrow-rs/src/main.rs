@@ -1,16 +1,80 @@
-use clap::{Arg, ArgAction, Command};
-use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
-use std::fs::File;
+use arrow::array::{RecordBatch, RecordBatchReader};
+use arrow::datatypes::SchemaRef;
+use arrow::ipc::CompressionType;
+use bytes::Bytes;
+use checksum::Checksum;
+use clap::{value_parser, Arg, ArgAction, Command};
+use metrics::Metrics;
+use output::{Output, OutputFormat};
+use parquet::arrow::arrow_reader::{ArrowReaderOptions, ParquetRecordBatchReaderBuilder};
+use parquet::arrow::async_reader::{AsyncFileReader, ParquetObjectReader};
+use parquet::arrow::ProjectionMask;
+use parquet::file::metadata::ParquetMetaDataReader;
+use partition::Partitions;
+use predicate::Predicate;
+use prefetch::PrefetchOptions;
+use scan::{ScanOptions, ScanPlan, StringType};
+use std::error::Error;
+use std::ops::Range;
 use std::path::Path;
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use store::{Bandwidth, StoreKind, StoreOptions};
+use sync_reader::Input;
+use trace::IoTrace;
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let matches = Command::new("Parquet to Arrow")
+mod async_reader;
+mod bloom;
+mod checksum;
+mod cold;
+mod dataset;
+mod error;
+mod metrics;
+mod output;
+mod pages;
+mod partition;
+mod predicate;
+mod prefetch;
+mod projection;
+mod pruning;
+mod scan;
+mod store;
+mod sync_reader;
+mod trace;
+#[cfg(feature = "io-uring")]
+mod uring;
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(0) => ExitCode::SUCCESS,
+        Ok(errors) => {
+            eprintln!("{} row groups failed to decode", errors);
+            ExitCode::FAILURE
+        }
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// The command line of the reader.
+fn command() -> Command {
+    let mut io_modes = vec!["file", "mmap", "preload"];
+    if cfg!(feature = "io-uring") {
+        io_modes.push("uring");
+    }
+
+    Command::new("Parquet to Arrow")
         .version("0.1.0")
         .author("Your Name")
         .about("Converts Parquet files to Arrow format")
         .arg(
             Arg::new("input")
-                .help("The input Parquet file path")
+                .help("The input Parquet file, or a directory or glob of files sharing a schema, e.g. 'data/**/*.parquet'")
                 .required(true)
                 .index(1),
         )
@@ -20,26 +84,628 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 .short('p')
                 .action(ArgAction::SetTrue),
         )
-        .get_matches();
+        .arg(
+            Arg::new("columns")
+                .help("Comma separated columns to read, by leaf index or dotted path (default: all)")
+                .long("columns")
+                .short('c'),
+        )
+        .arg(
+            Arg::new("predicate")
+                .help("Filter rows, e.g. \"l_quantity < 10 AND l_returnflag IN ('A', 'R')\"")
+                .long("predicate")
+                .short('f'),
+        )
+        .arg(
+            Arg::new("partition-filter")
+                .help("Skip the files of a hive partitioned directory whose key=value partition values fail this filter, e.g. \"year = 2024 AND month IN (1, 2)\", before their footers are read")
+                .long("partition-filter"),
+        )
+        .arg(
+            Arg::new("filter-mode")
+                .help("Evaluate the predicate during decode (pushdown) or after a full scan (scan)")
+                .long("filter-mode")
+                .value_parser(["pushdown", "scan"])
+                .default_value("pushdown"),
+        )
+        .arg(
+            Arg::new("prune")
+                .help("Skip row groups and pages using statistics and the page index, requires --predicate")
+                .long("prune")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("bloom-filter")
+                .help("Skip row groups whose bloom filters rule out the = and IN terms of --predicate, reporting the bytes read against the bytes saved")
+                .long("bloom-filter")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("rows")
+                .help("Only read rows a to b (exclusive) of each file, e.g. 1000..2000, skipping the pages outside of it")
+                .long("rows")
+                .value_name("a..b")
+                .value_parser(parse_rows),
+        )
+        .arg(
+            Arg::new("offset")
+                .help("Skip this many rows of each file, or of the rows --predicate returns")
+                .long("offset")
+                .value_parser(value_parser!(usize))
+                .default_value("0"),
+        )
+        .arg(
+            Arg::new("limit")
+                .help("Return at most this many rows of each file after --offset, with --predicate the first matching rows in file order")
+                .long("limit")
+                .value_parser(value_parser!(usize)),
+        )
+        .arg(
+            Arg::new("batch-size")
+                .help("Rows per record batch (default: the reader's, 1024)")
+                .long("batch-size")
+                .value_parser(value_parser!(usize)),
+        )
+        .arg(
+            Arg::new("strings")
+                .help("Arrow type for string and binary columns: as read (plain), Dictionary(Int32, _) (dictionary) or Utf8View/BinaryView (view)")
+                .long("strings")
+                .value_parser(["plain", "dictionary", "view"])
+                .default_value("plain"),
+        )
+        .arg(
+            Arg::new("threads")
+                .help("Decode row groups on this many threads, each with its own reader")
+                .long("threads")
+                .value_parser(value_parser!(usize))
+                .default_value("1")
+                .conflicts_with_all(["async", "object-store"]),
+        )
+        .arg(
+            Arg::new("file-concurrency")
+                .help("Files of a directory or glob read at once, each on its own thread")
+                .long("file-concurrency")
+                .value_parser(value_parser!(usize))
+                .default_value("1"),
+        )
+        .arg(
+            Arg::new("io")
+                .help("How the file is read: through std::fs::File, a memory map, or from memory after reading it all before the run is timed, with the sync reader; or through io_uring with the async reader (io-uring feature)")
+                .long("io")
+                .value_parser(io_modes)
+                .default_value("file"),
+        )
+        .arg(
+            Arg::new("cold")
+                .help("Evict the input from the page cache with posix_fadvise before the run, no root needed")
+                .long("cold")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("keep-going")
+                .help("Report row groups that fail to decode and read the rest, still exiting non-zero")
+                .long("keep-going")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("async")
+                .help("Read through the async ParquetRecordBatchStream on tokio")
+                .long("async")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("concurrency")
+                .help("Row group streams in flight with --async")
+                .long("concurrency")
+                .value_parser(value_parser!(usize))
+                .default_value("4"),
+        )
+        .arg(
+            Arg::new("coalesce-bytes")
+                .help("Plan the column chunk reads up front, merging ranges at most this many bytes apart, with --async, --object-store or --io uring")
+                .long("coalesce-bytes")
+                .value_parser(value_parser!(usize)),
+        )
+        .arg(
+            Arg::new("prefetch-depth")
+                .help("Planned reads fetched ahead of the decoder and not yet read by it, each kept until its row groups are decoded (default: 0, on demand), implies planning as with --coalesce-bytes")
+                .long("prefetch-depth")
+                .value_parser(value_parser!(usize)),
+        )
+        .arg(
+            Arg::new("object-store")
+                .help("Read through ParquetObjectReader over a local or in-memory object store, implies --async")
+                .long("object-store")
+                .value_parser(["local", "memory"]),
+        )
+        .arg(
+            Arg::new("latency-ms")
+                .help("Latency added to every object store request")
+                .long("latency-ms")
+                .value_parser(value_parser!(u64))
+                .default_value("0"),
+        )
+        .arg(
+            Arg::new("bandwidth-mbps")
+                .help("Object store bandwidth in MB/s, shared by all requests (default: unlimited)")
+                .long("bandwidth-mbps")
+                .value_parser(value_parser!(u64).range(1..)),
+        )
+        .arg(
+            Arg::new("output")
+                .help("Write the returned batches to this Arrow IPC file (Feather v2), or stream if it ends in .arrows, in the order of the files and their rows")
+                .long("output")
+                .short('o')
+                .value_name("PATH"),
+        )
+        .arg(
+            Arg::new("output-format")
+                .help("IPC format of --output, overriding the one picked from its extension")
+                .long("output-format")
+                .value_parser(["file", "stream"])
+                .requires("output"),
+        )
+        .arg(
+            Arg::new("output-compression")
+                .help("Buffer compression of --output (default: none)")
+                .long("output-compression")
+                .value_parser(["none", "lz4", "zstd"])
+                .requires("output"),
+        )
+        .arg(
+            Arg::new("checksum")
+                .help("Print a digest of the returned values, per column and overall, independent of batch boundaries and row order, to compare the ways this reader reads a file")
+                .long("checksum")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("metrics-json")
+                .help("Write phase timings, row and byte counts and peak RSS as JSON to this path (- for stdout)")
+                .long("metrics-json")
+                .value_name("PATH"),
+        )
+        .arg(
+            Arg::new("io-trace")
+                .help("Record every file read of the sync reader and write them to this path, as parquet if it ends in .parquet and json otherwise")
+                .long("io-trace")
+                .value_name("PATH")
+                .conflicts_with_all(["async", "object-store"]),
+        )
+}
+
+/// Runs the read, returning the number of row groups that failed with `--keep-going`.
+fn run() -> Result<usize, Box<dyn Error>> {
+    let matches = command().get_matches();
 
-    let print = matches.get_one::<bool>("print").unwrap();
+    let print = *matches.get_one::<bool>("print").unwrap();
     let input_path = matches.get_one::<String>("input").unwrap();
-    let file = File::open(Path::new(input_path))?;
+    let mut scan = ScanOptions {
+        columns: matches.get_one::<String>("columns").cloned(),
+        predicate: matches.get_one::<String>("predicate").cloned(),
+        pushdown: matches.get_one::<String>("filter-mode").unwrap() == "pushdown",
+        prune: matches.get_flag("prune"),
+        bloom_filter: matches.get_flag("bloom-filter"),
+        batch_size: matches.get_one::<usize>("batch-size").copied(),
+        strings: match matches.get_one::<String>("strings").unwrap().as_str() {
+            "dictionary" => StringType::Dictionary,
+            "view" => StringType::View,
+            _ => StringType::Plain,
+        },
+        keep_going: matches.get_flag("keep-going"),
+        rows: matches.get_one::<Range<usize>>("rows").cloned(),
+        offset: *matches.get_one::<usize>("offset").unwrap(),
+        limit: matches.get_one::<usize>("limit").copied(),
+        schema: None,
+        ordered: matches.contains_id("output"),
+    };
+    let options = ArrowReaderOptions::new().with_page_index(scan.needs_page_index());
+
+    let io = matches.get_one::<String>("io").unwrap();
+    let uring = io == "uring";
+    let is_async = matches.get_flag("async") || matches.contains_id("object-store") || uring;
+    if !is_async && (matches.contains_id("coalesce-bytes") || matches.contains_id("prefetch-depth"))
+    {
+        return Err("--coalesce-bytes and --prefetch-depth need an async reader".into());
+    }
+    if io != "file" {
+        if matches.contains_id("io-trace") {
+            return Err("--io-trace requires --io file".into());
+        }
+        if matches.contains_id("object-store") {
+            return Err("--io does not apply to --object-store".into());
+        }
+        if matches.get_flag("async") && !uring {
+            return Err("--io mmap and preload only apply to the sync reader".into());
+        }
+        if uring && *matches.get_one::<usize>("threads").unwrap() > 1 {
+            return Err("--io uring reads through the async reader, use --concurrency".into());
+        }
+    }
+    let file_concurrency = (*matches.get_one::<usize>("file-concurrency").unwrap()).max(1);
+    if file_concurrency > 1 && matches.contains_id("io-trace") {
+        return Err("--io-trace records one file at a time, use --file-concurrency 1".into());
+    }
+
+    let files = dataset::files(input_path)?;
+    let is_dataset = !Path::new(input_path).is_file();
+    let partitions = is_dataset
+        .then(|| Partitions::new(&dataset::root(input_path), &files))
+        .transpose()?;
+    // Indices of the files to read, those the partition filter keeps.
+    let selected: Vec<usize> = match (matches.get_one::<String>("partition-filter"), &partitions) {
+        (Some(filter), Some(partitions)) => {
+            let keep = partitions.keep(&Predicate::parse(filter)?)?;
+            (0..files.len()).filter(|&i| keep[i]).collect()
+        }
+        (Some(_), None) => return Err("--partition-filter requires a directory or glob".into()),
+        (None, _) => (0..files.len()).collect(),
+    };
+    // Footers read here rather than by the readers, added to the metrics once they are in.
+    let mut footers_read = 0;
+    // Every file of a dataset is checked against the schema of the first before it is
+    // decoded, which costs one more read of that footer.
+    if let (Some(partitions), Some(&first)) = (&partitions, selected.first()) {
+        let path = &files[first];
+        footers_read += 1;
+        let schema = file_schema(path, options.clone(), &scan)?;
+        for field in partitions.schema().fields() {
+            if schema.field_with_name(field.name()).is_ok() {
+                return Err(format!(
+                    "partition column {} is also a column of {}",
+                    field.name(),
+                    path.display()
+                )
+                .into());
+            }
+        }
+        scan.schema = Some((path.display().to_string(), schema));
+    }
+    if matches.get_flag("cold") {
+        for &i in &selected {
+            cold::evict(&files[i])?;
+        }
+    }
 
-    let builder = ParquetRecordBatchReaderBuilder::try_new(file).unwrap();
-    let mut reader = builder.build().unwrap();
+    let concurrency = (*matches.get_one::<usize>("concurrency").unwrap()).max(1);
+    let coalesce = matches.get_one::<usize>("coalesce-bytes").copied();
+    let depth = matches.get_one::<usize>("prefetch-depth").copied();
+    let prefetch = (coalesce.is_some() || depth.is_some()).then(|| PrefetchOptions {
+        coalesce: coalesce.unwrap_or(0),
+        depth: depth.unwrap_or(0),
+    });
+    // One runtime for every file, only the async readers need it.
+    let runtime = is_async
+        .then(|| {
+            tokio::runtime::Builder::new_multi_thread()
+                .enable_all()
+                .build()
+        })
+        .transpose()?;
+    let trace = matches
+        .get_one::<String>("io-trace")
+        .map(|_| Arc::new(IoTrace::new()));
+    // One bandwidth for every file, so that files read concurrently share it.
+    let store_options = matches
+        .get_one::<String>("object-store")
+        .map(|kind| StoreOptions {
+            kind: match kind.as_str() {
+                "local" => StoreKind::Local,
+                _ => StoreKind::Memory,
+            },
+            latency: Duration::from_millis(*matches.get_one::<u64>("latency-ms").unwrap()),
+            bandwidth: matches
+                .get_one::<u64>("bandwidth-mbps")
+                .map(|mbps| Arc::new(Bandwidth::new(mbps * 1_000_000))),
+        });
 
-    while let Some(record_batch) = reader.next() {
-        match record_batch {
-            Ok(batch) => {
-                if *print {
-                    println!("{:?}", batch);
+    // Reads one file, passing every batch on once the plan has finished it.
+    let read_file = |path: &Path,
+                     on_batch: &mut dyn FnMut(RecordBatch) -> Result<(), Box<dyn Error>>|
+     -> Result<(ScanPlan, Metrics), Box<dyn Error>> {
+        let preloaded = match io.as_str() {
+            "preload" => Some(Bytes::from(std::fs::read(path)?)),
+            _ => None,
+        };
+        let mut metrics = Metrics::new();
+        let on_batch = |plan: &ScanPlan, batch| on_batch(plan.finish_batch(batch)?);
+
+        let plan = if let (Some(store_options), Some(runtime)) = (&store_options, &runtime) {
+            runtime.block_on(async {
+                let now = Instant::now();
+                let (inner, location) = store::load(path, store_options.kind).await?;
+                metrics.store_load_time_nanos = metrics::nanos(now.elapsed());
+                let now = Instant::now();
+                let (store, meta) = store::open(inner, &location, store_options).await?;
+                metrics.open_time_nanos = metrics::nanos(now.elapsed());
+                let open = || {
+                    let reader = ParquetObjectReader::new(store.clone(), meta.clone());
+                    async { Ok(reader) }
+                };
+                let plan = async_reader::read(
+                    open,
+                    options.clone(),
+                    &scan,
+                    concurrency,
+                    prefetch,
+                    &mut metrics,
+                    on_batch,
+                )
+                .await?;
+                store.report();
+                Ok::<_, Box<dyn Error>>(plan)
+            })?
+        } else if let Some(runtime) = &runtime {
+            let open = || {
+                let (path, io) = (path.to_path_buf(), io.clone());
+                async move {
+                    let reader: Box<dyn AsyncFileReader> = match io.as_str() {
+                        #[cfg(feature = "io-uring")]
+                        "uring" => Box::new(uring::UringFile::open(path)?),
+                        _ => Box::new(tokio::fs::File::open(path).await?),
+                    };
+                    Ok(reader)
                 }
-                std::hint::black_box(batch);
+            };
+            runtime.block_on(async_reader::read(
+                open,
+                options.clone(),
+                &scan,
+                concurrency,
+                prefetch,
+                &mut metrics,
+                on_batch,
+            ))?
+        } else {
+            let input = match (io.as_str(), preloaded) {
+                (_, Some(bytes)) => Input::Bytes(bytes),
+                ("mmap", None) => Input::Mmap(path),
+                _ => Input::File(path, trace.clone()),
+            };
+            sync_reader::read(
+                input,
+                options.clone(),
+                &scan,
+                *matches.get_one::<usize>("threads").unwrap(),
+                &mut metrics,
+                on_batch,
+            )?
+        };
+        Ok((plan, metrics))
+    };
+
+    let mut metrics = Metrics::new();
+    let mut rows_returned = 0;
+    let mut checksum = matches.get_flag("checksum").then(Checksum::default);
+    let mut output = match matches.get_one::<String>("output") {
+        Some(path) => {
+            let format = match matches
+                .get_one::<String>("output-format")
+                .map(String::as_str)
+            {
+                Some("stream") => OutputFormat::Stream,
+                Some(_) => OutputFormat::File,
+                None => OutputFormat::from_path(Path::new(path)),
+            };
+            let compression = match matches
+                .get_one::<String>("output-compression")
+                .map(String::as_str)
+            {
+                Some("lz4") => Some(CompressionType::LZ4_FRAME),
+                Some("zstd") => Some(CompressionType::ZSTD),
+                _ => None,
+            };
+            let output = Output::new(path, format, compression)?;
+            // Rejected before the scan rather than at the first batch it returns.
+            if let (OutputFormat::File, Some(&first)) = (format, selected.first()) {
+                footers_read += 1;
+                let schema = output_schema(&files[first], options.clone(), &scan)?;
+                output.check_schema(&schema)?;
             }
-            Err(e) => eprintln!("Error reading batch: {}", e),
+            Some(output)
+        }
+        None => None,
+    };
+    let mut on_batch = |i: usize, batch: RecordBatch| {
+        let batch = match &partitions {
+            Some(partitions) => partitions.append(i, batch)?,
+            None => batch,
+        };
+        rows_returned += batch.num_rows();
+        if print {
+            println!("{:?}", batch);
+        }
+        if let Some(checksum) = &mut checksum {
+            checksum.update(&batch)?;
+        }
+        if let Some(output) = &mut output {
+            output.write(&batch)?;
+        }
+        std::hint::black_box(batch);
+        Ok::<_, Box<dyn Error>>(())
+    };
+
+    // Reports the file.
+    let mut finish_file = |i: usize, plan: ScanPlan, file: Metrics, rows: usize| {
+        let path = &files[i];
+        if !is_dataset {
+            plan.report(rows);
+            metrics = file;
+            return Ok::<_, Box<dyn Error>>(());
+        }
+        println!(
+            "file {}: {} rows returned, {} row groups, {:.3} ms",
+            path.display(),
+            rows,
+            plan.row_groups.len(),
+            (file.open_time_nanos
+                + file.metadata_decode_time_nanos
+                + file.page_index_load_time_nanos
+                + file.schema_build_time_nanos
+                + file.decode_time_nanos) as f64
+                / 1e6
+        );
+        plan.report(rows);
+        metrics.add_file(path, file);
+        Ok(())
+    };
+    let in_file = |i: usize, e: Box<dyn Error>| -> Box<dyn Error> {
+        match is_dataset {
+            true => format!("{}: {}", files[i].display(), e).into(),
+            false => e,
         }
+    };
+
+    if file_concurrency <= 1 || selected.len() <= 1 {
+        for &i in &selected {
+            let mut rows = 0;
+            let (plan, file) = read_file(&files[i], &mut |batch| {
+                rows += batch.num_rows();
+                on_batch(i, batch)
+            })
+            .map_err(|e| in_file(i, e))?;
+            finish_file(i, plan, file, rows)?;
+        }
+    } else {
+        // Whole files are handed out to the workers, and their batches passed back to
+        // this thread, as with row groups in the sync reader. In file order, every file
+        // has a channel of its own, drained in turn.
+        let next = AtomicUsize::new(0);
+        let (tx, rx) = mpsc::sync_channel(file_concurrency * 2);
+        let (senders, receivers): (Vec<_>, Vec<_>) = match scan.ordered {
+            true => selected
+                .iter()
+                .map(|_| {
+                    let (tx, rx) = mpsc::sync_channel(2);
+                    (Mutex::new(Some(tx)), rx)
+                })
+                .unzip(),
+            false => (vec![], vec![]),
+        };
+        thread::scope(|s| {
+            for _ in 0..file_concurrency.min(selected.len()) {
+                let (tx, next, selected, senders) = (tx.clone(), &next, &selected, &senders);
+                let (files, read_file) = (&files, &read_file);
+                s.spawn(move || loop {
+                    let n = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(&i) = selected.get(n) else {
+                        return;
+                    };
+                    let tx = match senders.get(n) {
+                        Some(sender) => sender.lock().unwrap().take().unwrap(),
+                        None => tx.clone(),
+                    };
+                    let result = read_file(&files[i], &mut |batch| {
+                        tx.send(FileEvent::Batch(i, batch))
+                            .map_err(|_| "the run was stopped".into())
+                    });
+                    let result = result.map(Box::new).map_err(|e| e.to_string());
+                    if tx.send(FileEvent::Done(i, result)).is_err() {
+                        return;
+                    }
+                });
+            }
+            drop(tx);
+
+            let events: Box<dyn Iterator<Item = FileEvent>> = match receivers.is_empty() {
+                true => Box::new(rx.into_iter()),
+                false => Box::new(receivers.into_iter().flatten()),
+            };
+            let mut rows = vec![0; files.len()];
+            for event in events {
+                match event {
+                    FileEvent::Batch(i, batch) => {
+                        rows[i] += batch.num_rows();
+                        on_batch(i, batch)?;
+                    }
+                    FileEvent::Done(i, result) => {
+                        let (plan, file) = *result.map_err(|e| in_file(i, e.into()))?;
+                        finish_file(i, plan, file, rows[i])?;
+                    }
+                }
+            }
+            Ok::<_, Box<dyn Error>>(())
+        })?;
     }
 
-    Ok(())
+    metrics.footers_read += footers_read;
+    if is_dataset {
+        metrics.files_pruned = files.len() - selected.len();
+        println!(
+            "files: {}, pruned: {}, footers read: {}, rows returned: {}",
+            files.len(),
+            metrics.files_pruned,
+            metrics.footers_read,
+            rows_returned
+        );
+    }
+    if let (Some(trace), Some(path)) = (trace, matches.get_one::<String>("io-trace")) {
+        trace.report();
+        trace.save(path)?;
+    }
+    if let Some(checksum) = &checksum {
+        checksum.report();
+    }
+    if let Some(output) = output {
+        let (time, bytes) = output.finish()?;
+        metrics.output_write_time_nanos = metrics::nanos(time);
+        metrics.output_bytes = Some(bytes);
+    }
+    let errors = metrics.errors;
+    if let Some(path) = matches.get_one::<String>("metrics-json") {
+        metrics.save_to_json(path)?;
+    }
+    Ok(errors)
+}
+
+/// The Arrow schema `path` is decoded into, read from its footer.
+fn file_schema(
+    path: &Path,
+    options: ArrowReaderOptions,
+    scan: &ScanOptions,
+) -> Result<SchemaRef, Box<dyn Error>> {
+    let metadata = ParquetMetaDataReader::new().parse_and_finish(&std::fs::File::open(path)?)?;
+    Ok(scan::arrow_metadata(Arc::new(metadata), options, scan)?
+        .schema()
+        .clone())
+}
+
+/// The Arrow schema of the batches `scan` returns from `path`, built from its footer
+/// without reading any rows.
+fn output_schema(
+    path: &Path,
+    options: ArrowReaderOptions,
+    scan: &ScanOptions,
+) -> Result<SchemaRef, Box<dyn Error>> {
+    let metadata = ParquetMetaDataReader::new().parse_and_finish(&std::fs::File::open(path)?)?;
+    let metadata = scan::arrow_metadata(Arc::new(metadata), options, scan)?;
+    let mut builder = ParquetRecordBatchReaderBuilder::new_with_metadata(Bytes::new(), metadata)
+        .with_row_groups(vec![]);
+    if let Some(spec) = &scan.columns {
+        let leaves = projection::parse_projection(spec, builder.parquet_schema())?;
+        let mask = ProjectionMask::leaves(builder.parquet_schema(), leaves);
+        builder = builder.with_projection(mask);
+    }
+    Ok(builder.build()?.schema())
+}
+
+/// Parses a `--rows` range, `a..b` with `a <= b`.
+fn parse_rows(s: &str) -> Result<Range<usize>, String> {
+    let (start, end) = s
+        .split_once("..")
+        .ok_or_else(|| format!("expected a..b, got {}", s))?;
+    let start: usize = start
+        .trim()
+        .parse()
+        .map_err(|e| format!("{}: {}", start, e))?;
+    let end: usize = end.trim().parse().map_err(|e| format!("{}: {}", end, e))?;
+    if start > end {
+        return Err(format!("{} is after {}", start, end));
+    }
+    Ok(start..end)
+}
+
+/// What a file worker passes back to the main thread.
+enum FileEvent {
+    Batch(usize, RecordBatch),
+    Done(usize, Result<Box<(ScanPlan, Metrics)>, String>),
 }
@@ -0,0 +1,248 @@
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use arrow::array::RecordBatch;
+use serde::Serialize;
+
+/// Per-run measurements, written by `--metrics-json`.
+///
+/// Timings are taken inside the process, so they exclude process startup. For a dataset
+/// they are summed over its files, which overlap with `--file-concurrency`.
+#[derive(Debug, Serialize)]
+pub struct Metrics {
+    /// Opening the file, or the object store.
+    pub open_time_nanos: usize,
+    /// Copying the file into the `--object-store memory` store before it is opened.
+    pub store_load_time_nanos: usize,
+    /// Reading the footer and decoding the thrift metadata.
+    pub metadata_decode_time_nanos: usize,
+    /// Reading the column and offset indexes, zero unless they are needed.
+    pub page_index_load_time_nanos: usize,
+    /// Converting the parquet schema into an Arrow schema.
+    pub schema_build_time_nanos: usize,
+    /// Reading and probing bloom filters, zero unless `--bloom-filter` is given.
+    pub bloom_filter_time_nanos: usize,
+    /// Building the reader and decoding every batch.
+    pub decode_time_nanos: usize,
+    /// From the start of the run until the first batch is decoded.
+    pub first_batch_time_nanos: Option<usize>,
+    pub rows: usize,
+    pub batches: usize,
+    /// Arrow memory size of the decoded batches.
+    pub bytes_decoded: usize,
+    /// Encoding and writing `--output`, which is also part of the decode time.
+    pub output_write_time_nanos: usize,
+    /// Size of the `--output` file.
+    pub output_bytes: Option<u64>,
+    /// Row groups that failed to decode with `--keep-going`.
+    pub errors: usize,
+    pub peak_rss_bytes: Option<usize>,
+    /// Files of a dataset skipped by `--partition-filter`.
+    pub files_pruned: usize,
+    /// Footers read and decoded, one per file opened, plus the re-reads of the first
+    /// file of a dataset for its schema and of `--output` for the schema it is written in.
+    pub footers_read: usize,
+    /// Per file metrics of a dataset, in the order the files finish.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub files: Vec<FileMetrics>,
+    #[serde(skip)]
+    start: Instant,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self {
+            open_time_nanos: 0,
+            store_load_time_nanos: 0,
+            metadata_decode_time_nanos: 0,
+            page_index_load_time_nanos: 0,
+            schema_build_time_nanos: 0,
+            bloom_filter_time_nanos: 0,
+            decode_time_nanos: 0,
+            first_batch_time_nanos: None,
+            rows: 0,
+            batches: 0,
+            bytes_decoded: 0,
+            output_write_time_nanos: 0,
+            output_bytes: None,
+            errors: 0,
+            peak_rss_bytes: None,
+            files_pruned: 0,
+            footers_read: 0,
+            files: vec![],
+            start: Instant::now(),
+        }
+    }
+
+    pub fn record_batch(&mut self, batch: &RecordBatch) {
+        if self.first_batch_time_nanos.is_none() {
+            self.first_batch_time_nanos = Some(nanos(self.start.elapsed()));
+        }
+        self.rows += batch.num_rows();
+        self.batches += 1;
+        self.bytes_decoded += batch.get_array_memory_size();
+    }
+
+    /// Adds the metrics of one file of a dataset to the totals.
+    pub fn add_file(&mut self, path: &Path, file: Metrics) {
+        self.open_time_nanos += file.open_time_nanos;
+        self.store_load_time_nanos += file.store_load_time_nanos;
+        self.metadata_decode_time_nanos += file.metadata_decode_time_nanos;
+        self.page_index_load_time_nanos += file.page_index_load_time_nanos;
+        self.schema_build_time_nanos += file.schema_build_time_nanos;
+        self.bloom_filter_time_nanos += file.bloom_filter_time_nanos;
+        self.decode_time_nanos += file.decode_time_nanos;
+        if let Some(first_batch) = file.first_batch_time_nanos {
+            let first_batch = nanos(file.start.duration_since(self.start)) + first_batch;
+            self.first_batch_time_nanos = Some(
+                self.first_batch_time_nanos
+                    .map_or(first_batch, |t| t.min(first_batch)),
+            );
+        }
+        self.rows += file.rows;
+        self.batches += file.batches;
+        self.bytes_decoded += file.bytes_decoded;
+        self.errors += file.errors;
+        self.footers_read += file.footers_read;
+        self.files.push(FileMetrics {
+            path: path.display().to_string(),
+            metrics: file,
+        });
+    }
+
+    /// Writes the metrics as json to `dst`, or to stdout if `dst` is `-`.
+    pub fn save_to_json(mut self, dst: impl AsRef<Path>) -> std::io::Result<()> {
+        self.peak_rss_bytes = peak_rss_bytes();
+        let dst = dst.as_ref();
+        if dst == Path::new("-") {
+            serde_json::to_writer_pretty(std::io::stdout(), &self)?;
+            println!();
+        } else {
+            let file = std::fs::File::create(dst)?;
+            serde_json::to_writer_pretty(file, &self)?;
+        }
+        Ok(())
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct FileMetrics {
+    pub path: String,
+    #[serde(flatten)]
+    pub metrics: Metrics,
+}
+
+pub fn nanos(d: Duration) -> usize {
+    d.as_nanos() as usize
+}
+
+/// High water mark of the resident set size, from `/proc/self/status` (Linux only).
+fn peak_rss_bytes() -> Option<usize> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|l| l.starts_with("VmHWM:"))?;
+    let kb: usize = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kb * 1024)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::Int64Array;
+    use serde_json::Value;
+    use std::sync::Arc;
+
+    fn saved(metrics: Metrics) -> Value {
+        let path = std::env::temp_dir().join(format!("metrics-{}.json", std::process::id()));
+        metrics.save_to_json(&path).unwrap();
+        let json = serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        json
+    }
+
+    fn batch(rows: i64) -> RecordBatch {
+        RecordBatch::try_from_iter([("v", Arc::new(Int64Array::from_iter_values(0..rows)) as _)])
+            .unwrap()
+    }
+
+    #[test]
+    fn json_has_every_field_of_a_run() {
+        let mut metrics = Metrics::new();
+        metrics.open_time_nanos = 7;
+        metrics.footers_read = 1;
+        metrics.record_batch(&batch(10));
+        metrics.record_batch(&batch(5));
+        let json = saved(metrics);
+
+        // In the order serde_json keeps its keys, sorted.
+        let fields: Vec<&str> = json
+            .as_object()
+            .unwrap()
+            .keys()
+            .map(|k| k.as_str())
+            .collect();
+        assert_eq!(
+            fields,
+            [
+                "batches",
+                "bloom_filter_time_nanos",
+                "bytes_decoded",
+                "decode_time_nanos",
+                "errors",
+                "files_pruned",
+                "first_batch_time_nanos",
+                "footers_read",
+                "metadata_decode_time_nanos",
+                "open_time_nanos",
+                "output_bytes",
+                "output_write_time_nanos",
+                "page_index_load_time_nanos",
+                "peak_rss_bytes",
+                "rows",
+                "schema_build_time_nanos",
+                "store_load_time_nanos",
+            ]
+        );
+        assert_eq!(json["open_time_nanos"], 7);
+        assert_eq!(json["footers_read"], 1);
+        assert_eq!(json["rows"], 15);
+        assert_eq!(json["batches"], 2);
+        assert!(json["first_batch_time_nanos"].is_u64());
+        assert!(json["output_bytes"].is_null());
+        if cfg!(target_os = "linux") {
+            assert!(json["peak_rss_bytes"].as_u64().unwrap() > 0);
+        }
+    }
+
+    #[test]
+    fn json_of_a_dataset_lists_its_files() {
+        let mut metrics = Metrics::new();
+        metrics.footers_read = 1;
+        for (path, rows) in [("a.parquet", 10), ("b.parquet", 20)] {
+            let mut file = Metrics::new();
+            file.open_time_nanos = 3;
+            file.footers_read = 1;
+            file.record_batch(&batch(rows));
+            metrics.add_file(Path::new(path), file);
+        }
+        let json = saved(metrics);
+
+        assert_eq!(json["rows"], 30);
+        assert_eq!(json["open_time_nanos"], 6);
+        // The schema re-read of the first file, and one footer per file.
+        assert_eq!(json["footers_read"], 3);
+        let files = json["files"].as_array().unwrap();
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[1]["path"], "b.parquet");
+        assert_eq!(files[1]["rows"], 20);
+        assert_eq!(files[1]["footers_read"], 1);
+        // Per file metrics are flattened next to the path, without files of their own.
+        assert!(files[1].get("files").is_none());
+        assert!(files[1].get("metrics").is_none());
+    }
+}
@@ -0,0 +1,297 @@
+//! Writing the returned batches to an Arrow IPC file or stream.
+
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use arrow::array::RecordBatch;
+use arrow::datatypes::{DataType, Schema, SchemaRef};
+use arrow::error::ArrowError;
+use arrow::ipc::writer::{FileWriter, IpcWriteOptions, StreamWriter};
+use arrow::ipc::CompressionType;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// The IPC file format, also known as Feather v2, with a footer for random access.
+    File,
+    /// The IPC streaming format.
+    Stream,
+}
+
+impl OutputFormat {
+    /// The stream format for `.arrows` paths, the file format otherwise.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().is_some_and(|ext| ext == "arrows") {
+            true => OutputFormat::Stream,
+            false => OutputFormat::File,
+        }
+    }
+}
+
+enum Writer {
+    File(FileWriter<BufWriter<File>>),
+    Stream(StreamWriter<BufWriter<File>>),
+}
+
+/// An IPC writer, created with the schema of the first batch.
+pub struct Output {
+    path: PathBuf,
+    format: OutputFormat,
+    options: IpcWriteOptions,
+    writer: Option<Writer>,
+    rows: usize,
+    batches: usize,
+    /// Time spent encoding and writing.
+    time: Duration,
+}
+
+impl Output {
+    pub fn new(
+        path: impl Into<PathBuf>,
+        format: OutputFormat,
+        compression: Option<CompressionType>,
+    ) -> Result<Self, ArrowError> {
+        Ok(Self {
+            path: path.into(),
+            format,
+            options: IpcWriteOptions::default().try_with_compression(compression)?,
+            writer: None,
+            rows: 0,
+            batches: 0,
+            time: Duration::ZERO,
+        })
+    }
+
+    pub fn write(&mut self, batch: &RecordBatch) -> Result<(), ArrowError> {
+        let now = Instant::now();
+        if self.writer.is_none() {
+            self.writer = Some(self.create(&batch.schema())?);
+        }
+        match self.writer.as_mut().unwrap() {
+            Writer::File(w) => w.write(batch)?,
+            Writer::Stream(w) => w.write(batch)?,
+        }
+        self.rows += batch.num_rows();
+        self.batches += 1;
+        self.time += now.elapsed();
+        Ok(())
+    }
+
+    /// Checks that batches of `schema` can be written in the output format.
+    pub fn check_schema(&self, schema: &Schema) -> Result<(), ArrowError> {
+        // Every batch decoded from parquet comes with its own dictionaries, which the file
+        // format cannot replace.
+        if self.format == OutputFormat::File
+            && schema
+                .fields()
+                .iter()
+                .any(|f| has_dictionary(f.data_type()))
+        {
+            return Err(ArrowError::InvalidArgumentError(
+                "dictionaries can only be written with --output-format stream".into(),
+            ));
+        }
+        Ok(())
+    }
+
+    fn create(&self, schema: &SchemaRef) -> Result<Writer, ArrowError> {
+        self.check_schema(schema)?;
+        let file = BufWriter::new(File::create(&self.path)?);
+        let options = self.options.clone();
+        Ok(match self.format {
+            OutputFormat::File => {
+                Writer::File(FileWriter::try_new_with_options(file, schema, options)?)
+            }
+            OutputFormat::Stream => {
+                Writer::Stream(StreamWriter::try_new_with_options(file, schema, options)?)
+            }
+        })
+    }
+
+    /// Writes the footer or end of stream marker, returning the time spent writing and
+    /// the size of the output. Without any batches the output has an empty schema.
+    pub fn finish(mut self) -> Result<(Duration, u64), ArrowError> {
+        let now = Instant::now();
+        let writer = match self.writer.take() {
+            Some(writer) => writer,
+            None => self.create(&Schema::empty().into())?,
+        };
+        match writer {
+            Writer::File(mut w) => w.finish()?,
+            Writer::Stream(mut w) => w.finish()?,
+        }
+        self.time += now.elapsed();
+        let bytes = std::fs::metadata(&self.path)?.len();
+        println!(
+            "output: {} rows in {} batches, {} bytes to {}",
+            self.rows,
+            self.batches,
+            bytes,
+            self.path.display()
+        );
+        Ok((self.time, bytes))
+    }
+}
+
+fn has_dictionary(data_type: &DataType) -> bool {
+    match data_type {
+        DataType::Dictionary(_, _) => true,
+        DataType::Struct(fields) => fields.iter().any(|f| has_dictionary(f.data_type())),
+        DataType::List(f) | DataType::LargeList(f) | DataType::FixedSizeList(f, _) => {
+            has_dictionary(f.data_type())
+        }
+        DataType::Map(f, _) => has_dictionary(f.data_type()),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    use arrow::array::{Array, AsArray, DictionaryArray, Int64Array, MapBuilder, StringBuilder};
+    use arrow::datatypes::{Field, Int32Type, Int64Type};
+    use arrow::error::ArrowError;
+    use arrow::ipc::reader::{FileReader, StreamReader};
+    use clap::error::ErrorKind;
+
+    fn path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("output-{}-{}", std::process::id(), name))
+    }
+
+    fn batch(values: std::ops::Range<i64>) -> RecordBatch {
+        RecordBatch::try_from_iter([("v", Arc::new(Int64Array::from_iter_values(values)) as _)])
+            .unwrap()
+    }
+
+    fn read_back(path: &Path, format: OutputFormat) -> Vec<i64> {
+        let file = File::open(path).unwrap();
+        let batches: Vec<RecordBatch> = match format {
+            OutputFormat::File => FileReader::try_new(file, None)
+                .unwrap()
+                .collect::<Result<_, ArrowError>>()
+                .unwrap(),
+            OutputFormat::Stream => StreamReader::try_new(file, None)
+                .unwrap()
+                .collect::<Result<_, ArrowError>>()
+                .unwrap(),
+        };
+        batches
+            .iter()
+            .flat_map(|b| b.column(0).as_primitive::<Int64Type>().values().to_vec())
+            .collect()
+    }
+
+    #[test]
+    fn batches_round_trip_in_the_order_written() {
+        for (name, format, compression) in [
+            ("file.arrow", OutputFormat::File, None),
+            ("stream.arrows", OutputFormat::Stream, None),
+            (
+                "lz4.arrow",
+                OutputFormat::File,
+                Some(CompressionType::LZ4_FRAME),
+            ),
+            (
+                "zstd.arrows",
+                OutputFormat::Stream,
+                Some(CompressionType::ZSTD),
+            ),
+        ] {
+            let path = path(name);
+            assert_eq!(OutputFormat::from_path(&path), format);
+            let mut output = Output::new(&path, format, compression).unwrap();
+            // Batches of three files, in file order.
+            for range in [0..10, 10..15, 15..40] {
+                output.write(&batch(range)).unwrap();
+            }
+            let (_, bytes) = output.finish().unwrap();
+            assert_eq!(bytes, std::fs::metadata(&path).unwrap().len());
+            assert_eq!(
+                read_back(&path, format),
+                (0..40).collect::<Vec<_>>(),
+                "{}",
+                name
+            );
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    fn dictionaries_need_the_stream_format() {
+        let dictionary: DictionaryArray<Int32Type> = vec!["a", "b", "a"].into_iter().collect();
+        let mut map = MapBuilder::new(None, StringBuilder::new(), StringBuilder::new());
+        map.keys().append_value("k");
+        map.values().append_value("v");
+        map.append(true).unwrap();
+        let map = map.finish();
+        let nested = Schema::new(vec![Field::new(
+            "m",
+            DataType::Map(
+                Arc::new(Field::new_struct(
+                    "entries",
+                    vec![
+                        Field::new("keys", DataType::Utf8, false),
+                        Field::new_dictionary("values", DataType::Int32, DataType::Utf8, true),
+                    ],
+                    false,
+                )),
+                false,
+            ),
+            true,
+        )]);
+
+        let file = Output::new(path("dict.arrow"), OutputFormat::File, None).unwrap();
+        let stream = Output::new(path("dict.arrows"), OutputFormat::Stream, None).unwrap();
+        for schema in [
+            Schema::new(vec![Field::new("d", dictionary.data_type().clone(), true)]),
+            Schema::new(vec![Field::new_list(
+                "l",
+                Field::new_list_field(dictionary.data_type().clone(), true),
+                true,
+            )]),
+            nested,
+        ] {
+            assert!(file.check_schema(&schema).is_err(), "{:?}", schema);
+            assert!(stream.check_schema(&schema).is_ok(), "{:?}", schema);
+        }
+        let plain = Schema::new(vec![Field::new("m", map.data_type().clone(), true)]);
+        assert!(file.check_schema(&plain).is_ok());
+
+        // The first batch is checked too, before the file is created.
+        let batch = RecordBatch::try_from_iter([("d", Arc::new(dictionary) as _)]).unwrap();
+        let mut file = file;
+        assert!(file.write(&batch).is_err());
+        assert!(!path("dict.arrow").exists());
+    }
+
+    #[test]
+    fn compression_requires_an_output() {
+        let err = crate::command()
+            .try_get_matches_from([
+                "parquet_to_arrow",
+                "in.parquet",
+                "--output-compression",
+                "lz4",
+            ])
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::MissingRequiredArgument);
+
+        let matches = crate::command()
+            .try_get_matches_from([
+                "parquet_to_arrow",
+                "in.parquet",
+                "--output",
+                "out.arrow",
+                "--output-compression",
+                "lz4",
+            ])
+            .unwrap();
+        assert_eq!(
+            matches.get_one::<String>("output-compression").unwrap(),
+            "lz4"
+        );
+    }
+}
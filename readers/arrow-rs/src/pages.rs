@@ -0,0 +1,247 @@
+//! Counting the pages a scan decompressed, from the reads of parquet's page reader.
+//!
+//! The sync reader decodes every page through a `SerializedPageReader` over the
+//! `ChunkReader` it is given, and the page reader only reads a page's data to decompress
+//! it. With an offset index, a decoded page is one `get_bytes` of its location, and a
+//! skipped page is not read at all. Without one, a `get_read` at a page header reads on
+//! into the page data to decode the page, but only the header to skip or peek at it, after
+//! which the data is read from where the header ends. A column decoded twice, once for a
+//! pushed down predicate and once for the output, is counted twice.
+//!
+//! The async reader decodes pages from buffers it fetched and owns, so its pages are not
+//! counted.
+
+use std::collections::HashSet;
+use std::io::{self, Read};
+use std::sync::{Arc, Mutex};
+
+use bytes::Bytes;
+use parquet::errors::Result;
+use parquet::file::metadata::ParquetMetaData;
+use parquet::file::reader::{ChunkReader, Length};
+use parquet::format::PageHeader;
+use parquet::thrift::TSerializable;
+use thrift::protocol::TCompactInputProtocol;
+
+/// Bytes of a `get_read` kept to decode the page header it starts with.
+const HEADER_BYTES: usize = 4096;
+
+/// Pages decompressed through every [`RecordingReader`] sharing it.
+#[derive(Debug, Default)]
+pub struct PageReads {
+    state: Mutex<State>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    readers: usize,
+    decompressed: usize,
+    /// Where the data of a page starts whose header was read on its own.
+    peeked: HashSet<u64>,
+}
+
+impl PageReads {
+    fn add_reader(&self) {
+        self.state.lock().unwrap().readers += 1;
+    }
+
+    fn record_page(&self) {
+        self.state.lock().unwrap().decompressed += 1;
+    }
+
+    /// Records a `get_read` at `start` that `read` bytes were read from, `head` being the
+    /// first of them.
+    fn record_read(&self, start: u64, read: u64, head: &[u8]) {
+        let mut state = self.state.lock().unwrap();
+        if state.peeked.remove(&start) {
+            state.decompressed += 1;
+            return;
+        }
+        let mut rest = head;
+        let Ok(header) =
+            PageHeader::read_from_in_protocol(&mut TCompactInputProtocol::new(&mut rest))
+        else {
+            return;
+        };
+        let header_len = (head.len() - rest.len()) as u64;
+        if read > header_len {
+            state.decompressed += 1;
+        } else if header.compressed_page_size > 0 {
+            state.peeked.insert(start + header_len);
+        }
+    }
+
+    /// Pages decompressed, and pages of `leaves` in the whole file, dictionary pages
+    /// included, if the file has an offset index. `None` if no page was read through a
+    /// [`RecordingReader`].
+    pub fn count(
+        &self,
+        metadata: &ParquetMetaData,
+        leaves: &[usize],
+    ) -> Option<(usize, Option<usize>)> {
+        let state = self.state.lock().unwrap();
+        if state.readers == 0 {
+            return None;
+        }
+        let total = metadata.offset_index().map(|offset_index| {
+            metadata
+                .row_groups()
+                .iter()
+                .zip(offset_index)
+                .flat_map(|(rg, columns)| {
+                    leaves
+                        .iter()
+                        .map(move |&leaf| (rg.column(leaf), columns.get(leaf)))
+                })
+                .map(|(column, index)| {
+                    let pages = index.map_or(0, |index| index.page_locations().len());
+                    pages + column.dictionary_page_offset().is_some() as usize
+                })
+                .sum()
+        });
+        Some((state.decompressed, total))
+    }
+}
+
+/// Records the pages decompressed through it into [`PageReads`].
+pub struct RecordingReader<R> {
+    inner: R,
+    reads: Arc<PageReads>,
+}
+
+impl<R> RecordingReader<R> {
+    pub fn new(inner: R, reads: Arc<PageReads>) -> Self {
+        reads.add_reader();
+        Self { inner, reads }
+    }
+}
+
+impl<R: ChunkReader> Length for RecordingReader<R> {
+    fn len(&self) -> u64 {
+        self.inner.len()
+    }
+}
+
+impl<R: ChunkReader> ChunkReader for RecordingReader<R> {
+    type T = RecordedRead<R::T>;
+
+    fn get_read(&self, start: u64) -> Result<Self::T> {
+        Ok(RecordedRead {
+            inner: self.inner.get_read(start)?,
+            start,
+            read: 0,
+            head: vec![],
+            reads: self.reads.clone(),
+        })
+    }
+
+    fn get_bytes(&self, start: u64, length: usize) -> Result<Bytes> {
+        self.reads.record_page();
+        self.inner.get_bytes(start, length)
+    }
+}
+
+/// A `get_read` of a [`RecordingReader`], recorded once it is dropped.
+pub struct RecordedRead<T> {
+    inner: T,
+    start: u64,
+    read: u64,
+    head: Vec<u8>,
+    reads: Arc<PageReads>,
+}
+
+impl<T: Read> Read for RecordedRead<T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        let kept = n.min(HEADER_BYTES - self.head.len());
+        self.head.extend_from_slice(&buf[..kept]);
+        self.read += n as u64;
+        Ok(n)
+    }
+}
+
+impl<T> Drop for RecordedRead<T> {
+    fn drop(&mut self) {
+        self.reads.record_read(self.start, self.read, &self.head);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{Int64Array, RecordBatch};
+    use parquet::arrow::arrow_reader::{
+        ArrowReaderMetadata, ParquetRecordBatchReaderBuilder, RowSelection, RowSelector,
+    };
+    use parquet::arrow::ArrowWriter;
+    use parquet::file::metadata::ParquetMetaDataReader;
+    use parquet::file::properties::WriterProperties;
+
+    /// 1000 rows in pages of 100, without a dictionary.
+    fn file() -> Bytes {
+        let batch = RecordBatch::try_from_iter([(
+            "v",
+            Arc::new(Int64Array::from_iter_values(0..1000)) as _,
+        )])
+        .unwrap();
+        let props = WriterProperties::builder()
+            .set_dictionary_enabled(false)
+            .set_data_page_row_count_limit(100)
+            .set_write_batch_size(100)
+            .build();
+        let mut buf = vec![];
+        let mut writer = ArrowWriter::try_new(&mut buf, batch.schema(), Some(props)).unwrap();
+        writer.write(&batch).unwrap();
+        writer.close().unwrap();
+        Bytes::from(buf)
+    }
+
+    fn decompressed(page_index: bool, selection: Option<RowSelection>) -> (usize, Option<usize>) {
+        let file = file();
+        let metadata = ParquetMetaDataReader::new()
+            .with_page_indexes(page_index)
+            .parse_and_finish(&file)
+            .unwrap();
+        let metadata =
+            ArrowReaderMetadata::try_new(Arc::new(metadata), Default::default()).unwrap();
+        let reads = Arc::new(PageReads::default());
+        let input = RecordingReader::new(file, reads.clone());
+        let mut builder =
+            ParquetRecordBatchReaderBuilder::new_with_metadata(input, metadata.clone());
+        if let Some(selection) = selection {
+            builder = builder.with_row_selection(selection);
+        }
+        for batch in builder.build().unwrap() {
+            batch.unwrap();
+        }
+        reads.count(metadata.metadata(), &[0]).unwrap()
+    }
+
+    #[test]
+    fn counts_every_page_of_a_full_read() {
+        assert_eq!(decompressed(false, None), (10, None));
+        assert_eq!(decompressed(true, None), (10, Some(10)));
+    }
+
+    #[test]
+    fn skipped_pages_are_not_counted() {
+        let selection = || {
+            Some(RowSelection::from(vec![
+                RowSelector::skip(450),
+                RowSelector::select(100),
+                RowSelector::skip(450),
+            ]))
+        };
+        // Without an offset index, the skipped pages' headers are read, but not their data.
+        assert_eq!(decompressed(false, selection()), (2, None));
+        assert_eq!(decompressed(true, selection()), (2, Some(10)));
+    }
+
+    #[test]
+    fn nothing_is_counted_without_a_reader() {
+        let metadata = ParquetMetaDataReader::new()
+            .parse_and_finish(&file())
+            .unwrap();
+        assert_eq!(PageReads::default().count(&metadata, &[0]), None);
+    }
+}
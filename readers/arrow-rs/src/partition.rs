@@ -0,0 +1,214 @@
+//! Hive partition columns, parsed from the `key=value` directories of each file's path
+//! below the dataset root.
+//!
+//! A column is Int64 if every value parses as one and Utf8 otherwise. Values are
+//! percent-decoded, and `__HIVE_DEFAULT_PARTITION__` is null.
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use arrow::array::{
+    ArrayRef, Int64Array, RecordBatch, RecordBatchOptions, StringArray, UInt32Array,
+};
+use arrow::compute::take;
+use arrow::datatypes::{Field, Schema, SchemaRef};
+use arrow::error::ArrowError;
+
+use crate::predicate::Predicate;
+
+const DEFAULT_PARTITION: &str = "__HIVE_DEFAULT_PARTITION__";
+
+/// The partition values of every file of a dataset, one row per file.
+pub struct Partitions {
+    values: RecordBatch,
+}
+
+impl Partitions {
+    /// Parses the partition values of `files` below `root`, which must all have the same
+    /// keys in the same order.
+    pub fn new(root: &Path, files: &[PathBuf]) -> Result<Self, ArrowError> {
+        let parsed: Vec<Vec<(String, Option<String>)>> =
+            files.iter().map(|f| parse(root, f)).collect();
+        let keys: Vec<&str> = parsed
+            .first()
+            .map(|p| p.iter().map(|(k, _)| k.as_str()).collect())
+            .unwrap_or_default();
+        for (file, p) in files.iter().zip(&parsed) {
+            if !p.iter().map(|(k, _)| k.as_str()).eq(keys.iter().copied()) {
+                return Err(ArrowError::InvalidArgumentError(format!(
+                    "{} has partition keys {:?}, expected {:?}",
+                    file.display(),
+                    p.iter().map(|(k, _)| k).collect::<Vec<_>>(),
+                    keys
+                )));
+            }
+        }
+
+        let mut fields = vec![];
+        let mut columns: Vec<ArrayRef> = vec![];
+        for (i, key) in keys.iter().enumerate() {
+            let values: Vec<Option<&str>> = parsed.iter().map(|p| p[i].1.as_deref()).collect();
+            let ints: Option<Vec<Option<i64>>> = values
+                .iter()
+                .map(|v| match v {
+                    Some(v) => v.parse().ok().map(Some),
+                    None => Some(None),
+                })
+                .collect();
+            let column: ArrayRef = match ints {
+                Some(ints) => Arc::new(Int64Array::from(ints)),
+                None => Arc::new(StringArray::from(values)),
+            };
+            fields.push(Field::new(*key, column.data_type().clone(), true));
+            columns.push(column);
+        }
+        let options = RecordBatchOptions::new().with_row_count(Some(files.len()));
+        let values =
+            RecordBatch::try_new_with_options(Arc::new(Schema::new(fields)), columns, &options)?;
+        Ok(Self { values })
+    }
+
+    pub fn schema(&self) -> SchemaRef {
+        self.values.schema()
+    }
+
+    /// Which files `predicate` keeps, it may only reference partition columns.
+    pub fn keep(&self, predicate: &Predicate) -> Result<Vec<bool>, ArrowError> {
+        let keep = predicate.evaluate(&self.values)?;
+        Ok(keep.iter().map(|keep| keep == Some(true)).collect())
+    }
+
+    /// Appends the partition values of `file` to `batch` as constant columns.
+    pub fn append(&self, file: usize, batch: RecordBatch) -> Result<RecordBatch, ArrowError> {
+        if self.values.num_columns() == 0 {
+            return Ok(batch);
+        }
+        let indices = UInt32Array::from(vec![file as u32; batch.num_rows()]);
+        let mut fields = batch.schema().fields().to_vec();
+        let mut columns = batch.columns().to_vec();
+        for (field, values) in self
+            .values
+            .schema()
+            .fields()
+            .iter()
+            .zip(self.values.columns())
+        {
+            fields.push(field.clone());
+            columns.push(take(values, &indices, None)?);
+        }
+        RecordBatch::try_new(Arc::new(Schema::new(fields)), columns)
+    }
+}
+
+/// The `key=value` directories of `path` below `root`, outermost first.
+fn parse(root: &Path, path: &Path) -> Vec<(String, Option<String>)> {
+    let Some(dir) = path.strip_prefix(root).unwrap_or(path).parent() else {
+        return vec![];
+    };
+    dir.components()
+        .filter_map(|c| {
+            let segment = c.as_os_str().to_str()?;
+            let (key, value) = segment.split_once('=')?;
+            let value = (value != DEFAULT_PARTITION).then(|| unescape(value));
+            Some((unescape(key), value))
+        })
+        .collect()
+}
+
+/// Decodes the `%XX` escapes hive writes for characters that are not allowed in paths.
+fn unescape(s: &str) -> String {
+    let mut out = Vec::with_capacity(s.len());
+    let bytes = s.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .filter(|h| h.iter().all(u8::is_ascii_hexdigit))
+            .and_then(|h| u8::from_str_radix(std::str::from_utf8(h).ok()?, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(b)) => {
+                out.push(b);
+                i += 3;
+            }
+            (b, _) => {
+                out.push(b);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pair(key: &str, value: Option<&str>) -> (String, Option<String>) {
+        (key.into(), value.map(Into::into))
+    }
+
+    #[test]
+    fn parse_below_root() {
+        let root = Path::new("/data/env=prod/table");
+        let file = root.join("year=2024/month=1/part-0.parquet");
+        assert_eq!(
+            parse(root, &file),
+            vec![pair("year", Some("2024")), pair("month", Some("1"))]
+        );
+        assert_eq!(parse(root, &root.join("part-0.parquet")), vec![]);
+    }
+
+    #[test]
+    fn parse_skips_other_directories() {
+        let root = Path::new(".");
+        let file = Path::new("./year=2024/copy/region=__HIVE_DEFAULT_PARTITION__/p.parquet");
+        assert_eq!(
+            parse(root, file),
+            vec![pair("year", Some("2024")), pair("region", None)]
+        );
+    }
+
+    #[test]
+    fn parse_unescapes_keys_and_values() {
+        let file = Path::new("d/my%20key=a%2Fb%3D/p.parquet");
+        assert_eq!(
+            parse(Path::new("d"), file),
+            vec![pair("my key", Some("a/b="))]
+        );
+    }
+
+    #[test]
+    fn unescape_escapes() {
+        assert_eq!(unescape("a%2Fb"), "a/b");
+        assert_eq!(unescape("%41%62"), "Ab");
+        assert_eq!(unescape("%C3%A9"), "é");
+        assert_eq!(unescape("100%"), "100%");
+        assert_eq!(unescape("%zz%4"), "%zz%4");
+        assert_eq!(unescape("%FF"), "\u{fffd}");
+    }
+
+    #[test]
+    fn columns_are_typed_and_keys_checked() {
+        let root = Path::new("d");
+        let files = [
+            root.join("y=2024/c=a/p.parquet"),
+            root.join("y=__HIVE_DEFAULT_PARTITION__/c=7/p.parquet"),
+        ];
+        let partitions = Partitions::new(root, &files).unwrap();
+        let schema = partitions.schema();
+        assert_eq!(
+            schema.field(0).data_type(),
+            &arrow::datatypes::DataType::Int64
+        );
+        assert_eq!(
+            schema.field(1).data_type(),
+            &arrow::datatypes::DataType::Utf8
+        );
+
+        let keep = partitions.keep(&Predicate::parse("y = 2024").unwrap());
+        assert_eq!(keep.unwrap(), vec![true, false]);
+
+        let mismatched = [root.join("y=1/p.parquet"), root.join("c=1/p.parquet")];
+        assert!(Partitions::new(root, &mismatched).is_err());
+    }
+}
@@ -0,0 +1,612 @@
+//! A tiny filter expression language, e.g.
+//! `l_shipdate <= '1998-09-02' AND (l_returnflag IN ('A', 'R') OR l_comment IS NULL)`.
+
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use arrow::array::{Array, ArrayRef, BooleanArray, Float64Array, Int64Array, Scalar, StringArray};
+use arrow::array::{AsArray, RecordBatch};
+use arrow::compute::kernels::cmp;
+use arrow::compute::{and_kleene, cast_with_options, is_not_null, is_null, or_kleene, CastOptions};
+use arrow::datatypes::DataType;
+use arrow::error::ArrowError;
+use parquet::arrow::arrow_reader::{ArrowPredicateFn, RowFilter};
+use parquet::arrow::ProjectionMask;
+use parquet::errors::Result;
+use parquet::schema::types::SchemaDescriptor;
+
+use crate::projection::leaves_for_path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CmpOp {
+    Eq,
+    NotEq,
+    Lt,
+    LtEq,
+    Gt,
+    GtEq,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Literal {
+    Int(i64),
+    Float(f64),
+    Str(String),
+    Bool(bool),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Predicate {
+    Compare {
+        column: String,
+        op: CmpOp,
+        value: Literal,
+    },
+    In {
+        column: String,
+        values: Vec<Literal>,
+        negated: bool,
+    },
+    IsNull {
+        column: String,
+        negated: bool,
+    },
+    And(Box<Predicate>, Box<Predicate>),
+    Or(Box<Predicate>, Box<Predicate>),
+}
+
+impl Predicate {
+    pub fn parse(input: &str) -> Result<Self, ArrowError> {
+        let mut parser = Parser {
+            tokens: tokenize(input)?,
+            pos: 0,
+        };
+        let predicate = parser.parse_or()?;
+        match parser.peek() {
+            None => Ok(predicate),
+            Some(t) => Err(parse_error(format!("unexpected token {}", t))),
+        }
+    }
+
+    /// Columns referenced by the predicate, deduplicated, in order of appearance.
+    pub fn columns(&self) -> Vec<&str> {
+        let mut columns = vec![];
+        self.collect_columns(&mut columns);
+        columns
+    }
+
+    fn collect_columns<'a>(&'a self, out: &mut Vec<&'a str>) {
+        match self {
+            Predicate::Compare { column, .. }
+            | Predicate::In { column, .. }
+            | Predicate::IsNull { column, .. } => {
+                if !out.contains(&column.as_str()) {
+                    out.push(column)
+                }
+            }
+            Predicate::And(l, r) | Predicate::Or(l, r) => {
+                l.collect_columns(out);
+                r.collect_columns(out);
+            }
+        }
+    }
+
+    /// Evaluates the predicate against `batch`, nulls mean "unknown" and are filtered out.
+    pub fn evaluate(&self, batch: &RecordBatch) -> Result<BooleanArray, ArrowError> {
+        match self {
+            Predicate::Compare { column, op, value } => {
+                let array = column_by_path(batch, column)?;
+                let scalar = value.to_scalar(array.data_type())?;
+                match op {
+                    CmpOp::Eq => cmp::eq(&array, &scalar),
+                    CmpOp::NotEq => cmp::neq(&array, &scalar),
+                    CmpOp::Lt => cmp::lt(&array, &scalar),
+                    CmpOp::LtEq => cmp::lt_eq(&array, &scalar),
+                    CmpOp::Gt => cmp::gt(&array, &scalar),
+                    CmpOp::GtEq => cmp::gt_eq(&array, &scalar),
+                }
+            }
+            Predicate::In {
+                column,
+                values,
+                negated,
+            } => {
+                let array = column_by_path(batch, column)?;
+                let mut result = BooleanArray::from(vec![false; array.len()]);
+                for value in values {
+                    let scalar = value.to_scalar(array.data_type())?;
+                    result = or_kleene(&result, &cmp::eq(&array, &scalar)?)?;
+                }
+                match negated {
+                    true => arrow::compute::not(&result),
+                    false => Ok(result),
+                }
+            }
+            Predicate::IsNull { column, negated } => {
+                let array = column_by_path(batch, column)?;
+                match negated {
+                    true => is_not_null(&array),
+                    false => is_null(&array),
+                }
+            }
+            Predicate::And(l, r) => and_kleene(&l.evaluate(batch)?, &r.evaluate(batch)?),
+            Predicate::Or(l, r) => or_kleene(&l.evaluate(batch)?, &r.evaluate(batch)?),
+        }
+    }
+}
+
+impl Literal {
+    /// Casts the literal to `data_type`, so that `'1998-09-02'` compares against a `Date32`
+    /// column and `5` against a `Decimal128` one.
+    ///
+    /// Numbers that the cast would round or truncate are rejected, `x < 2.5` against an
+    /// integer column must not become `x < 2`. Floating point columns are exempt, they
+    /// compare approximately anyway.
+    pub fn to_scalar(&self, data_type: &DataType) -> Result<Scalar<ArrayRef>, ArrowError> {
+        let array: ArrayRef = match self {
+            Literal::Int(v) => Arc::new(Int64Array::from(vec![*v])),
+            Literal::Float(v) => Arc::new(Float64Array::from(vec![*v])),
+            Literal::Str(v) => Arc::new(StringArray::from(vec![v.as_str()])),
+            Literal::Bool(v) => Arc::new(BooleanArray::from(vec![*v])),
+        };
+        let options = CastOptions {
+            safe: false,
+            ..Default::default()
+        };
+        let scalar = cast_with_options(&array, data_type, &options)?;
+        if matches!(self, Literal::Int(_) | Literal::Float(_)) && !data_type.is_floating() {
+            let back = cast_with_options(&scalar, array.data_type(), &options)?;
+            if back.to_data() != array.to_data() {
+                return Err(ArrowError::CastError(format!(
+                    "{:?} cannot be represented as {} without loss",
+                    self, data_type
+                )));
+            }
+        }
+        Ok(Scalar::new(scalar))
+    }
+}
+
+/// Looks up a top-level column, descending into struct children for dotted paths.
+fn column_by_path(batch: &RecordBatch, path: &str) -> Result<ArrayRef, ArrowError> {
+    let not_found = || ArrowError::SchemaError(format!("column {} not found", path));
+    let mut parts = path.split('.');
+    let root = parts.next().ok_or_else(not_found)?;
+    let mut array = batch.column_by_name(root).ok_or_else(not_found)?.clone();
+    for part in parts {
+        let child = match array.data_type() {
+            DataType::Struct(_) => array.as_struct().column_by_name(part).cloned(),
+            _ => None,
+        };
+        array = child.ok_or_else(not_found)?;
+    }
+    Ok(array)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Literal(Literal),
+    Op(CmpOp),
+    LParen,
+    RParen,
+    Comma,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Ident(s) => write!(f, "{}", s),
+            Token::Literal(l) => write!(f, "{:?}", l),
+            Token::Op(op) => write!(f, "{:?}", op),
+            Token::LParen => write!(f, "("),
+            Token::RParen => write!(f, ")"),
+            Token::Comma => write!(f, ","),
+        }
+    }
+}
+
+fn parse_error(msg: impl Into<String>) -> ArrowError {
+    ArrowError::InvalidArgumentError(format!("invalid predicate: {}", msg.into()))
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, ArrowError> {
+    let mut tokens = vec![];
+    let mut chars = input.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' | ')' | ',' => {
+                chars.next();
+                tokens.push(match c {
+                    '(' => Token::LParen,
+                    ')' => Token::RParen,
+                    _ => Token::Comma,
+                });
+            }
+            '=' | '!' | '<' | '>' => {
+                chars.next();
+                let eq = chars.next_if_eq(&'=').is_some();
+                let op = match (c, eq) {
+                    ('=', _) => CmpOp::Eq,
+                    ('!', true) => CmpOp::NotEq,
+                    ('<', true) => CmpOp::LtEq,
+                    ('<', false) if chars.next_if_eq(&'>').is_some() => CmpOp::NotEq,
+                    ('<', false) => CmpOp::Lt,
+                    ('>', true) => CmpOp::GtEq,
+                    ('>', false) => CmpOp::Gt,
+                    _ => return Err(parse_error(format!("unexpected character {}", c))),
+                };
+                tokens.push(Token::Op(op));
+            }
+            '\'' | '"' => {
+                chars.next();
+                let mut s = String::new();
+                loop {
+                    match chars.next() {
+                        // a doubled quote escapes itself
+                        Some(q) if q == c && chars.next_if_eq(&c).is_some() => s.push(c),
+                        Some(q) if q == c => break,
+                        Some(other) => s.push(other),
+                        None => return Err(parse_error("unterminated quote")),
+                    }
+                }
+                tokens.push(match c {
+                    '\'' => Token::Literal(Literal::Str(s)),
+                    _ => Token::Ident(s),
+                });
+            }
+            c if c.is_ascii_digit() || c == '-' || c == '.' => {
+                let mut s = String::new();
+                while let Some(d) = chars
+                    .next_if(|d| d.is_ascii_alphanumeric() || *d == '.' || *d == '-' || *d == '+')
+                {
+                    s.push(d);
+                }
+                let literal = match s.parse::<i64>() {
+                    Ok(v) => Literal::Int(v),
+                    Err(_) => Literal::Float(
+                        s.parse::<f64>()
+                            .map_err(|_| parse_error(format!("invalid number {}", s)))?,
+                    ),
+                };
+                tokens.push(Token::Literal(literal));
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let mut s = String::new();
+                while let Some(d) = chars.next_if(|d| d.is_alphanumeric() || *d == '_' || *d == '.')
+                {
+                    s.push(d);
+                }
+                tokens.push(match s.to_ascii_lowercase().as_str() {
+                    "true" => Token::Literal(Literal::Bool(true)),
+                    "false" => Token::Literal(Literal::Bool(false)),
+                    _ => Token::Ident(s),
+                });
+            }
+            c => return Err(parse_error(format!("unexpected character {}", c))),
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Result<Token, ArrowError> {
+        let token = self
+            .tokens
+            .get(self.pos)
+            .cloned()
+            .ok_or_else(|| parse_error("unexpected end of input"))?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<(), ArrowError> {
+        match self.next()? {
+            t if t == expected => Ok(()),
+            t => Err(parse_error(format!("expected {}, found {}", expected, t))),
+        }
+    }
+
+    /// Consumes the next token if it is the (case insensitive) keyword `kw`.
+    fn keyword(&mut self, kw: &str) -> bool {
+        match self.peek() {
+            Some(Token::Ident(s)) if s.eq_ignore_ascii_case(kw) => {
+                self.pos += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<Predicate, ArrowError> {
+        let mut left = self.parse_and()?;
+        while self.keyword("or") {
+            left = Predicate::Or(Box::new(left), Box::new(self.parse_and()?));
+        }
+        Ok(left)
+    }
+
+    fn parse_and(&mut self) -> Result<Predicate, ArrowError> {
+        let mut left = self.parse_atom()?;
+        while self.keyword("and") {
+            left = Predicate::And(Box::new(left), Box::new(self.parse_atom()?));
+        }
+        Ok(left)
+    }
+
+    fn parse_atom(&mut self) -> Result<Predicate, ArrowError> {
+        let column = match self.next()? {
+            Token::LParen => {
+                let inner = self.parse_or()?;
+                self.expect(Token::RParen)?;
+                return Ok(inner);
+            }
+            Token::Ident(column) => column,
+            t => return Err(parse_error(format!("expected column, found {}", t))),
+        };
+
+        if self.keyword("is") {
+            let negated = self.keyword("not");
+            if !self.keyword("null") {
+                return Err(parse_error("expected NULL after IS"));
+            }
+            return Ok(Predicate::IsNull { column, negated });
+        }
+
+        let negated = self.keyword("not");
+        if self.keyword("in") {
+            self.expect(Token::LParen)?;
+            let mut values = vec![self.parse_literal()?];
+            while self.peek() == Some(&Token::Comma) {
+                self.pos += 1;
+                values.push(self.parse_literal()?);
+            }
+            self.expect(Token::RParen)?;
+            return Ok(Predicate::In {
+                column,
+                values,
+                negated,
+            });
+        } else if negated {
+            return Err(parse_error("expected IN after NOT"));
+        }
+
+        match self.next()? {
+            Token::Op(op) => Ok(Predicate::Compare {
+                column,
+                op,
+                value: self.parse_literal()?,
+            }),
+            t => Err(parse_error(format!("expected comparison, found {}", t))),
+        }
+    }
+
+    fn parse_literal(&mut self) -> Result<Literal, ArrowError> {
+        match self.next()? {
+            Token::Literal(l) => Ok(l),
+            t => Err(parse_error(format!("expected literal, found {}", t))),
+        }
+    }
+}
+
+/// Rows seen and selected by a [`Predicate`], shared with the reader's row filter.
+#[derive(Debug, Default)]
+pub struct FilterCounters {
+    scanned: AtomicUsize,
+    selected: AtomicUsize,
+}
+
+impl FilterCounters {
+    pub fn record(&self, selection: &BooleanArray) {
+        self.scanned.fetch_add(selection.len(), Ordering::Relaxed);
+        self.selected
+            .fetch_add(selection.true_count(), Ordering::Relaxed);
+    }
+
+    pub fn scanned(&self) -> usize {
+        self.scanned.load(Ordering::Relaxed)
+    }
+
+    pub fn selected(&self) -> usize {
+        self.selected.load(Ordering::Relaxed)
+    }
+}
+
+/// Leaf columns the predicate needs to be evaluated.
+pub fn predicate_leaves(predicate: &Predicate, schema: &SchemaDescriptor) -> Result<Vec<usize>> {
+    let mut leaves = vec![];
+    for column in predicate.columns() {
+        leaves.extend(leaves_for_path(schema, column)?);
+    }
+    leaves.sort_unstable();
+    leaves.dedup();
+    Ok(leaves)
+}
+
+/// Wraps the predicate into a [`RowFilter`] so that it is evaluated during decode.
+pub fn row_filter(
+    predicate: Predicate,
+    schema: &SchemaDescriptor,
+    counters: Arc<FilterCounters>,
+) -> Result<RowFilter> {
+    let mask = ProjectionMask::leaves(schema, predicate_leaves(&predicate, schema)?);
+    let filter = ArrowPredicateFn::new(mask, move |batch| {
+        let selection = predicate.evaluate(&batch)?;
+        counters.record(&selection);
+        Ok(selection)
+    });
+    Ok(RowFilter::new(vec![Box::new(filter)]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{Date32Array, Datum, Int32Array};
+    use arrow::datatypes::{Field, Schema};
+
+    fn compare(column: &str, op: CmpOp, value: Literal) -> Predicate {
+        Predicate::Compare {
+            column: column.into(),
+            op,
+            value,
+        }
+    }
+
+    #[test]
+    fn tokenize_operators() {
+        let ops = tokenize("= != <> < <= > >=").unwrap();
+        let expected = [
+            CmpOp::Eq,
+            CmpOp::NotEq,
+            CmpOp::NotEq,
+            CmpOp::Lt,
+            CmpOp::LtEq,
+            CmpOp::Gt,
+            CmpOp::GtEq,
+        ];
+        assert_eq!(ops, expected.map(Token::Op));
+        assert!(tokenize("a ! 1").is_err());
+    }
+
+    #[test]
+    fn tokenize_literals() {
+        let tokens = tokenize("5 -3 2.5 1e3 'it''s' \"quoted col\" TRUE false a.b_c").unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Literal(Literal::Int(5)),
+                Token::Literal(Literal::Int(-3)),
+                Token::Literal(Literal::Float(2.5)),
+                Token::Literal(Literal::Float(1000.0)),
+                Token::Literal(Literal::Str("it's".into())),
+                Token::Ident("quoted col".into()),
+                Token::Literal(Literal::Bool(true)),
+                Token::Literal(Literal::Bool(false)),
+                Token::Ident("a.b_c".into()),
+            ]
+        );
+        assert!(tokenize("'open").is_err());
+        assert!(tokenize("1.2.3").is_err());
+        assert!(tokenize("a ; b").is_err());
+    }
+
+    #[test]
+    fn parse_precedence() {
+        let predicate = Predicate::parse("a = 1 OR b < 2 and c IS NULL").unwrap();
+        assert_eq!(
+            predicate,
+            Predicate::Or(
+                Box::new(compare("a", CmpOp::Eq, Literal::Int(1))),
+                Box::new(Predicate::And(
+                    Box::new(compare("b", CmpOp::Lt, Literal::Int(2))),
+                    Box::new(Predicate::IsNull {
+                        column: "c".into(),
+                        negated: false,
+                    }),
+                )),
+            )
+        );
+
+        let predicate = Predicate::parse("(a = 1 OR b < 2) AND c IS NOT NULL").unwrap();
+        assert!(matches!(predicate, Predicate::And(l, _) if matches!(*l, Predicate::Or(_, _))));
+    }
+
+    #[test]
+    fn parse_in() {
+        assert_eq!(
+            Predicate::parse("f NOT IN ('A', 'R')").unwrap(),
+            Predicate::In {
+                column: "f".into(),
+                values: vec![Literal::Str("A".into()), Literal::Str("R".into())],
+                negated: true,
+            }
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        for input in [
+            "",
+            "a",
+            "a = ",
+            "a = 1 b",
+            "a NOT = 1",
+            "a IS 1",
+            "a IN ()",
+            "a IN (1",
+            "(a = 1",
+            "1 = a",
+        ] {
+            assert!(Predicate::parse(input).is_err(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn columns_are_deduplicated() {
+        let predicate = Predicate::parse("b = 1 AND (a = 2 OR b = 3) AND s.x IS NULL").unwrap();
+        assert_eq!(predicate.columns(), vec!["b", "a", "s.x"]);
+    }
+
+    #[test]
+    fn literal_coercion() {
+        let scalar = Literal::Str("1998-09-02".into())
+            .to_scalar(&DataType::Date32)
+            .unwrap();
+        assert_eq!(scalar.get().0.data_type(), &DataType::Date32);
+
+        let decimal = DataType::Decimal128(15, 2);
+        assert!(Literal::Int(5).to_scalar(&decimal).is_ok());
+        assert!(Literal::Float(2.25).to_scalar(&decimal).is_ok());
+        assert!(Literal::Float(2.255).to_scalar(&decimal).is_err());
+
+        assert!(Literal::Float(2.0).to_scalar(&DataType::Int32).is_ok());
+        assert!(Literal::Float(2.5).to_scalar(&DataType::Int32).is_err());
+        assert!(Literal::Int(300).to_scalar(&DataType::Int8).is_err());
+        assert!(Literal::Int(2).to_scalar(&DataType::Boolean).is_err());
+        assert!(Literal::Float(0.1).to_scalar(&DataType::Float32).is_ok());
+        assert!(Literal::Str("x".into())
+            .to_scalar(&DataType::Int32)
+            .is_err());
+    }
+
+    #[test]
+    fn evaluate() {
+        let schema = Schema::new(vec![
+            Field::new("x", DataType::Int32, true),
+            Field::new("d", DataType::Date32, false),
+        ]);
+        let batch = RecordBatch::try_new(
+            Arc::new(schema),
+            vec![
+                Arc::new(Int32Array::from(vec![Some(1), Some(2), Some(3), None])),
+                Arc::new(Date32Array::from(vec![0, 1, 2, 3])),
+            ],
+        )
+        .unwrap();
+        let eval = |input: &str| Predicate::parse(input).unwrap().evaluate(&batch);
+
+        let selection = eval("x <= 2 OR d = '1970-01-04'").unwrap();
+        assert_eq!(
+            selection,
+            BooleanArray::from(vec![Some(true), Some(true), Some(false), Some(true)])
+        );
+        assert_eq!(eval("x IN (1, 3)").unwrap().true_count(), 2);
+        assert_eq!(eval("x IS NULL").unwrap().true_count(), 1);
+        assert!(eval("x < 2.5").is_err());
+        assert!(eval("y = 1").is_err());
+    }
+}
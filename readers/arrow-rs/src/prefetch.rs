@@ -0,0 +1,467 @@
+//! Planning the I/O of a scan before decoding starts: the column chunk ranges it needs are
+//! merged across small gaps and fetched ahead of the decoder.
+
+use std::collections::HashMap;
+use std::ops::Range;
+use std::sync::{Arc, Mutex};
+
+use bytes::Bytes;
+use futures::future::{BoxFuture, Shared};
+use futures::FutureExt;
+use parquet::arrow::async_reader::AsyncFileReader;
+use parquet::errors::{ParquetError, Result};
+use parquet::file::metadata::ParquetMetaData;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tokio::task::JoinSet;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PrefetchOptions {
+    /// Ranges at most this many bytes apart are fetched with one request.
+    pub coalesce: usize,
+    /// Planned requests fetched ahead of the decoder and not yet read by it, 0 fetches on
+    /// demand.
+    pub depth: usize,
+}
+
+/// The requests a scan issues for its column chunks.
+#[derive(Debug)]
+pub struct IoPlan {
+    /// Merged ranges in file order, one request each.
+    pub requests: Vec<Range<usize>>,
+    /// Row groups whose column chunks each request covers.
+    pub row_groups: Vec<Vec<usize>>,
+    /// Bytes of the column chunks in each request, counting overlaps once.
+    pub request_bytes: Vec<usize>,
+    /// Column chunks covered by the requests.
+    pub chunks: usize,
+    /// Bytes of the column chunks.
+    pub needed_bytes: usize,
+    /// Bytes between chunks that are fetched only because of merging.
+    pub wasted_bytes: usize,
+}
+
+impl IoPlan {
+    /// Plans the column chunks of `leaves` in `row_groups`.
+    pub fn new(
+        metadata: &ParquetMetaData,
+        row_groups: &[usize],
+        leaves: &[usize],
+        coalesce: usize,
+    ) -> Self {
+        let chunks = row_groups
+            .iter()
+            .flat_map(|&rg| {
+                leaves.iter().map(move |&leaf| {
+                    let (start, len) = metadata.row_group(rg).column(leaf).byte_range();
+                    (rg, start as usize..(start + len) as usize)
+                })
+            })
+            .collect();
+        Self::from_chunks(chunks, coalesce)
+    }
+
+    /// Plans the `(row group, byte range)` column chunks.
+    fn from_chunks(mut chunks: Vec<(usize, Range<usize>)>, coalesce: usize) -> Self {
+        chunks.sort_unstable_by_key(|(_, r)| r.start);
+
+        let mut requests: Vec<Range<usize>> = vec![];
+        let mut row_groups: Vec<Vec<usize>> = vec![];
+        let mut request_bytes: Vec<usize> = vec![];
+        let mut wasted_bytes = 0;
+        for (rg, chunk) in &chunks {
+            match (requests.last_mut(), row_groups.last_mut()) {
+                (Some(last), Some(covered)) if chunk.start <= last.end + coalesce => {
+                    wasted_bytes += chunk.start.saturating_sub(last.end);
+                    *request_bytes.last_mut().unwrap() +=
+                        chunk.end.saturating_sub(chunk.start.max(last.end));
+                    last.end = last.end.max(chunk.end);
+                    if !covered.contains(rg) {
+                        covered.push(*rg);
+                    }
+                }
+                _ => {
+                    requests.push(chunk.clone());
+                    row_groups.push(vec![*rg]);
+                    request_bytes.push(chunk.len());
+                }
+            }
+        }
+
+        Self {
+            chunks: chunks.len(),
+            needed_bytes: chunks.iter().map(|(_, r)| r.len()).sum(),
+            requests,
+            row_groups,
+            request_bytes,
+            wasted_bytes,
+        }
+    }
+
+    /// Prints the request and byte counts.
+    pub fn report(&self) {
+        println!(
+            "io plan: {} column chunks in {} requests, {} bytes needed, {} bytes wasted",
+            self.chunks,
+            self.requests.len(),
+            self.needed_bytes,
+            self.wasted_bytes
+        );
+    }
+}
+
+type Fetch = Shared<BoxFuture<'static, Result<Bytes, Arc<ParquetError>>>>;
+
+/// A planned request, kept until every row group it covers has been read.
+struct Planned {
+    fetch: Fetch,
+    /// Row groups whose readers have not been dropped yet.
+    readers: usize,
+    /// Whether a reader has asked for the request.
+    taken: bool,
+    /// Bytes of the column chunks not yet read from the request.
+    unread: usize,
+    /// Held from [`Prefetch::drive`] starting the request until its column chunks have
+    /// been read, or its readers dropped.
+    slot: Option<OwnedSemaphorePermit>,
+}
+
+/// The planned requests, each started either by [`Prefetch::drive`] or by the first
+/// reader that needs it, and shared by the [`PrefetchReader`]s of the row groups it
+/// covers. A request's bytes are dropped once all of those readers are.
+///
+/// Every request goes through one reader, opened by the first request that starts, so
+/// that a reader such as io-uring's sets up its ring once. Requests take turns on it.
+#[derive(Clone, Default)]
+pub struct Prefetch {
+    ranges: Arc<Vec<Range<usize>>>,
+    /// Requests covering each row group.
+    requests: Arc<HashMap<usize, Vec<usize>>>,
+    planned: Arc<Mutex<Vec<Option<Planned>>>>,
+}
+
+impl Prefetch {
+    /// Fetches every request of `plan` through one reader from `open`.
+    pub fn new<R, Fut>(plan: &IoPlan, open: impl Fn() -> Fut) -> Self
+    where
+        R: AsyncFileReader + 'static,
+        Fut: std::future::Future<Output = Result<R>> + Send + 'static,
+    {
+        let input = open()
+            .map(|r| {
+                r.map(|r| Arc::new(tokio::sync::Mutex::new(r)))
+                    .map_err(Arc::new)
+            })
+            .boxed()
+            .shared();
+        let planned = plan
+            .requests
+            .iter()
+            .zip(&plan.row_groups)
+            .zip(&plan.request_bytes)
+            .map(|((range, row_groups), &bytes)| {
+                let (request, input) = (range.clone(), input.clone());
+                let fetch = async move {
+                    let input = input.await?;
+                    let mut input = input.lock().await;
+                    input.get_bytes(request).await.map_err(Arc::new)
+                };
+                Some(Planned {
+                    fetch: fetch.boxed().shared(),
+                    readers: row_groups.len(),
+                    taken: false,
+                    unread: bytes,
+                    slot: None,
+                })
+            })
+            .collect();
+        let mut requests: HashMap<usize, Vec<usize>> = HashMap::new();
+        for (i, row_groups) in plan.row_groups.iter().enumerate() {
+            for &rg in row_groups {
+                requests.entry(rg).or_default().push(i);
+            }
+        }
+        Self {
+            ranges: Arc::new(plan.requests.clone()),
+            requests: Arc::new(requests),
+            planned: Arc::new(Mutex::new(planned)),
+        }
+    }
+
+    /// Starts the requests in file order, keeping at most `depth` of them fetched or in
+    /// flight but not yet taken by a reader. Cancelling it cancels the requests it
+    /// started that no reader has taken.
+    pub async fn drive(self, depth: usize) {
+        let slots = Arc::new(Semaphore::new(depth.max(1)));
+        let mut fetches = JoinSet::new();
+        for i in 0..self.ranges.len() {
+            let Ok(slot) = slots.clone().acquire_owned().await else {
+                break;
+            };
+            let mut planned = self.planned.lock().unwrap();
+            if let Some(p) = planned[i].as_mut().filter(|p| !p.taken) {
+                p.slot = Some(slot);
+                fetches.spawn(p.fetch.clone().map(drop));
+            }
+        }
+        while fetches.join_next().await.is_some() {}
+    }
+
+    /// Takes the parts of planned requests that `ranges` cover, or nothing unless the plan
+    /// covers every one of them. A request's slot is given back once its column chunks
+    /// have all been taken.
+    fn get(&self, ranges: &[Range<usize>]) -> Option<Vec<BoxFuture<'static, Result<Bytes>>>> {
+        let mut planned = self.planned.lock().unwrap();
+        let requests: Vec<usize> = ranges
+            .iter()
+            .map(|range| {
+                let i = self
+                    .ranges
+                    .partition_point(|r| r.start <= range.start)
+                    .checked_sub(1)?;
+                let covered = range.end <= self.ranges[i].end && planned[i].is_some();
+                covered.then_some(i)
+            })
+            .collect::<Option<_>>()?;
+
+        let fetches = ranges.iter().zip(requests).map(|(range, i)| {
+            let p = planned[i].as_mut().unwrap();
+            p.taken = true;
+            p.unread = p.unread.saturating_sub(range.len());
+            if p.unread == 0 {
+                p.slot = None;
+            }
+            let fetch = p.fetch.clone();
+            let start = self.ranges[i].start;
+            let slice = range.start - start..range.end - start;
+            async move {
+                let bytes = fetch
+                    .await
+                    .map_err(|e| ParquetError::External(Box::new(e)))?;
+                Ok(bytes.slice(slice))
+            }
+            .boxed()
+        });
+        Some(fetches.collect())
+    }
+
+    /// Drops the requests no reader of a row group still needs, once `row_group` is read.
+    fn release(&self, row_group: usize) {
+        let Some(requests) = self.requests.get(&row_group) else {
+            return;
+        };
+        let mut planned = self.planned.lock().unwrap();
+        for &i in requests {
+            if let Some(p) = planned[i].as_mut() {
+                p.readers -= 1;
+                if p.readers == 0 {
+                    planned[i] = None;
+                }
+            }
+        }
+    }
+}
+
+/// Serves the reads of `row_group` covered by the plan from its requests, and the rest,
+/// such as the footer and the page index, from `inner`. Dropping it releases the
+/// requests of the row group.
+pub struct PrefetchReader<R> {
+    inner: R,
+    prefetch: Prefetch,
+    row_group: usize,
+}
+
+impl<R> PrefetchReader<R> {
+    pub fn new(inner: R, prefetch: Prefetch, row_group: usize) -> Self {
+        Self {
+            inner,
+            prefetch,
+            row_group,
+        }
+    }
+}
+
+impl<R> Drop for PrefetchReader<R> {
+    fn drop(&mut self) {
+        self.prefetch.release(self.row_group);
+    }
+}
+
+impl<R: AsyncFileReader> AsyncFileReader for PrefetchReader<R> {
+    fn get_bytes(&mut self, range: Range<usize>) -> BoxFuture<'_, Result<Bytes>> {
+        match self.prefetch.get(std::slice::from_ref(&range)) {
+            Some(mut fetches) => fetches.pop().unwrap(),
+            None => self.inner.get_bytes(range),
+        }
+    }
+
+    fn get_byte_ranges(&mut self, ranges: Vec<Range<usize>>) -> BoxFuture<'_, Result<Vec<Bytes>>> {
+        match self.prefetch.get(&ranges) {
+            Some(fetches) => futures::future::try_join_all(fetches).boxed(),
+            None => self.inner.get_byte_ranges(ranges),
+        }
+    }
+
+    fn get_metadata(&mut self) -> BoxFuture<'_, Result<Arc<ParquetMetaData>>> {
+        self.inner.get_metadata()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plan(chunks: &[(usize, Range<usize>)], coalesce: usize) -> IoPlan {
+        IoPlan::from_chunks(chunks.to_vec(), coalesce)
+    }
+
+    #[test]
+    fn merges_across_small_gaps() {
+        let p = plan(&[(0, 0..10), (0, 10..20), (0, 25..30), (1, 100..110)], 5);
+        assert_eq!(p.requests, vec![0..30, 100..110]);
+        assert_eq!(p.row_groups, vec![vec![0], vec![1]]);
+        assert_eq!(p.chunks, 4);
+        assert_eq!(p.needed_bytes, 35);
+        assert_eq!(p.wasted_bytes, 5);
+    }
+
+    #[test]
+    fn without_coalescing_only_adjacent_chunks_merge() {
+        let p = plan(&[(0, 0..10), (0, 10..20), (0, 21..30)], 0);
+        assert_eq!(p.requests, vec![0..20, 21..30]);
+        assert_eq!(p.wasted_bytes, 0);
+    }
+
+    #[test]
+    fn sorts_chunks_and_tracks_row_groups() {
+        let p = plan(&[(1, 20..30), (0, 0..10), (1, 40..50), (0, 10..20)], 10);
+        assert_eq!(p.requests, vec![0..50]);
+        assert_eq!(p.row_groups, vec![vec![0, 1]]);
+        assert_eq!(p.wasted_bytes, 10);
+    }
+
+    #[test]
+    fn overlapping_chunks() {
+        let p = plan(&[(0, 0..100), (0, 10..20), (0, 50..60)], 0);
+        assert_eq!(p.requests, vec![0..100]);
+        assert_eq!(p.needed_bytes, 120);
+        assert_eq!(p.wasted_bytes, 0);
+    }
+
+    /// Serves zeros, recording the ranges requested.
+    #[derive(Clone, Default)]
+    struct Zeros(Arc<Mutex<Vec<Range<usize>>>>);
+
+    impl AsyncFileReader for Zeros {
+        fn get_bytes(&mut self, range: Range<usize>) -> BoxFuture<'_, Result<Bytes>> {
+            self.0.lock().unwrap().push(range.clone());
+            futures::future::ready(Ok(Bytes::from(vec![0; range.len()]))).boxed()
+        }
+
+        fn get_metadata(&mut self) -> BoxFuture<'_, Result<Arc<ParquetMetaData>>> {
+            unimplemented!()
+        }
+    }
+
+    #[test]
+    fn request_bytes_count_overlaps_once() {
+        let p = plan(&[(0, 0..10), (0, 5..20), (0, 25..30), (1, 100..110)], 5);
+        assert_eq!(p.requests, vec![0..30, 100..110]);
+        assert_eq!(p.request_bytes, vec![25, 10]);
+    }
+
+    /// A [`Prefetch`] of `p` over `zeros`, and the number of times it opened the file.
+    fn prefetch(p: &IoPlan, zeros: &Zeros) -> (Prefetch, Arc<Mutex<usize>>) {
+        let opened = Arc::new(Mutex::new(0));
+        let prefetch = {
+            let (zeros, opened) = (zeros.clone(), opened.clone());
+            Prefetch::new(p, move || {
+                *opened.lock().unwrap() += 1;
+                futures::future::ready(Ok(zeros.clone()))
+            })
+        };
+        (prefetch, opened)
+    }
+
+    async fn settle() {
+        for _ in 0..10 {
+            tokio::task::yield_now().await;
+        }
+    }
+
+    fn runtime() -> tokio::runtime::Runtime {
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn drive_is_bounded_by_consumption() {
+        let p = plan(&[(0, 0..10), (1, 20..30), (2, 40..50), (3, 60..70)], 0);
+        let zeros = Zeros::default();
+        let (prefetch, opened) = prefetch(&p, &zeros);
+        let fetched = || zeros.0.lock().unwrap().len();
+
+        runtime().block_on(async {
+            let driver = tokio::spawn(prefetch.clone().drive(2));
+            settle().await;
+            assert_eq!(fetched(), 2);
+
+            // Part of a request keeps its slot, the rest of it gives it back.
+            let mut reader = PrefetchReader::new(zeros.clone(), prefetch.clone(), 0);
+            assert_eq!(reader.get_bytes(2..4).await.unwrap().len(), 2);
+            settle().await;
+            assert_eq!(fetched(), 2);
+            let rest = reader.get_byte_ranges(vec![0..2, 4..10]).await.unwrap();
+            assert_eq!(rest.iter().map(Bytes::len).collect::<Vec<_>>(), [2, 6]);
+            settle().await;
+            assert_eq!(fetched(), 3);
+
+            assert!(prefetch.planned.lock().unwrap()[0].is_some());
+            drop(reader);
+            assert!(prefetch.planned.lock().unwrap()[0].is_none());
+
+            // A released request is no longer served from the plan.
+            let mut reader = PrefetchReader::new(zeros.clone(), prefetch.clone(), 0);
+            reader.get_bytes(0..10).await.unwrap();
+            assert_eq!(zeros.0.lock().unwrap().last(), Some(&(0..10)));
+            assert_eq!(fetched(), 4);
+            driver.abort();
+        });
+        // Every planned request went through one reader.
+        assert_eq!(*opened.lock().unwrap(), 1);
+    }
+
+    #[test]
+    fn ranges_partly_outside_the_plan_are_read_directly() {
+        let p = plan(&[(0, 0..10), (0, 20..30), (1, 40..50)], 0);
+        let zeros = Zeros::default();
+        let (prefetch, _) = prefetch(&p, &zeros);
+        let fetched = || zeros.0.lock().unwrap().clone();
+
+        runtime().block_on(async {
+            let driver = tokio::spawn(prefetch.clone().drive(1));
+            settle().await;
+            assert_eq!(fetched().len(), 1);
+
+            // 5..15 runs past its request, so neither range is taken from the plan.
+            let mut reader = PrefetchReader::new(zeros.clone(), prefetch.clone(), 0);
+            let bytes = reader.get_byte_ranges(vec![0..10, 5..15]).await.unwrap();
+            assert_eq!(bytes.iter().map(Bytes::len).collect::<Vec<_>>(), [10, 10]);
+            settle().await;
+            assert_eq!(fetched(), [0..10, 0..10, 5..15]);
+            {
+                let planned = prefetch.planned.lock().unwrap();
+                let p = planned[0].as_ref().unwrap();
+                assert!(!p.taken);
+                assert_eq!(p.unread, 10);
+                assert!(p.slot.is_some());
+            }
+
+            // Reading request 0 in full lets the driver start request 1.
+            reader.get_bytes(0..10).await.unwrap();
+            settle().await;
+            assert_eq!(fetched(), [0..10, 0..10, 5..15, 20..30]);
+            driver.abort();
+        });
+    }
+}
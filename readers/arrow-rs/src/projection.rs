@@ -0,0 +1,128 @@
+use parquet::errors::{ParquetError, Result};
+use parquet::schema::types::SchemaDescriptor;
+
+/// Resolves a comma separated list of columns into sorted leaf indices.
+///
+/// Each entry is either a leaf index (e.g. `3`) or a dotted column path (e.g. `l_comment`
+/// or `a.b.c`). A path selects every leaf at or below it, so naming a struct or list
+/// column selects all of its children.
+pub fn parse_projection(spec: &str, schema: &SchemaDescriptor) -> Result<Vec<usize>> {
+    let mut leaves = vec![];
+
+    for entry in spec.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+        match entry.parse::<usize>() {
+            Ok(idx) if idx < schema.num_columns() => leaves.push(idx),
+            Ok(idx) => {
+                return Err(ParquetError::General(format!(
+                    "leaf index {} out of range, file has {} leaf columns",
+                    idx,
+                    schema.num_columns()
+                )))
+            }
+            Err(_) => leaves.extend(leaves_for_path(schema, entry)?),
+        }
+    }
+
+    if leaves.is_empty() {
+        return Err(ParquetError::General("no columns selected".to_string()));
+    }
+
+    leaves.sort_unstable();
+    leaves.dedup();
+    Ok(leaves)
+}
+
+/// Returns the leaf indices at or below the dotted `path`.
+pub fn leaves_for_path(schema: &SchemaDescriptor, path: &str) -> Result<Vec<usize>> {
+    let leaves: Vec<usize> = schema
+        .columns()
+        .iter()
+        .enumerate()
+        .filter(|(_, c)| path_matches(&c.path().string(), path))
+        .map(|(i, _)| i)
+        .collect();
+
+    if leaves.is_empty() {
+        return Err(ParquetError::General(format!("column {} not found", path)));
+    }
+    Ok(leaves)
+}
+
+fn path_matches(path: &str, entry: &str) -> bool {
+    path == entry
+        || path
+            .strip_prefix(entry)
+            .is_some_and(|rest| rest.starts_with('.'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parquet::schema::parser::parse_message_type;
+    use std::sync::Arc;
+
+    /// Leaves: 0 `id`, 1 `a.b.x`, 2 `a.b.y`, 3 `a.bc`, 4 `l.list.element`, 5 `ab`.
+    fn schema() -> SchemaDescriptor {
+        let message = "
+            message m {
+                required int64 id;
+                required group a {
+                    required group b {
+                        required int32 x;
+                        required int32 y;
+                    }
+                    required int32 bc;
+                }
+                optional group l (LIST) {
+                    repeated group list {
+                        optional int32 element;
+                    }
+                }
+                required int32 ab;
+            }";
+        SchemaDescriptor::new(Arc::new(parse_message_type(message).unwrap()))
+    }
+
+    #[test]
+    fn paths_select_the_leaves_below_them() {
+        let schema = schema();
+        let leaves = |path| leaves_for_path(&schema, path).unwrap();
+        assert_eq!(leaves("id"), [0]);
+        assert_eq!(leaves("a"), [1, 2, 3]);
+        assert_eq!(leaves("a.b"), [1, 2]);
+        assert_eq!(leaves("a.bc"), [3]);
+        assert_eq!(leaves("a.b.y"), [2]);
+        assert_eq!(leaves("l"), [4]);
+        assert_eq!(leaves("l.list.element"), [4]);
+        assert_eq!(leaves("ab"), [5]);
+    }
+
+    #[test]
+    fn unknown_paths_are_errors() {
+        let schema = schema();
+        for path in ["nope", "a.b.z", "a.b.", "a.", "b", "a.b.x.y"] {
+            let error = leaves_for_path(&schema, path).unwrap_err();
+            assert_eq!(
+                error.to_string(),
+                format!("Parquet error: column {} not found", path)
+            );
+        }
+    }
+
+    #[test]
+    fn projections_mix_paths_and_indices() {
+        let schema = schema();
+        let projection = |spec| parse_projection(spec, &schema);
+        assert_eq!(projection("a.b, 0, 1").unwrap(), [0, 1, 2]);
+        assert_eq!(projection("ab,a.bc").unwrap(), [3, 5]);
+        assert_eq!(projection("l,").unwrap(), [4]);
+
+        let error = |spec| projection(spec).unwrap_err().to_string();
+        assert_eq!(
+            error("6"),
+            "Parquet error: leaf index 6 out of range, file has 6 leaf columns"
+        );
+        assert_eq!(error(" , "), "Parquet error: no columns selected");
+        assert_eq!(error("id, a.c"), "Parquet error: column a.c not found");
+    }
+}
@@ -0,0 +1,859 @@
+//! Row group and page pruning from min/max statistics and the page index.
+
+use std::cmp::Ordering;
+use std::collections::{BTreeSet, HashMap};
+
+use arrow::array::{AsArray, Datum};
+use arrow::compute::cast;
+use arrow::datatypes::{DataType, Int64Type, Schema, TimeUnit};
+use parquet::arrow::arrow_reader::{RowSelection, RowSelector};
+use parquet::errors::Result;
+use parquet::file::metadata::ParquetMetaData;
+use parquet::file::page_index::index::{Index, PageIndex};
+use parquet::file::page_index::offset_index::OffsetIndexMetaData;
+use parquet::file::statistics::Statistics;
+
+use crate::predicate::{CmpOp, Literal, Predicate};
+use crate::projection::leaves_for_path;
+
+/// Row groups and rows that survive pruning, plus what was skipped to get there.
+#[derive(Debug)]
+pub struct Pruned {
+    pub row_groups: Vec<usize>,
+    /// `None` when the file has no page index.
+    pub selection: Option<RowSelection>,
+    pub row_groups_total: usize,
+    pub pages_total: usize,
+    pub pages_skipped: usize,
+}
+
+/// Prunes row groups with column chunk statistics, then pages within the remaining row
+/// groups with the column index. `leaves` are the columns that will be decoded, pages
+/// skipped are counted over them, including those of pruned row groups.
+pub fn prune(
+    predicate: &Predicate,
+    metadata: &ParquetMetaData,
+    schema: &Schema,
+    leaves: &[usize],
+) -> Result<Pruned> {
+    let pruner = Pruner::try_new(predicate, metadata, schema)?;
+    let page_index = metadata.column_index().zip(metadata.offset_index());
+
+    let mut pruned = Pruned {
+        row_groups: vec![],
+        selection: None,
+        row_groups_total: metadata.num_row_groups(),
+        pages_total: 0,
+        pages_skipped: 0,
+    };
+    let mut selectors = vec![];
+
+    for (rg_idx, rg) in metadata.row_groups().iter().enumerate() {
+        let chunk_range = |leaf: usize| {
+            let column = rg.column(leaf);
+            column.statistics().map(|s| {
+                let (min, max) = statistics_bounds(s);
+                let null_count = s.null_count_opt();
+                let all_null = null_count == Some(column.num_values() as u64);
+                ColumnRange::new(min, max, null_count, all_null)
+            })
+        };
+        let Some((column_index, offset_index)) = page_index else {
+            if pruner.may_match(predicate, &chunk_range) {
+                pruned.row_groups.push(rg_idx);
+            }
+            continue;
+        };
+        let num_rows = rg.num_rows() as usize;
+        let first_rows = |leaf: usize| page_first_rows(offset_index, rg_idx, leaf);
+
+        if !pruner.may_match(predicate, &chunk_range) {
+            let pages: usize = leaves.iter().map(|&leaf| first_rows(leaf).len()).sum();
+            pruned.pages_total += pages;
+            pruned.pages_skipped += pages;
+            continue;
+        }
+        pruned.row_groups.push(rg_idx);
+
+        // Split the row group into intervals at every page boundary of the predicate
+        // columns, so that each interval sees exactly one page per column.
+        let mut boundaries = BTreeSet::from([0, num_rows]);
+        for &leaf in pruner.columns.values().map(|(leaf, _)| leaf) {
+            boundaries.extend(first_rows(leaf));
+        }
+        let boundaries: Vec<usize> = boundaries.into_iter().collect();
+
+        let mut rg_selectors: Vec<RowSelector> = vec![];
+        for window in boundaries.windows(2) {
+            let (start, len) = (window[0], window[1] - window[0]);
+            let page_range = |leaf: usize| {
+                let rows = first_rows(leaf);
+                let page = rows.partition_point(|&r| r <= start).checked_sub(1)?;
+                page_bounds(&column_index[rg_idx][leaf], page)
+            };
+            let selector = match pruner.may_match(predicate, &page_range) {
+                true => RowSelector::select(len),
+                false => RowSelector::skip(len),
+            };
+            match rg_selectors.last_mut() {
+                Some(last) if last.skip == selector.skip => last.row_count += len,
+                _ => rg_selectors.push(selector),
+            }
+        }
+
+        for &leaf in leaves {
+            let rows = first_rows(leaf);
+            pruned.pages_total += rows.len();
+            pruned.pages_skipped += rows
+                .iter()
+                .zip(rows.iter().skip(1).chain(std::iter::once(&num_rows)))
+                .filter(|(&start, &end)| is_skipped(&rg_selectors, start, end))
+                .count();
+        }
+        selectors.extend(rg_selectors);
+    }
+
+    if page_index.is_some() {
+        pruned.selection = Some(RowSelection::from(selectors));
+    }
+    Ok(pruned)
+}
+
+/// First row index of every page of a column chunk.
+fn page_first_rows(
+    offset_index: &[Vec<OffsetIndexMetaData>],
+    row_group: usize,
+    leaf: usize,
+) -> Vec<usize> {
+    offset_index[row_group][leaf]
+        .page_locations()
+        .iter()
+        .map(|p| p.first_row_index as usize)
+        .collect()
+}
+
+/// Whether the rows `start..end` fall entirely into skipped selectors.
+fn is_skipped(selectors: &[RowSelector], start: usize, end: usize) -> bool {
+    let mut offset = 0;
+    for s in selectors {
+        let s_end = offset + s.row_count;
+        if s_end > start && offset < end && !s.skip {
+            return false;
+        }
+        offset = s_end;
+    }
+    true
+}
+
+/// A statistics value, widened so that literals and parquet min/max compare directly.
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    Bytes(Vec<u8>),
+}
+
+impl Value {
+    fn compare(&self, other: &Value) -> Option<Ordering> {
+        match (self, other) {
+            (Value::Bool(a), Value::Bool(b)) => a.partial_cmp(b),
+            (Value::Int(a), Value::Int(b)) => a.partial_cmp(b),
+            (Value::Float(a), Value::Float(b)) => a.partial_cmp(b),
+            (Value::Bytes(a), Value::Bytes(b)) => a.partial_cmp(b),
+            _ => None,
+        }
+    }
+}
+
+/// Min, max and nulls of a column over some rows, either a column chunk or a page.
+#[derive(Debug)]
+struct ColumnRange {
+    min: Option<Value>,
+    max: Option<Value>,
+    null_count: Option<u64>,
+    all_null: bool,
+}
+
+impl ColumnRange {
+    fn new(
+        min: Option<Value>,
+        max: Option<Value>,
+        null_count: Option<u64>,
+        all_null: bool,
+    ) -> Self {
+        Self {
+            min,
+            max,
+            null_count,
+            all_null,
+        }
+    }
+}
+
+struct Pruner<'a> {
+    /// Predicate column to its leaf and the Arrow type literals are cast to.
+    columns: HashMap<&'a str, (usize, DataType)>,
+}
+
+impl<'a> Pruner<'a> {
+    fn try_new(
+        predicate: &'a Predicate,
+        metadata: &ParquetMetaData,
+        schema: &Schema,
+    ) -> Result<Self> {
+        let descr = metadata.file_metadata().schema_descr();
+        let mut columns = HashMap::new();
+        for column in predicate.columns() {
+            // Only columns backed by a single leaf with a comparable type can be pruned on,
+            // the rest of the predicate is treated as "may match".
+            let leaves = leaves_for_path(descr, column)?;
+            if let ([leaf], Some(data_type)) = (leaves.as_slice(), field_type(schema, column)) {
+                columns.insert(column, (*leaf, data_type));
+            }
+        }
+        Ok(Self { columns })
+    }
+
+    /// Returns false only if no row described by `range` can satisfy `predicate`.
+    fn may_match(
+        &self,
+        predicate: &Predicate,
+        range: &dyn Fn(usize) -> Option<ColumnRange>,
+    ) -> bool {
+        match predicate {
+            Predicate::And(l, r) => self.may_match(l, range) && self.may_match(r, range),
+            Predicate::Or(l, r) => self.may_match(l, range) || self.may_match(r, range),
+            Predicate::Compare { column, op, value } => self
+                .bounds(column, value, range)
+                .is_none_or(|(r, v)| compare_may_match(&r, *op, &v)),
+            Predicate::In {
+                column,
+                values,
+                negated: false,
+            } => values.iter().any(|value| {
+                self.bounds(column, value, range)
+                    .is_none_or(|(r, v)| compare_may_match(&r, CmpOp::Eq, &v))
+            }),
+            Predicate::In { negated: true, .. } => true,
+            Predicate::IsNull { column, negated } => {
+                let Some(r) = self
+                    .columns
+                    .get(column.as_str())
+                    .and_then(|(leaf, _)| range(*leaf))
+                else {
+                    return true;
+                };
+                match negated {
+                    false => r.null_count != Some(0),
+                    true => !r.all_null,
+                }
+            }
+        }
+    }
+
+    fn bounds(
+        &self,
+        column: &str,
+        literal: &Literal,
+        range: &dyn Fn(usize) -> Option<ColumnRange>,
+    ) -> Option<(ColumnRange, Value)> {
+        let (leaf, data_type) = self.columns.get(column)?;
+        let value = literal_value(literal, data_type)?;
+        Some((range(*leaf)?, value))
+    }
+}
+
+fn compare_may_match(range: &ColumnRange, op: CmpOp, value: &Value) -> bool {
+    if range.all_null {
+        // comparisons against null are never true
+        return false;
+    }
+    let (Some(min), Some(max)) = (&range.min, &range.max) else {
+        return true;
+    };
+    let (Some(lo), Some(hi)) = (min.compare(value), max.compare(value)) else {
+        return true;
+    };
+    match op {
+        CmpOp::Eq => lo.is_le() && hi.is_ge(),
+        CmpOp::NotEq => !(lo.is_eq() && hi.is_eq()),
+        CmpOp::Lt => lo.is_lt(),
+        CmpOp::LtEq => lo.is_le(),
+        CmpOp::Gt => hi.is_gt(),
+        CmpOp::GtEq => hi.is_ge(),
+    }
+}
+
+/// The Arrow type of a (possibly dotted) column path, if its statistics can be compared
+/// with [`Value`]s. Dictionaries are unwrapped, their statistics are of the values.
+pub fn field_type(schema: &Schema, path: &str) -> Option<DataType> {
+    let mut parts = path.split('.');
+    let mut data_type = schema.field_with_name(parts.next()?).ok()?.data_type();
+    for part in parts {
+        data_type = match data_type {
+            DataType::Struct(fields) => fields.iter().find(|f| f.name() == part)?.data_type(),
+            _ => return None,
+        };
+    }
+    if let DataType::Dictionary(_, value) = data_type {
+        data_type = value.as_ref();
+    }
+
+    // Unsigned integers, decimals and second-resolution timestamps are stored in a way
+    // that does not match their Arrow value, skip them rather than risk wrong answers.
+    match data_type {
+        DataType::Boolean
+        | DataType::Int8
+        | DataType::Int16
+        | DataType::Int32
+        | DataType::Int64
+        | DataType::Date32
+        | DataType::Float32
+        | DataType::Float64
+        | DataType::Utf8
+        | DataType::LargeUtf8
+        | DataType::Binary
+        | DataType::LargeBinary => Some(data_type.clone()),
+        DataType::Timestamp(unit, _) if *unit != TimeUnit::Second => Some(data_type.clone()),
+        _ => None,
+    }
+}
+
+fn literal_value(literal: &Literal, data_type: &DataType) -> Option<Value> {
+    let scalar = literal.to_scalar(data_type).ok()?;
+    let (array, _) = scalar.get();
+    match data_type {
+        DataType::Boolean => Some(Value::Bool(array.as_boolean().value(0))),
+        DataType::Float32 | DataType::Float64 => {
+            let array = cast(array, &DataType::Float64).ok()?;
+            Some(Value::Float(
+                array
+                    .as_primitive::<arrow::datatypes::Float64Type>()
+                    .value(0),
+            ))
+        }
+        DataType::Utf8 => Some(Value::Bytes(array.as_string::<i32>().value(0).into())),
+        DataType::LargeUtf8 => Some(Value::Bytes(array.as_string::<i64>().value(0).into())),
+        DataType::Binary => Some(Value::Bytes(array.as_binary::<i32>().value(0).into())),
+        DataType::LargeBinary => Some(Value::Bytes(array.as_binary::<i64>().value(0).into())),
+        _ => {
+            let array = cast(array, &DataType::Int64).ok()?;
+            Some(Value::Int(array.as_primitive::<Int64Type>().value(0)))
+        }
+    }
+}
+
+fn statistics_bounds(stats: &Statistics) -> (Option<Value>, Option<Value>) {
+    match stats {
+        Statistics::Boolean(s) => (
+            s.min_opt().map(|v| Value::Bool(*v)),
+            s.max_opt().map(|v| Value::Bool(*v)),
+        ),
+        Statistics::Int32(s) => (
+            s.min_opt().map(|v| Value::Int(*v as i64)),
+            s.max_opt().map(|v| Value::Int(*v as i64)),
+        ),
+        Statistics::Int64(s) => (
+            s.min_opt().map(|v| Value::Int(*v)),
+            s.max_opt().map(|v| Value::Int(*v)),
+        ),
+        Statistics::Float(s) => (
+            s.min_opt().map(|v| Value::Float(*v as f64)),
+            s.max_opt().map(|v| Value::Float(*v as f64)),
+        ),
+        Statistics::Double(s) => (
+            s.min_opt().map(|v| Value::Float(*v)),
+            s.max_opt().map(|v| Value::Float(*v)),
+        ),
+        Statistics::ByteArray(s) => (
+            s.min_opt().map(|v| Value::Bytes(v.data().to_vec())),
+            s.max_opt().map(|v| Value::Bytes(v.data().to_vec())),
+        ),
+        _ => (None, None),
+    }
+}
+
+fn page_bounds(index: &Index, page: usize) -> Option<ColumnRange> {
+    fn range<T>(p: &PageIndex<T>, f: impl Fn(&T) -> Value) -> ColumnRange {
+        let all_null = p.min.is_none() && p.max.is_none() && p.null_count.unwrap_or(0) > 0;
+        let null_count = p.null_count.map(|n| n as u64);
+        ColumnRange::new(
+            p.min.as_ref().map(&f),
+            p.max.as_ref().map(&f),
+            null_count,
+            all_null,
+        )
+    }
+    let range = match index {
+        Index::BOOLEAN(i) => range(i.indexes.get(page)?, |v| Value::Bool(*v)),
+        Index::INT32(i) => range(i.indexes.get(page)?, |v| Value::Int(*v as i64)),
+        Index::INT64(i) => range(i.indexes.get(page)?, |v| Value::Int(*v)),
+        Index::FLOAT(i) => range(i.indexes.get(page)?, |v| Value::Float(*v as f64)),
+        Index::DOUBLE(i) => range(i.indexes.get(page)?, |v| Value::Float(*v)),
+        Index::BYTE_ARRAY(i) => range(i.indexes.get(page)?, |v| Value::Bytes(v.data().to_vec())),
+        _ => return None,
+    };
+    Some(range)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    use arrow::array::{
+        ArrayRef, Decimal128Array, Int64Array, RecordBatch, StringArray, TimestampSecondArray,
+        UInt32Array,
+    };
+    use arrow::datatypes::{Field, Int32Type};
+    use bytes::Bytes;
+    use parquet::arrow::ArrowWriter;
+    use parquet::data_type::ByteArray;
+    use parquet::file::metadata::ParquetMetaDataReader;
+    use parquet::file::page_index::index::NativeIndex;
+    use parquet::file::properties::WriterProperties;
+    use parquet::format::BoundaryOrder;
+
+    fn write(batch: &RecordBatch, props: WriterProperties) -> ParquetMetaData {
+        let mut buf = vec![];
+        let mut writer = ArrowWriter::try_new(&mut buf, batch.schema(), Some(props)).unwrap();
+        writer.write(batch).unwrap();
+        writer.close().unwrap();
+        ParquetMetaDataReader::new()
+            .with_page_indexes(true)
+            .parse_and_finish(&Bytes::from(buf))
+            .unwrap()
+    }
+
+    fn prune_str(predicate: &str, batch: &RecordBatch, props: WriterProperties) -> Pruned {
+        let metadata = write(batch, props);
+        let leaves: Vec<usize> = (0..batch.num_columns()).collect();
+        let predicate = Predicate::parse(predicate).unwrap();
+        prune(&predicate, &metadata, &batch.schema(), &leaves).unwrap()
+    }
+
+    fn int_range(min: i64, max: i64) -> ColumnRange {
+        ColumnRange::new(Some(Value::Int(min)), Some(Value::Int(max)), Some(0), false)
+    }
+
+    #[test]
+    fn comparisons_at_the_edges() {
+        let range = int_range(10, 20);
+        let cases = [
+            (CmpOp::Eq, [(9, false), (10, true), (20, true), (21, false)]),
+            (CmpOp::Lt, [(9, false), (10, false), (11, true), (21, true)]),
+            (
+                CmpOp::LtEq,
+                [(9, false), (10, true), (11, true), (21, true)],
+            ),
+            (CmpOp::Gt, [(9, true), (19, true), (20, false), (21, false)]),
+            (
+                CmpOp::GtEq,
+                [(9, true), (19, true), (20, true), (21, false)],
+            ),
+            (
+                CmpOp::NotEq,
+                [(9, true), (10, true), (20, true), (21, true)],
+            ),
+        ];
+        for (op, values) in cases {
+            for (value, expected) in values {
+                assert_eq!(
+                    compare_may_match(&range, op, &Value::Int(value)),
+                    expected,
+                    "{:?} {}",
+                    op,
+                    value
+                );
+            }
+        }
+
+        // Only a range of a single value can rule out `!=`.
+        let single = int_range(5, 5);
+        assert!(!compare_may_match(&single, CmpOp::NotEq, &Value::Int(5)));
+        assert!(compare_may_match(&single, CmpOp::NotEq, &Value::Int(6)));
+    }
+
+    #[test]
+    fn null_only_ranges_match_no_comparison() {
+        let range = ColumnRange::new(None, None, Some(10), true);
+        for op in [
+            CmpOp::Eq,
+            CmpOp::NotEq,
+            CmpOp::Lt,
+            CmpOp::LtEq,
+            CmpOp::Gt,
+            CmpOp::GtEq,
+        ] {
+            assert!(!compare_may_match(&range, op, &Value::Int(0)));
+        }
+    }
+
+    #[test]
+    fn null_only_pages_are_skipped() {
+        // Pages of 100 rows, the second one all null.
+        let batch = RecordBatch::try_from_iter([(
+            "a",
+            Arc::new(Int64Array::from_iter(
+                (0..300).map(|i| (!(100..200).contains(&i)).then_some(i)),
+            )) as ArrayRef,
+        )])
+        .unwrap();
+        let props = || {
+            WriterProperties::builder()
+                .set_write_batch_size(100)
+                .set_data_page_row_count_limit(100)
+                .build()
+        };
+        let expected = Some(RowSelection::from(vec![
+            RowSelector::select(100),
+            RowSelector::skip(100),
+            RowSelector::select(100),
+        ]));
+        for predicate in ["a IS NOT NULL", "a >= 0"] {
+            let pruned = prune_str(predicate, &batch, props());
+            assert_eq!(pruned.selection, expected, "{}", predicate);
+            assert_eq!(pruned.pages_skipped, 1, "{}", predicate);
+        }
+        let pruned = prune_str("a IS NULL", &batch, props());
+        assert_eq!(
+            pruned.selection,
+            Some(RowSelection::from(vec![
+                RowSelector::skip(100),
+                RowSelector::select(100),
+                RowSelector::skip(100),
+            ]))
+        );
+    }
+
+    #[test]
+    fn missing_statistics_keep_rows() {
+        let no_bounds = ColumnRange::new(None, None, None, false);
+        assert!(compare_may_match(&no_bounds, CmpOp::Eq, &Value::Int(0)));
+        let no_max = ColumnRange::new(Some(Value::Int(10)), None, Some(0), false);
+        assert!(compare_may_match(&no_max, CmpOp::Lt, &Value::Int(0)));
+        // Values of different kinds do not compare.
+        let bytes = ColumnRange::new(
+            Some(Value::Bytes(b"a".to_vec())),
+            Some(Value::Bytes(b"b".to_vec())),
+            Some(0),
+            false,
+        );
+        assert!(compare_may_match(&bytes, CmpOp::Eq, &Value::Int(0)));
+    }
+
+    #[test]
+    fn pruner_combines_predicates() {
+        let batch = RecordBatch::try_from_iter([
+            (
+                "a",
+                Arc::new(Int64Array::from(vec![Some(10), None, Some(20)])) as ArrayRef,
+            ),
+            (
+                "s",
+                Arc::new(StringArray::from(vec!["b", "c", "d"])) as ArrayRef,
+            ),
+        ])
+        .unwrap();
+        let metadata = write(&batch, WriterProperties::default());
+        let may_match = |predicate: &str, range: &dyn Fn(usize) -> Option<ColumnRange>| {
+            let predicate = Predicate::parse(predicate).unwrap();
+            let pruner = Pruner::try_new(&predicate, &metadata, &batch.schema()).unwrap();
+            pruner.may_match(&predicate, range)
+        };
+        let range = |leaf: usize| match leaf {
+            0 => Some(ColumnRange::new(
+                Some(Value::Int(10)),
+                Some(Value::Int(20)),
+                Some(1),
+                false,
+            )),
+            _ => Some(ColumnRange::new(
+                Some(Value::Bytes(b"b".to_vec())),
+                Some(Value::Bytes(b"d".to_vec())),
+                Some(0),
+                false,
+            )),
+        };
+
+        assert!(may_match("a = 15 AND s = 'c'", &range));
+        assert!(!may_match("a = 15 AND s = 'e'", &range));
+        assert!(may_match("a = 30 OR s = 'b'", &range));
+        assert!(!may_match("a = 30 OR s = 'a'", &range));
+        assert!(may_match("a IN (1, 2, 15)", &range));
+        assert!(!may_match("a IN (1, 2, 30)", &range));
+        assert!(may_match("a NOT IN (10, 20)", &range));
+        assert!(may_match("a IS NULL", &range));
+        assert!(!may_match("s IS NULL", &range));
+        assert!(may_match("s IS NOT NULL", &range));
+        // A literal that does not cast to the column's type cannot prune.
+        assert!(may_match("a = 'x'", &range));
+
+        // Without statistics, everything may match.
+        assert!(may_match("a = 30 AND s IS NULL", &|_| None));
+
+        let all_null = |_| Some(ColumnRange::new(None, None, Some(3), true));
+        assert!(may_match("a IS NULL", &all_null));
+        assert!(!may_match("a IS NOT NULL", &all_null));
+        assert!(!may_match("a = 1", &all_null));
+    }
+
+    #[test]
+    fn field_type_excludes_types_stored_differently() {
+        let schema = Schema::new(vec![
+            Field::new("i", DataType::Int32, true),
+            Field::new("u", DataType::UInt32, true),
+            Field::new("d", DataType::Decimal128(10, 2), true),
+            Field::new("ts", DataType::Timestamp(TimeUnit::Second, None), true),
+            Field::new(
+                "tms",
+                DataType::Timestamp(TimeUnit::Millisecond, None),
+                true,
+            ),
+            Field::new_dictionary("dict", DataType::Int32, DataType::Utf8, true),
+            Field::new_list("l", Field::new_list_field(DataType::Int32, true), true),
+            Field::new_struct("st", vec![Field::new("x", DataType::Int64, true)], true),
+        ]);
+        assert_eq!(field_type(&schema, "i"), Some(DataType::Int32));
+        assert_eq!(field_type(&schema, "u"), None);
+        assert_eq!(field_type(&schema, "d"), None);
+        assert_eq!(field_type(&schema, "ts"), None);
+        assert_eq!(
+            field_type(&schema, "tms"),
+            Some(DataType::Timestamp(TimeUnit::Millisecond, None))
+        );
+        assert_eq!(field_type(&schema, "dict"), Some(DataType::Utf8));
+        assert_eq!(field_type(&schema, "l"), None);
+        assert_eq!(field_type(&schema, "st.x"), Some(DataType::Int64));
+        assert_eq!(field_type(&schema, "st.y"), None);
+        assert_eq!(field_type(&schema, "i.x"), None);
+        assert_eq!(field_type(&schema, "missing"), None);
+    }
+
+    #[test]
+    fn excluded_types_do_not_prune() {
+        let batch = RecordBatch::try_from_iter([
+            (
+                "u",
+                Arc::new(UInt32Array::from_iter_values(0..100)) as ArrayRef,
+            ),
+            (
+                "d",
+                Arc::new(
+                    Decimal128Array::from_iter_values(0..100)
+                        .with_precision_and_scale(10, 2)
+                        .unwrap(),
+                ) as ArrayRef,
+            ),
+            (
+                "ts",
+                Arc::new(TimestampSecondArray::from_iter_values(0..100)) as ArrayRef,
+            ),
+        ])
+        .unwrap();
+        for predicate in ["u = 1000", "d > 1000", "ts > 1000"] {
+            let pruned = prune_str(predicate, &batch, WriterProperties::default());
+            assert_eq!(pruned.row_groups, vec![0], "{}", predicate);
+            assert_eq!(pruned.pages_skipped, 0, "{}", predicate);
+            assert_eq!(
+                pruned.selection,
+                Some(RowSelection::from(vec![RowSelector::select(100)])),
+                "{}",
+                predicate
+            );
+        }
+    }
+
+    #[test]
+    fn statistics_bounds_widen_values() {
+        assert_eq!(
+            statistics_bounds(&Statistics::boolean(
+                Some(false),
+                Some(true),
+                None,
+                None,
+                false
+            )),
+            (Some(Value::Bool(false)), Some(Value::Bool(true)))
+        );
+        assert_eq!(
+            statistics_bounds(&Statistics::int32(Some(-1), Some(7), None, None, false)),
+            (Some(Value::Int(-1)), Some(Value::Int(7)))
+        );
+        assert_eq!(
+            statistics_bounds(&Statistics::int64(Some(i64::MIN), None, None, None, false)),
+            (Some(Value::Int(i64::MIN)), None)
+        );
+        assert_eq!(
+            statistics_bounds(&Statistics::float(Some(0.5), Some(1.5), None, None, false)),
+            (Some(Value::Float(0.5)), Some(Value::Float(1.5)))
+        );
+        assert_eq!(
+            statistics_bounds(&Statistics::double(
+                Some(-2.0),
+                Some(2.0),
+                None,
+                None,
+                false
+            )),
+            (Some(Value::Float(-2.0)), Some(Value::Float(2.0)))
+        );
+        assert_eq!(
+            statistics_bounds(&Statistics::byte_array(
+                Some(ByteArray::from("a")),
+                Some(ByteArray::from("z")),
+                None,
+                None,
+                false
+            )),
+            (
+                Some(Value::Bytes(b"a".to_vec())),
+                Some(Value::Bytes(b"z".to_vec()))
+            )
+        );
+        assert_eq!(
+            statistics_bounds(&Statistics::int96(None, None, None, None, false)),
+            (None, None)
+        );
+    }
+
+    #[test]
+    fn page_bounds_of_the_column_index() {
+        let page = |min: Option<i64>, max: Option<i64>, null_count: Option<i64>| PageIndex {
+            min,
+            max,
+            null_count,
+            repetition_level_histogram: None,
+            definition_level_histogram: None,
+        };
+        let index = Index::INT64(NativeIndex {
+            indexes: vec![
+                page(Some(1), Some(9), Some(0)),
+                page(None, None, Some(5)),
+                page(None, None, None),
+            ],
+            boundary_order: BoundaryOrder::UNORDERED,
+        });
+
+        let range = page_bounds(&index, 0).unwrap();
+        assert_eq!(range.min, Some(Value::Int(1)));
+        assert_eq!(range.max, Some(Value::Int(9)));
+        assert_eq!(range.null_count, Some(0));
+        assert!(!range.all_null);
+
+        let range = page_bounds(&index, 1).unwrap();
+        assert_eq!((range.min, range.max), (None, None));
+        assert_eq!(range.null_count, Some(5));
+        assert!(range.all_null);
+
+        // No bounds and no null count is missing statistics, not a page of nulls.
+        let range = page_bounds(&index, 2).unwrap();
+        assert_eq!(range.null_count, None);
+        assert!(!range.all_null);
+
+        assert!(page_bounds(&index, 3).is_none());
+        assert!(page_bounds(&Index::NONE, 0).is_none());
+    }
+
+    #[test]
+    fn selection_follows_page_intervals_of_every_predicate_column() {
+        // `a` has pages of 100 rows, `s` of 50 rows, as its values are large.
+        let batch = RecordBatch::try_from_iter([
+            (
+                "a",
+                Arc::new(Int64Array::from_iter_values(0..1000)) as ArrayRef,
+            ),
+            (
+                "s",
+                Arc::new(StringArray::from_iter_values(
+                    (0..1000).map(|i| format!("{:04}{}", i, "x".repeat(996))),
+                )) as ArrayRef,
+            ),
+        ])
+        .unwrap();
+        let props = || {
+            WriterProperties::builder()
+                .set_dictionary_enabled(false)
+                .set_write_batch_size(50)
+                .set_data_page_row_count_limit(100)
+                .set_data_page_size_limit(20_000)
+                .build()
+        };
+
+        let pruned = prune_str("a < 130 AND s >= '0075'", &batch, props());
+        assert_eq!(pruned.row_groups, vec![0]);
+        assert_eq!(
+            pruned.selection,
+            Some(RowSelection::from(vec![
+                RowSelector::skip(50),
+                RowSelector::select(150),
+                RowSelector::skip(800),
+            ]))
+        );
+        assert_eq!(pruned.pages_total, 30);
+        assert_eq!(pruned.pages_skipped, 25);
+
+        // A row group ruled out by its statistics has all its pages skipped, and no
+        // selector.
+        let pruned = prune_str("a > 2000", &batch, props());
+        assert!(pruned.row_groups.is_empty());
+        assert_eq!(pruned.selection, Some(RowSelection::from(vec![])));
+        assert_eq!((pruned.pages_total, pruned.pages_skipped), (30, 30));
+    }
+
+    #[test]
+    fn files_without_a_page_index_prune_row_groups_only() {
+        let batch = RecordBatch::try_from_iter([(
+            "a",
+            Arc::new(Int64Array::from_iter_values(0..300)) as ArrayRef,
+        )])
+        .unwrap();
+        let props = WriterProperties::builder()
+            .set_max_row_group_size(100)
+            .build();
+        let mut buf = vec![];
+        let mut writer = ArrowWriter::try_new(&mut buf, batch.schema(), Some(props)).unwrap();
+        writer.write(&batch).unwrap();
+        writer.close().unwrap();
+        let metadata = ParquetMetaDataReader::new()
+            .parse_and_finish(&Bytes::from(buf))
+            .unwrap();
+
+        let predicate = Predicate::parse("a >= 150").unwrap();
+        let pruned = prune(&predicate, &metadata, &batch.schema(), &[0]).unwrap();
+        assert_eq!(pruned.row_groups, vec![1, 2]);
+        assert_eq!(pruned.selection, None);
+        assert_eq!((pruned.pages_total, pruned.pages_skipped), (0, 0));
+    }
+
+    #[test]
+    fn is_skipped_needs_every_row_skipped() {
+        let selectors = [
+            RowSelector::skip(10),
+            RowSelector::select(5),
+            RowSelector::skip(10),
+        ];
+        assert!(is_skipped(&selectors, 0, 10));
+        assert!(!is_skipped(&selectors, 5, 11));
+        assert!(!is_skipped(&selectors, 14, 20));
+        assert!(is_skipped(&selectors, 15, 25));
+    }
+
+    #[test]
+    fn dictionary_columns_prune_on_their_values() {
+        let batch = RecordBatch::try_from_iter([(
+            "dict",
+            Arc::new(
+                vec!["a", "b", "c"]
+                    .into_iter()
+                    .collect::<arrow::array::DictionaryArray<Int32Type>>(),
+            ) as ArrayRef,
+        )])
+        .unwrap();
+        let pruned = prune_str("dict = 'z'", &batch, WriterProperties::default());
+        assert!(pruned.row_groups.is_empty());
+        let pruned = prune_str("dict = 'b'", &batch, WriterProperties::default());
+        assert_eq!(pruned.row_groups, vec![0]);
+    }
+}
@@ -0,0 +1,667 @@
+use std::error::Error;
+use std::ops::Range;
+use std::sync::{Arc, Mutex};
+
+use arrow::array::RecordBatch;
+use arrow::compute::filter_record_batch;
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use arrow::error::ArrowError;
+use parquet::arrow::arrow_reader::{
+    ArrowReaderBuilder, ArrowReaderMetadata, ArrowReaderOptions, RowSelection, RowSelector,
+};
+use parquet::arrow::async_reader::AsyncFileReader;
+use parquet::arrow::ProjectionMask;
+use parquet::errors::Result;
+use parquet::file::metadata::ParquetMetaData;
+use parquet::file::reader::ChunkReader;
+use parquet::schema::types::SchemaDescriptor;
+
+use crate::bloom::{self, BloomProbe};
+use crate::pages::PageReads;
+use crate::predicate::{self, FilterCounters, Predicate};
+use crate::projection;
+use crate::pruning::{self, Pruned};
+
+/// Scan options from the command line.
+#[derive(Debug, Clone, Default)]
+pub struct ScanOptions {
+    pub columns: Option<String>,
+    pub predicate: Option<String>,
+    pub pushdown: bool,
+    pub prune: bool,
+    /// Skip row groups whose bloom filters rule out the `=` and `IN` terms of the predicate.
+    pub bloom_filter: bool,
+    pub batch_size: Option<usize>,
+    pub strings: StringType,
+    /// Report row groups that fail to decode and read on, instead of stopping.
+    pub keep_going: bool,
+    /// Only read the rows in this range of the file.
+    pub rows: Option<Range<usize>>,
+    /// Rows to skip, and at most how many to return after them, counted from the start
+    /// of `rows` without a predicate and in matching rows with one.
+    pub offset: usize,
+    pub limit: Option<usize>,
+    /// The path and schema of the first file of a dataset, which every file must match
+    /// before it is decoded.
+    pub schema: Option<(String, SchemaRef)>,
+    /// Pass batches on in file order, even when row groups are decoded concurrently.
+    pub ordered: bool,
+}
+
+impl ScanOptions {
+    /// Whether the plan uses the offset index, to skip pages or to select rows.
+    pub fn needs_page_index(&self) -> bool {
+        self.prune || self.rows.is_some() || self.offset > 0 || self.limit.is_some()
+    }
+}
+
+/// Arrow type to decode string and binary columns into.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum StringType {
+    /// Whatever the reader picks, Utf8/Binary unless the file embeds an Arrow schema.
+    #[default]
+    Plain,
+    /// `Dictionary(Int32, _)`, keeping dictionary encoded pages as dictionaries.
+    Dictionary,
+    /// Utf8View/BinaryView.
+    View,
+}
+
+impl StringType {
+    fn convert(self, data_type: &DataType) -> DataType {
+        match (self, data_type) {
+            (_, DataType::Struct(fields)) => {
+                DataType::Struct(fields.iter().map(|f| self.convert_field(f)).collect())
+            }
+            (_, DataType::List(field)) => DataType::List(self.convert_field(field).into()),
+            (_, DataType::LargeList(field)) => {
+                DataType::LargeList(self.convert_field(field).into())
+            }
+            (_, DataType::Map(entries, sorted)) => {
+                DataType::Map(self.convert_field(entries).into(), *sorted)
+            }
+            (
+                StringType::Dictionary,
+                DataType::Utf8 | DataType::LargeUtf8 | DataType::Binary | DataType::LargeBinary,
+            ) => DataType::Dictionary(Box::new(DataType::Int32), Box::new(data_type.clone())),
+            (StringType::View, DataType::Dictionary(_, value)) => match self.convert(value) {
+                view @ (DataType::Utf8View | DataType::BinaryView) => view,
+                _ => data_type.clone(),
+            },
+            (StringType::View, DataType::Utf8 | DataType::LargeUtf8) => DataType::Utf8View,
+            (StringType::View, DataType::Binary | DataType::LargeBinary) => DataType::BinaryView,
+            _ => data_type.clone(),
+        }
+    }
+
+    fn convert_field(self, field: &Field) -> Field {
+        field
+            .clone()
+            .with_data_type(self.convert(field.data_type()))
+    }
+}
+
+/// Converts the parquet schema into an Arrow schema, using `options.strings` for string
+/// and binary columns.
+pub fn arrow_metadata(
+    metadata: Arc<ParquetMetaData>,
+    reader_options: ArrowReaderOptions,
+    options: &ScanOptions,
+) -> Result<ArrowReaderMetadata> {
+    let arrow_metadata = ArrowReaderMetadata::try_new(metadata.clone(), reader_options.clone())?;
+    if options.strings == StringType::Plain {
+        return Ok(arrow_metadata);
+    }
+    let schema = arrow_metadata.schema();
+    let fields: Vec<Field> = schema
+        .fields()
+        .iter()
+        .map(|f| options.strings.convert_field(f))
+        .collect();
+    let hint = Schema::new_with_metadata(fields, schema.metadata().clone());
+    ArrowReaderMetadata::try_new(metadata, reader_options.with_schema(Arc::new(hint)))
+}
+
+/// What to read from a file, shared by every reader path.
+pub struct ScanPlan {
+    projection: Option<ProjectionMask>,
+    pub row_groups: Vec<usize>,
+    selection: Option<RowSelection>,
+    predicate: Option<Predicate>,
+    pushdown: bool,
+    batch_size: Option<usize>,
+    /// Root columns to keep once a scanned batch has been filtered.
+    output_roots: Option<Vec<String>>,
+    pruned: Option<Pruned>,
+    bloom: Option<BloomProbe>,
+    counters: Arc<FilterCounters>,
+    /// Offset and limit left to apply to filtered batches, with a predicate.
+    limit: Option<Mutex<Limit>>,
+    /// Rows a row group reader can stop after, with a pushed down predicate.
+    row_group_limit: Option<usize>,
+    ordered: bool,
+    metadata: Arc<ParquetMetaData>,
+    pub page_reads: Arc<PageReads>,
+}
+
+#[derive(Debug)]
+struct Limit {
+    skip: usize,
+    remaining: usize,
+}
+
+impl ScanPlan {
+    pub fn try_new(
+        metadata: &ArrowReaderMetadata,
+        options: &ScanOptions,
+    ) -> Result<Self, Box<dyn Error>> {
+        if let Some((path, expected)) = &options.schema {
+            if metadata.schema().fields() != expected.fields() {
+                return Err(format!("schema differs from {}", path).into());
+            }
+        }
+        let schema = metadata.metadata().file_metadata().schema_descr_ptr();
+
+        let columns = options
+            .columns
+            .as_ref()
+            .map(|spec| projection::parse_projection(spec, &schema))
+            .transpose()?;
+        let predicate = options
+            .predicate
+            .as_ref()
+            .map(|p| Predicate::parse(p))
+            .transpose()?;
+
+        // Without pushdown, the predicate columns are read alongside the projection and
+        // dropped again once the batch is filtered.
+        let mut read_columns = columns.clone();
+        if let (Some(predicate), false, Some(leaves)) =
+            (&predicate, options.pushdown, &mut read_columns)
+        {
+            leaves.extend(predicate::predicate_leaves(predicate, &schema)?);
+            leaves.sort_unstable();
+            leaves.dedup();
+        }
+        let output_roots = columns.as_ref().map(|leaves| {
+            leaves
+                .iter()
+                .map(|&i| schema.get_column_root(i).name().to_string())
+                .collect()
+        });
+
+        if options.bloom_filter && predicate.is_none() {
+            return Err("--bloom-filter requires --predicate".into());
+        }
+
+        let mut row_groups: Vec<usize> = (0..metadata.metadata().num_row_groups()).collect();
+        let mut selection = None;
+        let mut pruned = None;
+        if options.prune {
+            let predicate = predicate.as_ref().ok_or("--prune requires --predicate")?;
+            let mut leaves = read_columns
+                .clone()
+                .unwrap_or_else(|| (0..schema.num_columns()).collect());
+            leaves.extend(predicate::predicate_leaves(predicate, &schema)?);
+            leaves.sort_unstable();
+            leaves.dedup();
+
+            let p = pruning::prune(predicate, metadata.metadata(), metadata.schema(), &leaves)?;
+            row_groups.clone_from(&p.row_groups);
+            selection.clone_from(&p.selection);
+            pruned = Some(p);
+        }
+
+        // Without a predicate, the offset and limit are rows of the file and narrow the
+        // selection, so the offset index can skip their pages. With one, they count
+        // matching rows and are applied as batches are finished.
+        let num_rows = metadata.metadata().file_metadata().num_rows() as usize;
+        let mut rows = options.rows.clone();
+        let mut limit = None;
+        let mut row_group_limit = None;
+        if predicate.is_none() {
+            if options.offset > 0 || options.limit.is_some() {
+                let range = rows.unwrap_or(0..num_rows);
+                let start = range.start.saturating_add(options.offset).min(range.end);
+                let end = options.limit.map_or(range.end, |limit| {
+                    range.end.min(start.saturating_add(limit))
+                });
+                rows = Some(start..end);
+            }
+        } else if options.offset > 0 || options.limit.is_some() {
+            limit = Some(Mutex::new(Limit {
+                skip: options.offset,
+                remaining: options.limit.unwrap_or(usize::MAX),
+            }));
+            if options.pushdown {
+                row_group_limit = options.limit.map(|l| l.saturating_add(options.offset));
+            }
+        }
+
+        let mut plan = Self {
+            projection: read_columns.map(|leaves| ProjectionMask::leaves(&schema, leaves)),
+            row_groups,
+            selection,
+            predicate,
+            pushdown: options.pushdown,
+            batch_size: options.batch_size,
+            output_roots,
+            pruned,
+            bloom: None,
+            counters: Arc::new(FilterCounters::default()),
+            limit,
+            row_group_limit,
+            ordered: options.ordered,
+            metadata: metadata.metadata().clone(),
+            page_reads: Arc::new(PageReads::default()),
+        };
+        if let Some(rows) = rows {
+            plan.select_rows(metadata, rows);
+        }
+        Ok(plan)
+    }
+
+    /// Narrows the plan to the rows in `rows`, dropping the row groups outside of it.
+    fn select_rows(&mut self, metadata: &ArrowReaderMetadata, rows: Range<usize>) {
+        let mut first_row = 0;
+        let first_rows: Vec<usize> = metadata
+            .metadata()
+            .row_groups()
+            .iter()
+            .map(|rg| {
+                let first = first_row;
+                first_row += rg.num_rows() as usize;
+                first
+            })
+            .collect();
+        let selection: RowSelection = self
+            .row_groups
+            .iter()
+            .flat_map(|&rg| {
+                let start = first_rows[rg];
+                let end = start + metadata.metadata().row_group(rg).num_rows() as usize;
+                let from = rows.start.clamp(start, end);
+                let to = rows.end.clamp(from, end);
+                [
+                    RowSelector::skip(from - start),
+                    RowSelector::select(to - from),
+                    RowSelector::skip(end - to),
+                ]
+            })
+            .collect();
+        self.selection = Some(match &self.selection {
+            Some(s) => s.intersection(&selection),
+            None => selection,
+        });
+        self.retain_row_groups(metadata, |_, s| s.is_none_or(|s| s.selects_any()));
+    }
+
+    /// Keeps the row groups for which `keep` returns true, given their selection.
+    fn retain_row_groups(
+        &mut self,
+        metadata: &ArrowReaderMetadata,
+        keep: impl Fn(usize, Option<&RowSelection>) -> bool,
+    ) {
+        let kept: Vec<_> = self
+            .split_row_groups(metadata)
+            .into_iter()
+            .filter(|(rg, s)| keep(*rg, s.as_ref()))
+            .collect();
+        self.row_groups = kept.iter().map(|(rg, _)| *rg).collect();
+        if self.selection.is_some() {
+            self.selection = Some(
+                kept.into_iter()
+                    .flat_map(|(_, s)| s.map(Vec::<RowSelector>::from).unwrap_or_default())
+                    .collect(),
+            );
+        }
+    }
+
+    /// Configures `builder` to read `row_groups` of the plan, `selection` must cover
+    /// exactly those row groups.
+    pub fn apply_to<T>(
+        &self,
+        mut builder: ArrowReaderBuilder<T>,
+        row_groups: Vec<usize>,
+        selection: Option<RowSelection>,
+    ) -> Result<ArrowReaderBuilder<T>> {
+        let schema = builder.metadata().file_metadata().schema_descr_ptr();
+        builder = builder.with_row_groups(row_groups);
+        if let Some(batch_size) = self.batch_size {
+            builder = builder.with_batch_size(batch_size);
+        }
+        if let Some(selection) = selection {
+            builder = builder.with_row_selection(selection);
+        }
+        if let Some(limit) = self.row_group_limit {
+            builder = builder.with_limit(limit);
+        }
+        if let Some(projection) = &self.projection {
+            builder = builder.with_projection(projection.clone());
+        }
+        if let (Some(predicate), true) = (&self.predicate, self.pushdown) {
+            let filter = predicate::row_filter(predicate.clone(), &schema, self.counters.clone())?;
+            builder = builder.with_row_filter(filter);
+        }
+        Ok(builder)
+    }
+
+    /// Configures `builder` to read the whole plan.
+    pub fn apply_to_all<T>(&self, builder: ArrowReaderBuilder<T>) -> Result<ArrowReaderBuilder<T>> {
+        self.apply_to(builder, self.row_groups.clone(), self.selection.clone())
+    }
+
+    /// Splits the plan into one `(row group, selection)` pair per row group.
+    pub fn split_row_groups(
+        &self,
+        metadata: &ArrowReaderMetadata,
+    ) -> Vec<(usize, Option<RowSelection>)> {
+        let mut selection = self.selection.clone();
+        self.row_groups
+            .iter()
+            .map(|&rg| {
+                let num_rows = metadata.metadata().row_group(rg).num_rows() as usize;
+                (rg, selection.as_mut().map(|s| s.split_off(num_rows)))
+            })
+            .collect()
+    }
+
+    /// Drops the row groups whose bloom filters rule out the predicate, reading the
+    /// filters through `input`.
+    pub async fn probe_bloom_filters<T: AsyncFileReader + Send + 'static>(
+        &mut self,
+        input: T,
+        metadata: &ArrowReaderMetadata,
+    ) -> Result<()> {
+        let Some(predicate) = &self.predicate else {
+            return Ok(());
+        };
+        let leaves = self.decoded_leaves(metadata.metadata().file_metadata().schema_descr());
+        let probe = bloom::probe(input, metadata, predicate, &self.row_groups, &leaves).await?;
+        self.apply_bloom_probe(metadata, probe);
+        Ok(())
+    }
+
+    /// Like [`Self::probe_bloom_filters`], reading the filters through a [`ChunkReader`].
+    pub fn probe_bloom_filters_sync<R: ChunkReader + 'static>(
+        &mut self,
+        input: R,
+        metadata: &ArrowReaderMetadata,
+    ) -> Result<()> {
+        let Some(predicate) = &self.predicate else {
+            return Ok(());
+        };
+        let leaves = self.decoded_leaves(metadata.metadata().file_metadata().schema_descr());
+        let probe = bloom::probe_sync(input, metadata, predicate, &self.row_groups, &leaves)?;
+        self.apply_bloom_probe(metadata, probe);
+        Ok(())
+    }
+
+    fn apply_bloom_probe(&mut self, metadata: &ArrowReaderMetadata, probe: BloomProbe) {
+        self.retain_row_groups(metadata, |rg, _| probe.row_groups.contains(&rg));
+        self.bloom = Some(probe);
+    }
+
+    /// Leaf columns the plan decodes, including the predicate's.
+    pub fn decoded_leaves(&self, schema: &SchemaDescriptor) -> Vec<usize> {
+        let predicate_leaves = self
+            .predicate
+            .as_ref()
+            .and_then(|p| predicate::predicate_leaves(p, schema).ok())
+            .unwrap_or_default();
+        (0..schema.num_columns())
+            .filter(|&i| {
+                self.projection.as_ref().is_none_or(|p| p.leaf_included(i))
+                    || predicate_leaves.contains(&i)
+            })
+            .collect()
+    }
+
+    /// Filters a decoded batch when the predicate is not pushed down, then applies the
+    /// offset and limit of a predicate.
+    pub fn finish_batch(&self, batch: RecordBatch) -> Result<RecordBatch, ArrowError> {
+        let batch = self.filter(batch)?;
+        let Some(limit) = &self.limit else {
+            return Ok(batch);
+        };
+        let mut limit = limit.lock().unwrap();
+        let skip = limit.skip.min(batch.num_rows());
+        let len = (batch.num_rows() - skip).min(limit.remaining);
+        limit.skip -= skip;
+        limit.remaining -= len;
+        Ok(batch.slice(skip, len))
+    }
+
+    /// Whether batches have to be passed on in file order, as asked for or as the offset
+    /// and limit of a predicate count matching rows in that order.
+    pub fn is_ordered(&self) -> bool {
+        self.ordered || self.limit.is_some()
+    }
+
+    /// Whether the limit has been reached, and the rest of the file can be left unread.
+    pub fn is_done(&self) -> bool {
+        self.limit
+            .as_ref()
+            .is_some_and(|l| l.lock().unwrap().remaining == 0)
+    }
+
+    fn filter(&self, batch: RecordBatch) -> Result<RecordBatch, ArrowError> {
+        let (Some(predicate), false) = (&self.predicate, self.pushdown) else {
+            return Ok(batch);
+        };
+        let selection = predicate.evaluate(&batch)?;
+        self.counters.record(&selection);
+        let batch = filter_record_batch(&batch, &selection)?;
+        match &self.output_roots {
+            Some(roots) => {
+                let indices: Vec<usize> = (0..batch.num_columns())
+                    .filter(|&i| roots.contains(batch.schema().field(i).name()))
+                    .collect();
+                batch.project(&indices)
+            }
+            None => Ok(batch),
+        }
+    }
+
+    /// Prints filter and pruning counters, if there are any.
+    pub fn report(&self, rows_returned: usize) {
+        if self.predicate.is_some() {
+            println!(
+                "rows scanned: {}, rows selected: {}, rows returned: {}",
+                self.counters.scanned(),
+                self.counters.selected(),
+                rows_returned
+            );
+        }
+        if let Some(p) = &self.pruned {
+            println!(
+                "row groups skipped: {} of {}, pages skipped: {} of {}",
+                p.row_groups_total - p.row_groups.len(),
+                p.row_groups_total,
+                p.pages_skipped,
+                p.pages_total
+            );
+        }
+        if let Some(b) = &self.bloom {
+            println!(
+                "bloom filters read: {} ({} bytes in {} requests), row groups skipped: {}, column chunk bytes saved: {}",
+                b.filters_read, b.filter_bytes, b.requests, b.row_groups_skipped, b.bytes_saved
+            );
+        }
+        let leaves = self.decoded_leaves(self.metadata.file_metadata().schema_descr());
+        match self.page_reads.count(&self.metadata, &leaves) {
+            Some((decompressed, Some(total))) => {
+                println!("pages decompressed: {} of {}", decompressed, total)
+            }
+            Some((decompressed, None)) => println!("pages decompressed: {}", decompressed),
+            None => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::Int64Array;
+    use bytes::Bytes;
+    use parquet::arrow::ArrowWriter;
+    use parquet::file::metadata::ParquetMetaDataReader;
+    use parquet::file::properties::WriterProperties;
+
+    /// Three row groups of 100 rows, `v` counting up from 0.
+    fn metadata() -> ArrowReaderMetadata {
+        let batch = batch(0..300);
+        let props = WriterProperties::builder()
+            .set_max_row_group_size(100)
+            .build();
+        let mut buf = vec![];
+        let mut writer = ArrowWriter::try_new(&mut buf, batch.schema(), Some(props)).unwrap();
+        writer.write(&batch).unwrap();
+        writer.close().unwrap();
+        let metadata = ParquetMetaDataReader::new()
+            .with_page_indexes(true)
+            .parse_and_finish(&Bytes::from(buf))
+            .unwrap();
+        ArrowReaderMetadata::try_new(Arc::new(metadata), Default::default()).unwrap()
+    }
+
+    fn plan(options: ScanOptions) -> ScanPlan {
+        ScanPlan::try_new(&metadata(), &options).unwrap()
+    }
+
+    fn selection(selectors: Vec<RowSelector>) -> Option<RowSelection> {
+        Some(RowSelection::from(selectors))
+    }
+
+    fn batch(values: Range<i64>) -> RecordBatch {
+        RecordBatch::try_from_iter([("v", Arc::new(Int64Array::from_iter_values(values)) as _)])
+            .unwrap()
+    }
+
+    fn values(batch: RecordBatch) -> Vec<i64> {
+        let values = batch
+            .column(0)
+            .as_any()
+            .downcast_ref::<Int64Array>()
+            .unwrap();
+        values.values().to_vec()
+    }
+
+    #[test]
+    fn offset_and_limit_select_rows_of_the_file() {
+        let plan = plan(ScanOptions {
+            offset: 150,
+            limit: Some(20),
+            ..Default::default()
+        });
+        assert_eq!(plan.row_groups, vec![1]);
+        assert_eq!(
+            plan.selection,
+            selection(vec![
+                RowSelector::skip(50),
+                RowSelector::select(20),
+                RowSelector::skip(30)
+            ])
+        );
+        assert!(plan.limit.is_none());
+        assert!(!plan.is_ordered());
+    }
+
+    #[test]
+    fn offset_and_limit_count_from_the_start_of_rows() {
+        let plan = plan(ScanOptions {
+            rows: Some(90..250),
+            offset: 5,
+            limit: Some(10),
+            ..Default::default()
+        });
+        assert_eq!(plan.row_groups, vec![0, 1]);
+        assert_eq!(
+            plan.selection,
+            selection(vec![
+                RowSelector::skip(95),
+                RowSelector::select(10),
+                RowSelector::skip(95)
+            ])
+        );
+    }
+
+    #[test]
+    fn offset_and_limit_count_matching_rows_with_a_predicate() {
+        let plan = plan(ScanOptions {
+            predicate: Some("v >= 10".to_string()),
+            offset: 5,
+            limit: Some(8),
+            ..Default::default()
+        });
+        assert_eq!(plan.row_groups, vec![0, 1, 2]);
+        assert_eq!(plan.selection, None);
+        assert!(plan.is_ordered());
+
+        assert!(values(plan.finish_batch(batch(0..12)).unwrap()).is_empty());
+        assert!(!plan.is_done());
+        assert_eq!(
+            values(plan.finish_batch(batch(12..20)).unwrap()),
+            [15, 16, 17, 18, 19]
+        );
+        assert_eq!(
+            values(plan.finish_batch(batch(20..30)).unwrap()),
+            [20, 21, 22]
+        );
+        assert!(plan.is_done());
+        assert!(values(plan.finish_batch(batch(30..40)).unwrap()).is_empty());
+    }
+
+    #[test]
+    fn a_pushed_down_predicate_stops_row_groups_after_offset_and_limit() {
+        let plan = plan(ScanOptions {
+            predicate: Some("v >= 10".to_string()),
+            pushdown: true,
+            offset: 5,
+            limit: Some(8),
+            ..Default::default()
+        });
+        assert_eq!(plan.row_group_limit, Some(13));
+        assert_eq!(
+            values(plan.finish_batch(batch(10..20)).unwrap()),
+            [15, 16, 17, 18, 19]
+        );
+    }
+
+    #[test]
+    fn rows_past_the_end_are_cut_to_the_file() {
+        let plan = plan(ScanOptions {
+            rows: Some(250..1000),
+            ..Default::default()
+        });
+        assert_eq!(plan.row_groups, vec![2]);
+        assert_eq!(
+            plan.selection,
+            selection(vec![RowSelector::skip(50), RowSelector::select(50)])
+        );
+
+        let plan = self::plan(ScanOptions {
+            offset: 400,
+            ..Default::default()
+        });
+        assert!(plan.row_groups.is_empty());
+    }
+
+    #[test]
+    fn an_empty_range_reads_nothing() {
+        for options in [
+            ScanOptions {
+                rows: Some(120..120),
+                ..Default::default()
+            },
+            ScanOptions {
+                limit: Some(0),
+                ..Default::default()
+            },
+        ] {
+            let plan = plan(options);
+            assert!(plan.row_groups.is_empty());
+            assert_eq!(plan.selection, selection(vec![]));
+        }
+    }
+}
@@ -0,0 +1,287 @@
+//! A local object store stand-in, so that S3-like reads can be benchmarked without a network.
+
+use std::fmt;
+use std::ops::Range;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::stream::BoxStream;
+use object_store::local::LocalFileSystem;
+use object_store::memory::InMemory;
+use object_store::path::Path as ObjectPath;
+use object_store::throttle::{ThrottleConfig, ThrottledStore};
+use object_store::{
+    GetOptions, GetResult, ListResult, MultipartUpload, ObjectMeta, ObjectStore, PutMultipartOpts,
+    PutOptions, PutPayload, PutResult, Result,
+};
+use tokio::time::Instant;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StoreKind {
+    /// Reads the file in place through [`LocalFileSystem`].
+    Local,
+    /// Copies the file into an [`InMemory`] store first, so that only the injected
+    /// latency and bandwidth remain.
+    Memory,
+}
+
+#[derive(Debug, Clone)]
+pub struct StoreOptions {
+    pub kind: StoreKind,
+    /// Added to every request.
+    pub latency: Duration,
+    /// Shared by every request, unlimited if `None`.
+    pub bandwidth: Option<Arc<Bandwidth>>,
+}
+
+/// A link that every request it is given to transfers over: transfers queue behind one
+/// another, so that concurrent requests, of one store or of several, split the bandwidth
+/// instead of each getting all of it.
+#[derive(Debug)]
+pub struct Bandwidth {
+    bytes_per_second: u64,
+    /// When the transfers queued so far have gone through.
+    free_at: Mutex<Instant>,
+}
+
+impl Bandwidth {
+    pub fn new(bytes_per_second: u64) -> Self {
+        Self {
+            bytes_per_second: bytes_per_second.max(1),
+            free_at: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Waits until `bytes` have gone through, after the transfers queued before them.
+    async fn transfer(&self, bytes: usize) {
+        let done = {
+            let mut free_at = self.free_at.lock().unwrap();
+            let start = (*free_at).max(Instant::now());
+            *free_at = start + Duration::from_secs_f64(bytes as f64 / self.bytes_per_second as f64);
+            *free_at
+        };
+        tokio::time::sleep_until(done).await;
+    }
+}
+
+/// Makes `path` available to an object store of `kind`, returning the store and the
+/// object's location in it. For [`StoreKind::Memory`] this copies the whole file, which
+/// is why it is kept apart from [`open`].
+pub async fn load(
+    path: &Path,
+    kind: StoreKind,
+) -> Result<(Box<dyn ObjectStore>, ObjectPath), Box<dyn std::error::Error>> {
+    let path = path.canonicalize()?;
+    let file_name = path.file_name().ok_or("input is not a file")?;
+    let location = ObjectPath::from(file_name.to_string_lossy().as_ref());
+
+    let inner: Box<dyn ObjectStore> = match kind {
+        StoreKind::Local => Box::new(LocalFileSystem::new_with_prefix(
+            path.parent().ok_or("input has no parent directory")?,
+        )?),
+        StoreKind::Memory => {
+            let store = InMemory::new();
+            let bytes = Bytes::from(tokio::fs::read(&path).await?);
+            store.put(&location, bytes.into()).await?;
+            Box::new(store)
+        }
+    };
+    Ok((inner, location))
+}
+
+/// Puts a store from [`load`] behind the latency and bandwidth of `options`, returning
+/// the throttled store and the metadata of the object at `location`.
+pub async fn open(
+    inner: Box<dyn ObjectStore>,
+    location: &ObjectPath,
+    options: &StoreOptions,
+) -> Result<(Arc<CountingStore>, ObjectMeta), Box<dyn std::error::Error>> {
+    let config = ThrottleConfig {
+        wait_get_per_call: options.latency,
+        ..Default::default()
+    };
+    let store = Arc::new(CountingStore::new(
+        ThrottledStore::new(inner, config),
+        options.bandwidth.clone(),
+    ));
+    let meta = store.head(location).await?;
+    Ok((store, meta))
+}
+
+/// Counts the get requests issued against the wrapped store, and holds back their bytes
+/// until they have gone through the shared [`Bandwidth`].
+#[derive(Debug)]
+pub struct CountingStore {
+    inner: ThrottledStore<Box<dyn ObjectStore>>,
+    bandwidth: Option<Arc<Bandwidth>>,
+    requests: AtomicUsize,
+    ranges: AtomicUsize,
+    bytes: AtomicUsize,
+}
+
+impl CountingStore {
+    fn new(inner: ThrottledStore<Box<dyn ObjectStore>>, bandwidth: Option<Arc<Bandwidth>>) -> Self {
+        Self {
+            inner,
+            bandwidth,
+            requests: AtomicUsize::new(0),
+            ranges: AtomicUsize::new(0),
+            bytes: AtomicUsize::new(0),
+        }
+    }
+
+    /// Counts a request for `ranges` and waits for their bytes to go through.
+    async fn record(&self, ranges: &[Range<usize>]) {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        self.ranges.fetch_add(ranges.len(), Ordering::Relaxed);
+        let bytes: usize = ranges.iter().map(|r| r.end - r.start).sum();
+        self.bytes.fetch_add(bytes, Ordering::Relaxed);
+        if let Some(bandwidth) = &self.bandwidth {
+            bandwidth.transfer(bytes).await;
+        }
+    }
+
+    /// Prints the request counters.
+    pub fn report(&self) {
+        println!(
+            "object store requests: {}, ranges: {}, bytes: {}",
+            self.requests.load(Ordering::Relaxed),
+            self.ranges.load(Ordering::Relaxed),
+            self.bytes.load(Ordering::Relaxed)
+        );
+    }
+}
+
+impl fmt::Display for CountingStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "CountingStore({})", self.inner)
+    }
+}
+
+#[async_trait]
+impl ObjectStore for CountingStore {
+    async fn put_opts(
+        &self,
+        location: &ObjectPath,
+        payload: PutPayload,
+        opts: PutOptions,
+    ) -> Result<PutResult> {
+        self.inner.put_opts(location, payload, opts).await
+    }
+
+    async fn put_multipart_opts(
+        &self,
+        location: &ObjectPath,
+        opts: PutMultipartOpts,
+    ) -> Result<Box<dyn MultipartUpload>> {
+        self.inner.put_multipart_opts(location, opts).await
+    }
+
+    async fn get_opts(&self, location: &ObjectPath, options: GetOptions) -> Result<GetResult> {
+        let result = self.inner.get_opts(location, options).await?;
+        self.record(std::slice::from_ref(&result.range)).await;
+        Ok(result)
+    }
+
+    async fn get_range(&self, location: &ObjectPath, range: Range<usize>) -> Result<Bytes> {
+        let bytes = self.inner.get_range(location, range.clone()).await?;
+        self.record(std::slice::from_ref(&range)).await;
+        Ok(bytes)
+    }
+
+    async fn get_ranges(
+        &self,
+        location: &ObjectPath,
+        ranges: &[Range<usize>],
+    ) -> Result<Vec<Bytes>> {
+        let bytes = self.inner.get_ranges(location, ranges).await?;
+        self.record(ranges).await;
+        Ok(bytes)
+    }
+
+    async fn head(&self, location: &ObjectPath) -> Result<ObjectMeta> {
+        self.inner.head(location).await
+    }
+
+    async fn delete(&self, location: &ObjectPath) -> Result<()> {
+        self.inner.delete(location).await
+    }
+
+    fn list(&self, prefix: Option<&ObjectPath>) -> BoxStream<'_, Result<ObjectMeta>> {
+        self.inner.list(prefix)
+    }
+
+    async fn list_with_delimiter(&self, prefix: Option<&ObjectPath>) -> Result<ListResult> {
+        self.inner.list_with_delimiter(prefix).await
+    }
+
+    async fn copy(&self, from: &ObjectPath, to: &ObjectPath) -> Result<()> {
+        self.inner.copy(from, to).await
+    }
+
+    async fn copy_if_not_exists(&self, from: &ObjectPath, to: &ObjectPath) -> Result<()> {
+        self.inner.copy_if_not_exists(from, to).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn runtime() -> tokio::runtime::Runtime {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .start_paused(true)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn concurrent_transfers_split_the_bandwidth() {
+        runtime().block_on(async {
+            let bandwidth = Bandwidth::new(1000);
+            let start = Instant::now();
+            futures::join!(bandwidth.transfer(300), bandwidth.transfer(700));
+            assert_eq!(start.elapsed(), Duration::from_secs(1));
+
+            // The link has been idle since, so a later transfer does not queue.
+            tokio::time::sleep(Duration::from_secs(1)).await;
+            let start = Instant::now();
+            bandwidth.transfer(500).await;
+            assert_eq!(start.elapsed(), Duration::from_millis(500));
+        });
+    }
+
+    #[test]
+    fn stores_sharing_a_bandwidth_queue_behind_each_other() {
+        let path = std::env::temp_dir().join(format!("store-{}", std::process::id()));
+        std::fs::write(&path, vec![7; 2000]).unwrap();
+        let options = StoreOptions {
+            kind: StoreKind::Memory,
+            latency: Duration::ZERO,
+            bandwidth: Some(Arc::new(Bandwidth::new(1000))),
+        };
+        runtime().block_on(async {
+            let (a, location) = load(&path, options.kind).await.unwrap();
+            let (a, _) = open(a, &location, &options).await.unwrap();
+            let (b, _) = load(&path, options.kind).await.unwrap();
+            let (b, _) = open(b, &location, &options).await.unwrap();
+
+            let start = Instant::now();
+            let (x, y) = futures::join!(
+                a.get_range(&location, 0..1000),
+                b.get_ranges(&location, &[0..500, 1500..2000]),
+            );
+            assert_eq!(start.elapsed(), Duration::from_secs(2));
+            assert_eq!(x.unwrap().len(), 1000);
+            assert_eq!(y.unwrap().iter().map(Bytes::len).sum::<usize>(), 1000);
+            assert_eq!(a.bytes.load(Ordering::Relaxed), 1000);
+            assert_eq!(b.ranges.load(Ordering::Relaxed), 2);
+        });
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,527 @@
+use std::error::Error;
+use std::fs::File;
+use std::io;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::Instant;
+
+use arrow::array::RecordBatch;
+use bytes::Bytes;
+use memmap2::Mmap;
+use parquet::arrow::arrow_reader::{
+    ArrowReaderMetadata, ArrowReaderOptions, ParquetRecordBatchReaderBuilder, RowSelection,
+    RowSelector,
+};
+use parquet::arrow::ProjectionMask;
+use parquet::errors::ParquetError;
+use parquet::file::metadata::{ParquetMetaData, ParquetMetaDataReader};
+use parquet::file::reader::ChunkReader;
+use parquet::file::FOOTER_SIZE;
+
+use crate::error::{ReadError, Stage};
+use crate::metrics::{nanos, Metrics};
+use crate::pages::RecordingReader;
+use crate::scan::{self, ScanOptions, ScanPlan};
+use crate::trace::{IoTrace, Phase, TracingReader};
+
+type BoxError = Box<dyn Error + Send + Sync>;
+
+/// Where the sync reader reads the file from.
+pub enum Input<'a> {
+    /// A new `std::fs::File` per reader, recording every request into the trace if there
+    /// is one.
+    File(&'a Path, Option<Arc<IoTrace>>),
+    /// A read-only memory map, handing out zero-copy slices of it.
+    Mmap(&'a Path),
+    /// The whole file, read into memory before the run is timed.
+    Bytes(Bytes),
+}
+
+/// Reads `input` through [`ParquetRecordBatchReaderBuilder`].
+///
+/// On one thread, the plan is read through the reader the footer was read with, and a
+/// decode error is pinned to a row group afterwards. With `--keep-going`, every row group
+/// is read through its own reader instead, so that the remaining ones are still read after
+/// an error. With more than one thread, row groups are handed out to a pool of `threads`
+/// workers in the same way, and batches are passed back to the calling thread for
+/// `on_batch`, in file order if [`ScanPlan::is_ordered`].
+pub fn read(
+    input: Input,
+    options: ArrowReaderOptions,
+    scan: &ScanOptions,
+    threads: usize,
+    metrics: &mut Metrics,
+    on_batch: impl FnMut(&ScanPlan, RecordBatch) -> Result<(), Box<dyn Error>>,
+) -> Result<ScanPlan, Box<dyn Error>> {
+    match input {
+        Input::File(path, Some(trace)) => {
+            let open = || Ok(TracingReader::new(File::open(path)?, trace.clone()));
+            let set_phase = |phase| trace.set_phase(phase);
+            read_from(open, options, scan, threads, metrics, set_phase, on_batch)
+        }
+        Input::File(path, None) => {
+            let open = || File::open(path);
+            read_from(open, options, scan, threads, metrics, |_| {}, on_batch)
+        }
+        Input::Mmap(path) => {
+            let now = Instant::now();
+            // Safety: the file is not expected to change while it is being read.
+            let mmap = File::open(path)
+                .and_then(|file| unsafe { Mmap::map(&file) })
+                .map_err(ReadError::at(Stage::Open))?;
+            let bytes = Bytes::from_owner(mmap);
+            metrics.open_time_nanos = nanos(now.elapsed());
+            let open = || Ok(bytes.clone());
+            read_from(open, options, scan, threads, metrics, |_| {}, on_batch)
+        }
+        Input::Bytes(bytes) => {
+            let open = || Ok(bytes.clone());
+            read_from(open, options, scan, threads, metrics, |_| {}, on_batch)
+        }
+    }
+}
+
+fn read_from<R: ChunkReader + 'static>(
+    open: impl Fn() -> io::Result<R> + Sync,
+    options: ArrowReaderOptions,
+    scan: &ScanOptions,
+    threads: usize,
+    metrics: &mut Metrics,
+    set_phase: impl Fn(Phase),
+    mut on_batch: impl FnMut(&ScanPlan, RecordBatch) -> Result<(), Box<dyn Error>>,
+) -> Result<ScanPlan, Box<dyn Error>> {
+    let now = Instant::now();
+    let file = open().map_err(ReadError::at(Stage::Open))?;
+    metrics.open_time_nanos += nanos(now.elapsed());
+
+    set_phase(Phase::Footer);
+    let now = Instant::now();
+    metrics.footers_read += 1;
+    let mut metadata = read_metadata(&file)?;
+    metrics.metadata_decode_time_nanos = nanos(now.elapsed());
+
+    if scan.needs_page_index() {
+        set_phase(Phase::PageIndex);
+        let now = Instant::now();
+        let mut reader = ParquetMetaDataReader::new_with_metadata(metadata).with_page_indexes(true);
+        reader
+            .read_page_indexes(&file)
+            .map_err(ReadError::at(Stage::PageIndex))?;
+        metadata = reader.finish().map_err(ReadError::at(Stage::PageIndex))?;
+        metrics.page_index_load_time_nanos = nanos(now.elapsed());
+    }
+
+    let now = Instant::now();
+    let metadata = scan::arrow_metadata(Arc::new(metadata), options, scan)
+        .map_err(ReadError::at(Stage::Schema))?;
+    metrics.schema_build_time_nanos = nanos(now.elapsed());
+
+    let mut plan = ScanPlan::try_new(&metadata, scan)?;
+
+    if scan.bloom_filter {
+        set_phase(Phase::BloomFilter);
+        let now = Instant::now();
+        let input = open().map_err(ReadError::at(Stage::Open))?;
+        plan.probe_bloom_filters_sync(input, &metadata)
+            .map_err(ReadError::at(Stage::BloomFilter))?;
+        metrics.bloom_filter_time_nanos = nanos(now.elapsed());
+    }
+
+    set_phase(Phase::ColumnChunk);
+    let now = Instant::now();
+    let mut handle = |batch: Result<RecordBatch, ReadError>| {
+        match batch {
+            Ok(batch) => {
+                metrics.record_batch(&batch);
+                on_batch(&plan, batch)?
+            }
+            Err(e) if scan.keep_going => {
+                eprintln!("error: {}", e);
+                metrics.errors += 1;
+            }
+            Err(e) => return Err(e.into()),
+        }
+        Ok::<_, Box<dyn Error>>(())
+    };
+
+    if threads <= 1 && !scan.keep_going {
+        let mut result = Ok(());
+        read_plan(file, &open, &metadata, &plan, |batch| {
+            result = handle(batch);
+            result.is_ok() && !plan.is_done()
+        });
+        result?;
+    } else if threads <= 1 {
+        for (rg, selection) in plan.split_row_groups(&metadata) {
+            let mut result = Ok(());
+            read_row_group(&open, &metadata, &plan, rg, selection, |batch| {
+                result = handle(batch);
+                result.is_ok() && !plan.is_done()
+            });
+            result?;
+            if plan.is_done() {
+                break;
+            }
+        }
+    } else {
+        let row_groups = plan.split_row_groups(&metadata);
+        let next = AtomicUsize::new(0);
+        let (tx, rx) = mpsc::sync_channel(threads * 2);
+        // In file order, every row group has a channel of its own and the calling thread
+        // drains them in turn, so that at most `threads` row groups are decoded ahead.
+        let (senders, receivers): (Vec<_>, Vec<_>) = match plan.is_ordered() {
+            true => row_groups
+                .iter()
+                .map(|_| {
+                    let (tx, rx) = mpsc::sync_channel(2);
+                    (Mutex::new(Some(tx)), rx)
+                })
+                .unzip(),
+            false => (vec![], vec![]),
+        };
+        thread::scope(|s| {
+            for _ in 0..threads {
+                let (tx, open, plan, metadata) = (tx.clone(), &open, &plan, &metadata);
+                let (row_groups, next, senders) = (&row_groups, &next, &senders);
+                s.spawn(move || loop {
+                    let i = next.fetch_add(1, Ordering::Relaxed);
+                    let Some((rg, selection)) = row_groups.get(i) else {
+                        return;
+                    };
+                    let tx = match senders.get(i) {
+                        Some(sender) => sender.lock().unwrap().take().unwrap(),
+                        None => tx.clone(),
+                    };
+                    // The receiver only hangs up once the read has failed.
+                    let mut sent = true;
+                    read_row_group(open, metadata, plan, *rg, selection.clone(), |batch| {
+                        sent = tx.send(batch).is_ok();
+                        sent && !plan.is_done()
+                    });
+                    if !sent || plan.is_done() {
+                        return;
+                    }
+                });
+            }
+            drop(tx);
+            if receivers.is_empty() {
+                return rx.into_iter().try_for_each(&mut handle);
+            }
+            for rx in receivers {
+                // The row groups after the limit are never taken, nor their channels closed.
+                if plan.is_done() {
+                    break;
+                }
+                rx.into_iter().try_for_each(&mut handle)?;
+            }
+            Ok(())
+        })?;
+    }
+    metrics.decode_time_nanos = nanos(now.elapsed());
+
+    Ok(plan)
+}
+
+/// Reads the 8 byte footer and decodes the metadata it points to.
+fn read_metadata<R: ChunkReader>(file: &R) -> Result<ParquetMetaData, ReadError> {
+    let len = file.len();
+    let footer_start = len
+        .checked_sub(FOOTER_SIZE as u64)
+        .ok_or_else(|| ReadError::new(Stage::Footer, "file is smaller than the footer"))?;
+    let footer = file
+        .get_bytes(footer_start, FOOTER_SIZE)
+        .map_err(ReadError::at(Stage::Footer))?;
+    let footer = footer.as_ref().try_into().map_err(|_| {
+        ReadError::new(
+            Stage::Footer,
+            format!(
+                "read {} footer bytes, expected {}",
+                footer.len(),
+                FOOTER_SIZE
+            ),
+        )
+    })?;
+    let metadata_len =
+        ParquetMetaDataReader::decode_footer(footer).map_err(ReadError::at(Stage::Footer))?;
+    let metadata_start = footer_start
+        .checked_sub(metadata_len as u64)
+        .ok_or_else(|| {
+            ReadError::new(
+                Stage::Footer,
+                format!("metadata length {} exceeds the file", metadata_len),
+            )
+        })?;
+    let buf = file
+        .get_bytes(metadata_start, metadata_len)
+        .map_err(ReadError::at(Stage::Metadata))?;
+    ParquetMetaDataReader::decode_metadata(&buf).map_err(ReadError::at(Stage::Metadata))
+}
+
+/// Decodes the whole plan through `file`, passing every batch to `emit` until it returns
+/// false. The first error ends the read, and is pinned to the first row group that fails
+/// to decode on its own through a new reader from `open`.
+fn read_plan<R: ChunkReader + 'static>(
+    file: R,
+    open: &impl Fn() -> io::Result<R>,
+    metadata: &ArrowReaderMetadata,
+    plan: &ScanPlan,
+    mut emit: impl FnMut(Result<RecordBatch, ReadError>) -> bool,
+) {
+    let file = RecordingReader::new(file, plan.page_reads.clone());
+    let builder = ParquetRecordBatchReaderBuilder::new_with_metadata(file, metadata.clone());
+    let error: BoxError = match plan.apply_to_all(builder).and_then(|b| b.build()) {
+        Ok(reader) => {
+            let mut error = None;
+            for batch in reader {
+                match batch {
+                    Ok(batch) => {
+                        if !emit(Ok(batch)) {
+                            return;
+                        }
+                    }
+                    Err(e) => {
+                        error = Some(e.into());
+                        break;
+                    }
+                }
+            }
+            match error {
+                Some(error) => error,
+                None => return,
+            }
+        }
+        Err(e) => e.into(),
+    };
+
+    let schema = metadata.metadata().file_metadata().schema_descr();
+    let projection = ProjectionMask::leaves(schema, plan.decoded_leaves(schema));
+    let fails = |row_group: usize, selection: Option<RowSelection>| {
+        let mut builder =
+            ParquetRecordBatchReaderBuilder::new_with_metadata(open()?, metadata.clone())
+                .with_row_groups(vec![row_group])
+                .with_projection(projection.clone());
+        if let Some(selection) = selection {
+            builder = builder.with_row_selection(selection);
+        }
+        for batch in builder.build()? {
+            batch?;
+        }
+        Ok::<_, BoxError>(())
+    };
+    let row_group = plan
+        .split_row_groups(metadata)
+        .into_iter()
+        .find(|(rg, selection)| fails(*rg, selection.clone()).is_err());
+    let error = match row_group {
+        Some((rg, _)) => {
+            let mut error = ReadError::new(Stage::RowGroup(rg), error);
+            (error.column, error.page_offset) = locate(open, metadata, plan, rg);
+            error
+        }
+        None => ReadError::new(Stage::Decode, error),
+    };
+    emit(Err(error));
+}
+
+/// Decodes `row_group` of the plan through a new reader from `open`, passing every batch
+/// to `emit` until it returns false. The first error ends the row group.
+fn read_row_group<R: ChunkReader + 'static>(
+    open: &impl Fn() -> io::Result<R>,
+    metadata: &ArrowReaderMetadata,
+    plan: &ScanPlan,
+    row_group: usize,
+    selection: Option<RowSelection>,
+    mut emit: impl FnMut(Result<RecordBatch, ReadError>) -> bool,
+) {
+    let reader = open().map_err(ParquetError::from).and_then(|file| {
+        let file = RecordingReader::new(file, plan.page_reads.clone());
+        let builder = ParquetRecordBatchReaderBuilder::new_with_metadata(file, metadata.clone());
+        plan.apply_to(builder, vec![row_group], selection)?.build()
+    });
+    let error = match reader {
+        Ok(reader) => {
+            let mut error = None;
+            for batch in reader {
+                match batch {
+                    Ok(batch) => {
+                        if !emit(Ok(batch)) {
+                            return;
+                        }
+                    }
+                    Err(e) => {
+                        error = Some(ReadError::new(Stage::RowGroup(row_group), e));
+                        break;
+                    }
+                }
+            }
+            error
+        }
+        Err(e) => Some(ReadError::new(Stage::RowGroup(row_group), e)),
+    };
+    if let Some(mut error) = error {
+        (error.column, error.page_offset) = locate(open, metadata, plan, row_group);
+        emit(Err(error));
+    }
+}
+
+/// Narrows a failed row group down to a column, and to a page if the file has an offset
+/// index, by decoding each column and then each of its pages on its own.
+fn locate<R: ChunkReader + 'static>(
+    open: &impl Fn() -> io::Result<R>,
+    metadata: &ArrowReaderMetadata,
+    plan: &ScanPlan,
+    row_group: usize,
+) -> (Option<String>, Option<i64>) {
+    let decode = |metadata: &ArrowReaderMetadata, leaf: usize, selection: Option<RowSelection>| {
+        let schema = metadata.metadata().file_metadata().schema_descr();
+        let mut builder =
+            ParquetRecordBatchReaderBuilder::new_with_metadata(open()?, metadata.clone())
+                .with_row_groups(vec![row_group])
+                .with_projection(ProjectionMask::leaves(schema, [leaf]));
+        if let Some(selection) = selection {
+            builder = builder.with_row_selection(selection);
+        }
+        for batch in builder.build()? {
+            batch?;
+        }
+        Ok::<_, Box<dyn Error>>(())
+    };
+
+    let schema = metadata.metadata().file_metadata().schema_descr();
+    let Some(leaf) = plan
+        .decoded_leaves(schema)
+        .into_iter()
+        .find(|&leaf| decode(metadata, leaf, None).is_err())
+    else {
+        return (None, None);
+    };
+    let column = Some(schema.column(leaf).path().string());
+
+    let metadata = match metadata.metadata().offset_index() {
+        Some(_) => metadata.clone(),
+        None => {
+            // Only the page index is loaded, the footer has been read already.
+            let mut reader =
+                ParquetMetaDataReader::new_with_metadata(metadata.metadata().as_ref().clone())
+                    .with_page_indexes(true);
+            let loaded = match open() {
+                Ok(file) => reader
+                    .read_page_indexes(&file)
+                    .and_then(|_| reader.finish()),
+                Err(e) => Err(e.into()),
+            };
+            match loaded.and_then(|m| ArrowReaderMetadata::try_new(Arc::new(m), Default::default()))
+            {
+                Ok(metadata) => metadata,
+                Err(_) => return (column, None),
+            }
+        }
+    };
+    let Some(pages) = metadata
+        .metadata()
+        .offset_index()
+        .and_then(|index| index.get(row_group)?.get(leaf))
+        .map(|index| index.page_locations())
+    else {
+        return (column, None);
+    };
+    let num_rows = metadata.metadata().row_group(row_group).num_rows();
+    let page = pages.iter().enumerate().find(|(i, page)| {
+        let end = pages
+            .get(i + 1)
+            .map_or(num_rows, |next| next.first_row_index);
+        let selection = RowSelection::from(vec![
+            RowSelector::skip(page.first_row_index as usize),
+            RowSelector::select((end - page.first_row_index) as usize),
+        ]);
+        decode(&metadata, leaf, Some(selection)).is_err()
+    });
+    (column, page.map(|(_, page)| page.offset))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::Int64Array;
+    use parquet::arrow::ArrowWriter;
+    use parquet::file::properties::WriterProperties;
+
+    /// Two row groups of 500 rows, `a` and `b` in pages of 100 without a dictionary.
+    fn file() -> Bytes {
+        let batch = RecordBatch::try_from_iter([
+            ("a", Arc::new(Int64Array::from_iter_values(0..1000)) as _),
+            ("b", Arc::new(Int64Array::from_iter_values(1000..2000)) as _),
+        ])
+        .unwrap();
+        let props = WriterProperties::builder()
+            .set_max_row_group_size(500)
+            .set_dictionary_enabled(false)
+            .set_data_page_row_count_limit(100)
+            .set_write_batch_size(100)
+            .build();
+        let mut buf = vec![];
+        let mut writer = ArrowWriter::try_new(&mut buf, batch.schema(), Some(props)).unwrap();
+        writer.write(&batch).unwrap();
+        writer.close().unwrap();
+        Bytes::from(buf)
+    }
+
+    fn read_error(bytes: Bytes, threads: usize) -> ReadError {
+        let scan = ScanOptions::default();
+        let mut metrics = Metrics::new();
+        let error = read(
+            Input::Bytes(bytes),
+            Default::default(),
+            &scan,
+            threads,
+            &mut metrics,
+            |_, _| Ok(()),
+        )
+        .err()
+        .expect("the read fails");
+        *error.downcast::<ReadError>().unwrap()
+    }
+
+    #[test]
+    fn a_corrupt_page_is_located() {
+        let file = file();
+        let metadata = ParquetMetaDataReader::new()
+            .with_page_indexes(true)
+            .parse_and_finish(&file)
+            .unwrap();
+        let offset = metadata.offset_index().unwrap()[1][1].page_locations()[3].offset;
+
+        // Garbage in place of the page header.
+        let mut corrupt = file.to_vec();
+        corrupt[offset as usize..offset as usize + 8].fill(0xff);
+        let corrupt = Bytes::from(corrupt);
+
+        for threads in [1, 2] {
+            let error = read_error(corrupt.clone(), threads);
+            assert_eq!(error.stage, Stage::RowGroup(1));
+            assert_eq!(error.column.as_deref(), Some("b"));
+            assert_eq!(error.page_offset, Some(offset));
+        }
+    }
+
+    #[test]
+    fn a_truncated_footer_is_an_error() {
+        let file = file();
+        let len = file.len();
+
+        let error = read_error(file.slice(len - 4..), 1);
+        assert_eq!(error.stage, Stage::Footer);
+
+        // The footer is intact, but the metadata it points to is cut off.
+        let error = read_error(file.slice(len - 20..), 1);
+        assert_eq!(error.stage, Stage::Footer);
+        assert!(error.to_string().contains("exceeds the file"));
+
+        let mut no_magic = file.to_vec();
+        no_magic[len - 1] = 0;
+        let error = read_error(Bytes::from(no_magic), 1);
+        assert_eq!(error.stage, Stage::Footer);
+    }
+}
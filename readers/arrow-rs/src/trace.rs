@@ -0,0 +1,357 @@
+//! A [`ChunkReader`] that records every request the sync reader issues against the file.
+
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use arrow::array::{ArrayRef, RecordBatch, StringArray, UInt64Array};
+use bytes::Bytes;
+use parquet::arrow::ArrowWriter;
+use parquet::file::reader::{ChunkReader, Length};
+use serde::Serialize;
+
+use crate::metrics::nanos;
+
+/// What the reader was doing when it issued a request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Phase {
+    Footer,
+    PageIndex,
+    BloomFilter,
+    ColumnChunk,
+}
+
+impl Phase {
+    fn as_str(self) -> &'static str {
+        match self {
+            Phase::Footer => "footer",
+            Phase::PageIndex => "page_index",
+            Phase::BloomFilter => "bloom_filter",
+            Phase::ColumnChunk => "column_chunk",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct IoRequest {
+    pub phase: Phase,
+    /// `get_read` or `get_bytes`.
+    pub method: &'static str,
+    pub offset: u64,
+    /// Bytes read from the file, for `get_read` this includes the `BufReader` readahead.
+    pub length: u64,
+    /// Time spent in the file reads, for `get_read` summed over the life of the reader.
+    pub latency_nanos: usize,
+}
+
+/// The requests seen by every [`TracingReader`] sharing it.
+#[derive(Debug)]
+pub struct IoTrace {
+    phase: Mutex<Phase>,
+    requests: Mutex<Vec<IoRequest>>,
+}
+
+impl IoTrace {
+    pub fn new() -> Self {
+        Self {
+            phase: Mutex::new(Phase::Footer),
+            requests: Mutex::new(vec![]),
+        }
+    }
+
+    /// Attributes the following requests to `phase`.
+    pub fn set_phase(&self, phase: Phase) {
+        *self.phase.lock().unwrap() = phase;
+    }
+
+    fn record(&self, method: &'static str, offset: u64, length: u64, latency: Duration) {
+        let phase = *self.phase.lock().unwrap();
+        self.requests.lock().unwrap().push(IoRequest {
+            phase,
+            method,
+            offset,
+            length,
+            latency_nanos: nanos(latency),
+        });
+    }
+
+    /// Prints the number of requests and bytes per phase.
+    pub fn report(&self) {
+        let requests = self.requests.lock().unwrap();
+        let phases = [
+            Phase::Footer,
+            Phase::PageIndex,
+            Phase::BloomFilter,
+            Phase::ColumnChunk,
+        ];
+        let summary: Vec<String> = phases
+            .into_iter()
+            .map(|phase| {
+                let (count, bytes) = requests
+                    .iter()
+                    .filter(|r| r.phase == phase)
+                    .fold((0, 0), |(count, bytes), r| (count + 1, bytes + r.length));
+                format!("{} {} ({} bytes)", phase.as_str(), count, bytes)
+            })
+            .collect();
+        println!("io requests: {}", summary.join(", "));
+    }
+
+    /// Writes the trace to `dst`, as parquet if it ends in `.parquet` and as json otherwise.
+    pub fn save(&self, dst: impl AsRef<Path>) -> Result<(), Box<dyn std::error::Error>> {
+        let dst = dst.as_ref();
+        let requests = self.requests.lock().unwrap();
+        let file = File::create(dst)?;
+        if dst.extension().is_some_and(|ext| ext == "parquet") {
+            let column = |f: fn(&IoRequest) -> u64| -> ArrayRef {
+                Arc::new(requests.iter().map(f).collect::<UInt64Array>())
+            };
+            let batch = RecordBatch::try_from_iter([
+                (
+                    "phase",
+                    Arc::new(StringArray::from_iter_values(
+                        requests.iter().map(|r| r.phase.as_str()),
+                    )) as ArrayRef,
+                ),
+                (
+                    "method",
+                    Arc::new(StringArray::from_iter_values(
+                        requests.iter().map(|r| r.method),
+                    )),
+                ),
+                ("offset", column(|r| r.offset)),
+                ("length", column(|r| r.length)),
+                ("latency_nanos", column(|r| r.latency_nanos as u64)),
+            ])?;
+            let mut writer = ArrowWriter::try_new(file, batch.schema(), None)?;
+            writer.write(&batch)?;
+            writer.close()?;
+        } else {
+            serde_json::to_writer_pretty(file, &*requests)?;
+        }
+        Ok(())
+    }
+}
+
+impl Default for IoTrace {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Wraps a [`File`], recording each request into an [`IoTrace`].
+pub struct TracingReader {
+    file: File,
+    trace: Arc<IoTrace>,
+}
+
+impl TracingReader {
+    pub fn new(file: File, trace: Arc<IoTrace>) -> Self {
+        Self { file, trace }
+    }
+}
+
+impl Length for TracingReader {
+    fn len(&self) -> u64 {
+        self.file.len()
+    }
+}
+
+impl ChunkReader for TracingReader {
+    type T = BufReader<TracedRead>;
+
+    fn get_read(&self, start: u64) -> parquet::errors::Result<Self::T> {
+        let now = Instant::now();
+        let mut file = self.file.try_clone()?;
+        file.seek(SeekFrom::Start(start))?;
+        Ok(BufReader::new(TracedRead {
+            file,
+            trace: self.trace.clone(),
+            offset: start,
+            length: 0,
+            latency: now.elapsed(),
+        }))
+    }
+
+    fn get_bytes(&self, start: u64, length: usize) -> parquet::errors::Result<Bytes> {
+        let now = Instant::now();
+        let bytes = self.file.get_bytes(start, length)?;
+        self.trace
+            .record("get_bytes", start, length as u64, now.elapsed());
+        Ok(bytes)
+    }
+}
+
+/// The reader handed out by [`TracingReader::get_read`], recorded once it is dropped.
+pub struct TracedRead {
+    file: File,
+    trace: Arc<IoTrace>,
+    offset: u64,
+    length: u64,
+    latency: Duration,
+}
+
+impl Read for TracedRead {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let now = Instant::now();
+        let n = self.file.read(buf)?;
+        self.latency += now.elapsed();
+        self.length += n as u64;
+        Ok(n)
+    }
+}
+
+impl Drop for TracedRead {
+    fn drop(&mut self) {
+        self.trace
+            .record("get_read", self.offset, self.length, self.latency);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{AsArray, Int64Array};
+    use arrow::datatypes::UInt64Type;
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+    use serde_json::Value;
+
+    use crate::metrics::Metrics;
+    use crate::scan::ScanOptions;
+    use crate::sync_reader::{self, Input};
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("trace-{}-{}", std::process::id(), name))
+    }
+
+    /// Issues a request of every kind against a file of 100 bytes, in three phases.
+    fn traced() -> Arc<IoTrace> {
+        let path = temp_path("input");
+        std::fs::write(&path, (0..100).collect::<Vec<u8>>()).unwrap();
+        let trace = Arc::new(IoTrace::new());
+        let reader = TracingReader::new(File::open(&path).unwrap(), trace.clone());
+
+        assert_eq!(
+            reader.get_bytes(92, 8).unwrap().as_ref(),
+            &[92, 93, 94, 95, 96, 97, 98, 99]
+        );
+        trace.set_phase(Phase::PageIndex);
+        reader.get_bytes(50, 10).unwrap();
+        trace.set_phase(Phase::ColumnChunk);
+        let mut read = reader.get_read(10).unwrap();
+        let mut buf = [0; 5];
+        read.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, [10, 11, 12, 13, 14]);
+        drop(read);
+
+        std::fs::remove_file(&path).unwrap();
+        trace
+    }
+
+    #[test]
+    fn requests_are_attributed_to_the_phase_they_are_issued_in() {
+        let trace = traced();
+        let requests = trace.requests.lock().unwrap();
+        let summary: Vec<_> = requests
+            .iter()
+            .map(|r| (r.phase, r.method, r.offset))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (Phase::Footer, "get_bytes", 92),
+                (Phase::PageIndex, "get_bytes", 50),
+                (Phase::ColumnChunk, "get_read", 10),
+            ]
+        );
+        assert_eq!(requests[0].length, 8);
+        assert_eq!(requests[1].length, 10);
+        // The `BufReader` reads ahead to the end of the file.
+        assert_eq!(requests[2].length, 90);
+    }
+
+    #[test]
+    fn a_sync_read_starts_with_the_footer() {
+        let path = temp_path("file.parquet");
+        let batch = RecordBatch::try_from_iter([(
+            "v",
+            Arc::new(Int64Array::from_iter_values(0..1000)) as ArrayRef,
+        )])
+        .unwrap();
+        let mut writer =
+            ArrowWriter::try_new(File::create(&path).unwrap(), batch.schema(), None).unwrap();
+        writer.write(&batch).unwrap();
+        writer.close().unwrap();
+        let len = std::fs::metadata(&path).unwrap().len();
+
+        let trace = Arc::new(IoTrace::new());
+        let scan = ScanOptions {
+            limit: Some(10),
+            ..Default::default()
+        };
+        let input = Input::File(&path, Some(trace.clone()));
+        let mut metrics = Metrics::new();
+        sync_reader::read(input, Default::default(), &scan, 1, &mut metrics, |_, _| {
+            Ok(())
+        })
+        .unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let requests = trace.requests.lock().unwrap();
+        let phases: Vec<_> = requests.iter().map(|r| r.phase).collect();
+        assert_eq!(&phases[..2], [Phase::Footer, Phase::Footer]);
+        assert_eq!((requests[0].offset, requests[0].length), (len - 8, 8));
+        assert!(phases.contains(&Phase::PageIndex));
+        assert_eq!(phases.last(), Some(&Phase::ColumnChunk));
+        // The phases follow each other, and every page index request precedes the data.
+        assert!(phases.windows(2).all(|w| w[0] as u8 <= w[1] as u8));
+    }
+
+    #[test]
+    fn saves_as_json_or_parquet() {
+        let trace = traced();
+
+        let path = temp_path("trace.json");
+        trace.save(&path).unwrap();
+        let json: Value = serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let json = json.as_array().unwrap();
+        assert_eq!(json.len(), 3);
+        assert_eq!(json[1]["phase"], "page_index");
+        assert_eq!(json[2]["method"], "get_read");
+        assert_eq!(json[2]["offset"], 10);
+        assert!(json[2]["latency_nanos"].is_u64());
+
+        let path = temp_path("trace.parquet");
+        trace.save(&path).unwrap();
+        let reader = ParquetRecordBatchReaderBuilder::try_new(File::open(&path).unwrap())
+            .unwrap()
+            .build()
+            .unwrap();
+        let batches: Vec<_> = reader.map(Result::unwrap).collect();
+        std::fs::remove_file(&path).unwrap();
+        let batch = &batches[0];
+        let names: Vec<_> = batch
+            .schema()
+            .fields()
+            .iter()
+            .map(|f| f.name().clone())
+            .collect();
+        assert_eq!(
+            names,
+            ["phase", "method", "offset", "length", "latency_nanos"]
+        );
+        let phases: Vec<_> = batch
+            .column(0)
+            .as_string::<i32>()
+            .iter()
+            .flatten()
+            .collect();
+        assert_eq!(phases, ["footer", "page_index", "column_chunk"]);
+        let offsets = batch.column(2).as_primitive::<UInt64Type>().values();
+        assert_eq!(offsets.as_ref(), [92, 50, 10]);
+    }
+}
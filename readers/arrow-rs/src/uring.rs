@@ -0,0 +1,248 @@
+//! An io_uring backed [`AsyncFileReader`], behind the `io-uring` feature.
+//!
+//! The async reader fetches every column chunk of a row group with one
+//! `get_byte_ranges` call, which is submitted here as a single batch of `READ_FIXED`
+//! requests into one registered buffer, on a ring each reader creates once. Where the
+//! buffer cannot be registered, for its size or the locked memory limit, plain `READ`s are
+//! used instead. The wait for completions runs on tokio's blocking pool, and the returned
+//! [`Bytes`] are zero-copy slices of that buffer.
+
+use std::fs::File;
+use std::io;
+use std::ops::Range;
+use std::os::fd::AsRawFd;
+use std::path::Path;
+use std::sync::Arc;
+
+use bytes::Bytes;
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use io_uring::{opcode, types, IoUring};
+use parquet::arrow::async_reader::AsyncFileReader;
+use parquet::errors::{ParquetError, Result};
+use parquet::file::metadata::{ParquetMetaData, ParquetMetaDataReader};
+
+/// Most requests in flight on one ring.
+const MAX_ENTRIES: u32 = 1024;
+
+/// Longest single read, a read's length is a `u32` and Linux transfers less than 2 GiB at
+/// once anyway. Longer ranges are split.
+const MAX_READ: usize = 1 << 30;
+
+/// Largest buffer the kernel registers.
+const MAX_REGISTERED: usize = 1 << 30;
+
+pub struct UringFile {
+    file: Arc<File>,
+    size: usize,
+    /// Created by the first read and reused by the next ones, taken while one is running.
+    ring: Option<Ring>,
+}
+
+impl UringFile {
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = File::open(path)?;
+        let size = file.metadata()?.len() as usize;
+        Ok(Self {
+            file: Arc::new(file),
+            size,
+            ring: None,
+        })
+    }
+}
+
+impl AsyncFileReader for UringFile {
+    fn get_bytes(&mut self, range: Range<usize>) -> BoxFuture<'_, Result<Bytes>> {
+        async move { Ok(self.get_byte_ranges(vec![range]).await?.remove(0)) }.boxed()
+    }
+
+    fn get_byte_ranges(&mut self, ranges: Vec<Range<usize>>) -> BoxFuture<'_, Result<Vec<Bytes>>> {
+        let (file, ring) = (self.file.clone(), self.ring.take());
+        async move {
+            let (ring, result) = tokio::task::spawn_blocking(move || {
+                let mut ring = match ring {
+                    Some(ring) => ring,
+                    None => Ring::new()?,
+                };
+                let result = ring.read_ranges(&file, &ranges);
+                Ok::<_, io::Error>((ring, result))
+            })
+            .await
+            .map_err(|e| ParquetError::External(Box::new(e)))??;
+            // A failed read may leave requests behind on the ring, the next one starts over.
+            if result.is_ok() {
+                self.ring = Some(ring);
+            }
+            Ok(result?)
+        }
+        .boxed()
+    }
+
+    fn get_metadata(&mut self) -> BoxFuture<'_, Result<Arc<ParquetMetaData>>> {
+        let size = self.size;
+        async move {
+            let metadata = ParquetMetaDataReader::new()
+                .load_and_finish(&mut *self, size)
+                .await?;
+            Ok(Arc::new(metadata))
+        }
+        .boxed()
+    }
+}
+
+struct Ring {
+    ring: IoUring,
+    /// Whether to read into registered buffers, cleared once registering one fails.
+    fixed: bool,
+}
+
+impl Ring {
+    fn new() -> io::Result<Self> {
+        Ok(Self {
+            ring: IoUring::new(MAX_ENTRIES)?,
+            fixed: true,
+        })
+    }
+
+    /// Reads `ranges` of `file` into one buffer.
+    fn read_ranges(&mut self, file: &File, ranges: &[Range<usize>]) -> io::Result<Vec<Bytes>> {
+        let total = ranges.iter().map(|r| r.len()).sum();
+        let mut buf = vec![0u8; total];
+
+        // (buffer offset, file offset, length) of every read still to be done.
+        let pending: Vec<(usize, u64, usize)> = ranges
+            .iter()
+            .scan(0, |pos, r| {
+                let start = *pos;
+                *pos += r.len();
+                Some((start, r.clone()))
+            })
+            .flat_map(|(pos, r)| {
+                (0..r.len()).step_by(MAX_READ).map(move |i| {
+                    let len = (r.len() - i).min(MAX_READ);
+                    (pos + i, (r.start + i) as u64, len)
+                })
+            })
+            .collect();
+
+        if !pending.is_empty() {
+            let fixed = self.fixed && total <= MAX_REGISTERED && self.register(&mut buf);
+            let result = self.read(file, &mut buf, pending, fixed);
+            if fixed {
+                self.ring.submitter().unregister_buffers()?;
+            }
+            result?;
+        }
+
+        let buf = Bytes::from(buf);
+        Ok(ranges
+            .iter()
+            .scan(0, |pos, r| {
+                let bytes = buf.slice(*pos..*pos + r.len());
+                *pos += r.len();
+                Some(bytes)
+            })
+            .collect())
+    }
+
+    /// Registers `buf` with the ring, returning whether it worked.
+    fn register(&mut self, buf: &mut [u8]) -> bool {
+        let iovec = libc::iovec {
+            iov_base: buf.as_mut_ptr().cast(),
+            iov_len: buf.len(),
+        };
+        // Safety: `read_ranges` unregisters `buf` before it is moved or dropped.
+        match unsafe { self.ring.submitter().register_buffers(&[iovec]) } {
+            Ok(()) => true,
+            Err(_) => {
+                self.fixed = false;
+                false
+            }
+        }
+    }
+
+    /// Completes the `pending` reads into `buf`, which is registered if `fixed`.
+    fn read(
+        &mut self,
+        file: &File,
+        buf: &mut [u8],
+        mut pending: Vec<(usize, u64, usize)>,
+        fixed: bool,
+    ) -> io::Result<()> {
+        let fd = types::Fd(file.as_raw_fd());
+        let entries = self.ring.params().sq_entries() as usize;
+
+        while !pending.is_empty() {
+            let batch: Vec<_> = pending.drain(..pending.len().min(entries)).collect();
+            for (i, &(pos, offset, len)) in batch.iter().enumerate() {
+                // Safety: `pos + len` is within `buf`.
+                let ptr = unsafe { buf.as_mut_ptr().add(pos) };
+                // `len` is at most `MAX_READ`.
+                let entry = match fixed {
+                    true => opcode::ReadFixed::new(fd, ptr, len as u32, 0)
+                        .offset(offset)
+                        .build(),
+                    false => opcode::Read::new(fd, ptr, len as u32)
+                        .offset(offset)
+                        .build(),
+                };
+                // Safety: the target stays valid until its completion is reaped below.
+                unsafe { self.ring.submission().push(&entry.user_data(i as u64)) }
+                    .map_err(|_| io::Error::other("io_uring submission queue is full"))?;
+            }
+            self.ring.submit_and_wait(batch.len())?;
+
+            // Reap every completion before acting on any, the ring is reused.
+            let completions: Vec<_> = self
+                .ring
+                .completion()
+                .map(|cqe| (cqe.user_data() as usize, cqe.result()))
+                .collect();
+            for (i, result) in completions {
+                let (pos, offset, len) = batch[i];
+                match result {
+                    n if n < 0 => return Err(io::Error::from_raw_os_error(-n)),
+                    0 => return Err(io::ErrorKind::UnexpectedEof.into()),
+                    n if (n as usize) < len => {
+                        let n = n as usize;
+                        pending.push((pos + n, offset + n as u64, len - n));
+                    }
+                    _ => {}
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fixed_and_plain_reads() {
+        let path = std::env::temp_dir().join(format!("uring-{}", std::process::id()));
+        let data: Vec<u8> = (0..100_000u32).map(|i| i as u8).collect();
+        std::fs::write(&path, &data).unwrap();
+        let file = File::open(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        // More ranges than the ring has entries, and an empty one.
+        let mut ranges: Vec<_> = (0..2000).map(|i| i * 50..i * 50 + 7).collect();
+        ranges.push(10..10);
+        ranges.push(0..data.len());
+
+        let mut ring = Ring::new().unwrap();
+        for fixed in [true, false] {
+            ring.fixed = fixed;
+            let bytes = ring.read_ranges(&file, &ranges).unwrap();
+            for (range, bytes) in ranges.iter().zip(bytes) {
+                assert_eq!(&data[range.clone()], &bytes[..]);
+            }
+        }
+        let past_end = 0..data.len() + 1;
+        assert!(ring
+            .read_ranges(&file, std::slice::from_ref(&past_end))
+            .is_err());
+    }
+}
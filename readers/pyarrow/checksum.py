@@ -0,0 +1,220 @@
+"""The content digest of `--checksum`, encoded and hashed the same way as the arrow-rs
+reader's, see readers/arrow-rs/src/checksum.rs, so that the harness can compare the
+digests the readers print.
+
+Flat columns are hashed with numpy, one byte position of every value at a time. Values
+of nested columns are encoded one by one.
+"""
+
+import numpy as np
+import pyarrow as pa
+import pyarrow.compute as pc
+
+FNV_OFFSET = 0xCBF29CE484222325
+FNV_PRIME = 0x100000001B3
+MASK = (1 << 64) - 1
+
+
+def fnv1a(data) -> int:
+    h = FNV_OFFSET
+    for b in data:
+        h = ((h ^ b) * FNV_PRIME) & MASK
+    return h
+
+
+NULL_HASH = fnv1a(b"\x00")
+
+
+def normalize(array: pa.Array) -> pa.Array:
+    """Replaces dictionaries with their values and views with strings and binaries."""
+    if pa.types.is_dictionary(array.type):
+        return normalize(array.dictionary_decode())
+    if pa.types.is_string_view(array.type):
+        return array.cast(pa.string())
+    if pa.types.is_binary_view(array.type):
+        return array.cast(pa.binary())
+    return array
+
+
+def _hash_bytes(hashes: np.ndarray, columns: np.ndarray):
+    """Hashes byte `j` of every row into `hashes`, for every column `j` of `columns`."""
+    for j in range(columns.shape[1]):
+        hashes ^= columns[:, j]
+        hashes *= np.uint64(FNV_PRIME)
+
+
+def _fixed_width(array: pa.Array):
+    """Bytes per value of a fixed width type, `None` for any other type."""
+    t = array.type
+    if pa.types.is_boolean(t) or pa.types.is_nested(t):
+        return None
+    try:
+        width = t.bit_width
+    except ValueError:
+        return None
+    return width // 8
+
+
+def _fixed_width_values(array: pa.Array, width: int) -> np.ndarray:
+    data = np.frombuffer(array.buffers()[1], dtype=np.uint8)
+    start = array.offset * width
+    return data[start : start + len(array) * width].reshape(len(array), width)
+
+
+def _binary_values(array: pa.Array):
+    """Start and length of every value of a string or binary array, and its data."""
+    large = pa.types.is_large_binary(array.type) or pa.types.is_large_string(array.type)
+    offset_type = np.int64 if large else np.int32
+    offsets = np.frombuffer(array.buffers()[1], dtype=offset_type)
+    offsets = offsets[array.offset : array.offset + len(array) + 1].astype(np.int64)
+    data = array.buffers()[2]
+    data = np.frombuffer(data, dtype=np.uint8) if data is not None else np.empty(0, np.uint8)
+    return offsets[:-1], offsets[1:] - offsets[:-1], data
+
+
+def _is_binary(t: pa.DataType) -> bool:
+    return (
+        pa.types.is_string(t)
+        or pa.types.is_large_string(t)
+        or pa.types.is_binary(t)
+        or pa.types.is_large_binary(t)
+    )
+
+
+def value_hashes(array: pa.Array):
+    """The hash of every value of a flat array, `None` for a nested one."""
+    n = len(array)
+    t = array.type
+    if pa.types.is_null(t):
+        return np.full(n, NULL_HASH, dtype=np.uint64)
+
+    hashes = np.full(n, FNV_OFFSET, dtype=np.uint64)
+    _hash_bytes(hashes, np.ones((n, 1), dtype=np.uint8))
+    width = _fixed_width(array)
+    if pa.types.is_boolean(t):
+        values = pc.fill_null(array, False).to_numpy(zero_copy_only=False)
+        _hash_bytes(hashes, values.astype(np.uint8).reshape(n, 1))
+    elif width is not None:
+        _hash_bytes(hashes, _fixed_width_values(array, width))
+    elif _is_binary(t):
+        starts, lengths, data = _binary_values(array)
+        _hash_bytes(hashes, lengths.astype("<u8").view(np.uint8).reshape(n, 8))
+        rows = np.arange(n)
+        j = 0
+        while True:
+            rows = rows[lengths[rows] > j]
+            if len(rows) == 0:
+                break
+            h = hashes[rows]
+            h ^= data[starts[rows] + j]
+            h *= np.uint64(FNV_PRIME)
+            hashes[rows] = h
+            j += 1
+    else:
+        return None
+
+    if array.null_count:
+        valid = pc.is_valid(array).to_numpy(zero_copy_only=False)
+        hashes[~valid] = NULL_HASH
+    return hashes
+
+
+def _encoder(array: pa.Array):
+    """A function appending the encoding of value `i` of `array` to a bytearray."""
+    array = normalize(array)
+    t = array.type
+    if pa.types.is_null(t):
+        return lambda i, out: out.append(0)
+    valid = pc.is_valid(array).to_numpy(zero_copy_only=False)
+
+    width = _fixed_width(array)
+    if pa.types.is_boolean(t):
+        values = pc.fill_null(array, False).to_numpy(zero_copy_only=False)
+        encode = lambda i, out: out.append(int(values[i]))
+    elif width is not None:
+        values = _fixed_width_values(array, width)
+        encode = lambda i, out: out.extend(values[i].tobytes())
+    elif _is_binary(t):
+        starts, lengths, data = _binary_values(array)
+
+        def encode(i, out):
+            out.extend(int(lengths[i]).to_bytes(8, "little"))
+            out.extend(data[starts[i] : starts[i] + lengths[i]].tobytes())
+
+    elif pa.types.is_struct(t):
+        children = [_encoder(child) for child in array.flatten()]
+
+        def encode(i, out):
+            for child in children:
+                child(i, out)
+
+    elif pa.types.is_fixed_size_list(t):
+        size = t.list_size
+        element = _encoder(array.values)
+
+        def encode(i, out):
+            out.extend(size.to_bytes(8, "little"))
+            start = (array.offset + i) * size
+            for j in range(start, start + size):
+                element(j, out)
+
+    elif pa.types.is_list(t) or pa.types.is_large_list(t) or pa.types.is_map(t):
+        # Maps are lists of their entries, each a struct of the key and the value.
+        offsets = array.offsets.to_numpy()
+        element = _encoder(array.values)
+
+        def encode(i, out):
+            out.extend(int(offsets[i + 1] - offsets[i]).to_bytes(8, "little"))
+            for j in range(offsets[i], offsets[i + 1]):
+                element(j, out)
+
+    else:
+        raise NotImplementedError(f"checksum of {t}")
+
+    def encode_value(i, out):
+        if not valid[i]:
+            out.append(0)
+            return
+        out.append(1)
+        encode(i, out)
+
+    return encode_value
+
+
+def column_digest(column: pa.ChunkedArray) -> int:
+    """The wrapping sum of the hashes of every value of `column`."""
+    digest = 0
+    for chunk in column.chunks:
+        chunk = normalize(chunk)
+        hashes = value_hashes(chunk)
+        if hashes is not None:
+            digest += int(hashes.sum(dtype=np.uint64))
+        else:
+            encode = _encoder(chunk)
+            out = bytearray()
+            for i in range(len(chunk)):
+                out.clear()
+                encode(i, out)
+                digest += fnv1a(out)
+        digest &= MASK
+    return digest
+
+
+def digest(rows: int, columns) -> int:
+    """The digest of the row count and every `(name, digest)` column, in order."""
+    buf = bytearray(rows.to_bytes(8, "little"))
+    for name, d in columns:
+        buf.extend(name.encode())
+        buf.extend(d.to_bytes(8, "little"))
+    return fnv1a(buf)
+
+
+def report(table: pa.Table):
+    """Prints the digest of every column and of the whole table, like the arrow-rs reader."""
+    columns = [
+        (name, column_digest(table.column(i)))
+        for i, name in enumerate(table.column_names)
+    ]
+    for name, d in columns:
+        print(f"checksum {name}: {d:016x}")
+    print(f"checksum: {digest(table.num_rows, columns):016x} ({table.num_rows} rows)")
@@ -1,6 +1,8 @@
 import argparse
 import pyarrow.parquet as pq
 
+import checksum
+
 
 def main():
     # Set up argument parser
@@ -11,6 +13,11 @@ def main():
     parser.add_argument(
         "-p", "--print", action="store_true", help="Print the record batch"
     )
+    parser.add_argument(
+        "--checksum",
+        action="store_true",
+        help="Print a content digest of the table, comparable across readers",
+    )
 
     # Parse arguments
     args = parser.parse_args()
@@ -26,6 +33,9 @@ def main():
     if args.print:
         print(table)
 
+    if args.checksum:
+        checksum.report(table)
+
 
 if __name__ == "__main__":
     main()